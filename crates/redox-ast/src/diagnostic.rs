@@ -0,0 +1,295 @@
+use crate::Span;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => f.write_str("error"),
+            Self::Warning => f.write_str("warning"),
+            Self::Note => f.write_str("note"),
+        }
+    }
+}
+
+/// A message attached to a span of the source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// A message reported to the user, pointing at one or more locations in the source.
+/// The primary span is underlined with `^`, and secondary labels are underlined with `-`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+    /// The message shown under the primary span
+    pub label: Option<String>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+}
+
+/// Implemented by the errors of every stage of the compiler, so they can be reported in the same way
+pub trait ToDiagnostic {
+    fn to_diagnostic(&self) -> Diagnostic;
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>, span: Span) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            span,
+            label: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Severity::Error, message, span)
+    }
+
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Severity::Warning, message, span)
+    }
+
+    pub fn with_label(mut self, message: impl Into<String>) -> Self {
+        self.label = Some(message.into());
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Renders the diagnostic with the file name, line and column, and the excerpt of the source
+    /// that each label points to
+    pub fn render(&self, file: &SourceFile) -> String {
+        let mut result = String::new();
+        let (line, col) = file.line_col(self.span.start);
+        writeln!(result, "{}: {}", self.severity, self.message).unwrap();
+
+        // Every label, with the primary label first, as (span, message, underline character)
+        let mut labels = vec![(&self.span, self.label.as_deref().unwrap_or(""), '^')];
+        labels.extend(
+            self.labels
+                .iter()
+                .map(|label| (&label.span, label.message.as_str(), '-')),
+        );
+        labels.sort_by_key(|(span, _, _)| file.line_col(span.start).0);
+
+        let gutter = labels
+            .iter()
+            .map(|(span, _, _)| file.line_col(span.start).0.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let padding = " ".repeat(gutter);
+
+        writeln!(result, "{padding}--> {}:{line}:{col}", file.name).unwrap();
+        writeln!(result, "{padding} |").unwrap();
+        let mut last_line = None;
+        for (span, message, underline) in labels {
+            let (line, col) = file.line_col(span.start);
+            let text = file.line(line);
+            if last_line != Some(line) {
+                if last_line.is_some_and(|last| last + 1 < line) {
+                    writeln!(result, "{padding} ...").unwrap();
+                }
+                writeln!(result, "{line:>gutter$} | {text}").unwrap();
+                last_line = Some(line);
+            }
+            // Spans that cover multiple lines are only underlined until the end of the first line
+            let remaining = text.chars().count().saturating_sub(col - 1);
+            let width = file
+                .source
+                .get(span.clone())
+                .unwrap_or("")
+                .lines()
+                .next()
+                .map_or(0, |s| s.chars().count())
+                .clamp(1, remaining.max(1));
+            let marker = underline.to_string().repeat(width);
+            let message = if message.is_empty() {
+                String::new()
+            } else {
+                format!(" {message}")
+            };
+            writeln!(
+                result,
+                "{padding} | {}{marker}{message}",
+                " ".repeat(col - 1)
+            )
+            .unwrap();
+        }
+
+        if !self.notes.is_empty() || self.help.is_some() {
+            writeln!(result, "{padding} |").unwrap();
+        }
+        for note in &self.notes {
+            writeln!(result, "{padding} = note: {note}").unwrap();
+        }
+        if let Some(help) = &self.help {
+            writeln!(result, "{padding} = help: {help}").unwrap();
+        }
+        result
+    }
+}
+
+/// A source file, with the offsets of each line so spans can be converted to line and column
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name: impl Into<String>, source: impl Into<String>) -> Self {
+        let source = source.into();
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+        Self {
+            name: name.into(),
+            source,
+            line_starts,
+        }
+    }
+
+    /// Gets the line and column (both starting at 1) of a byte offset
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        let col = self.source[self.line_starts[line]..offset].chars().count() + 1;
+        (line + 1, col)
+    }
+
+    /// Gets the text of a line (starting at 1), without the line ending
+    pub fn line(&self, line: usize) -> &str {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .copied()
+            .unwrap_or(self.source.len());
+        self.source[start..end].trim_end_matches(['\n', '\r'])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_labels() {
+        let file = SourceFile::new(
+            "test.rx",
+            "fn foo() -> i32 {\n    let x = 1;\n\n    true\n}\n",
+        );
+        let diagnostic = Diagnostic::error("mismatched types", 38..42)
+            .with_label("expected `i32`")
+            .with_secondary(12..15, "because of this return type")
+            .with_secondary(3..6, "in this function");
+        let expected = "\
+error: mismatched types
+ --> test.rx:4:5
+  |
+1 | fn foo() -> i32 {
+  |             --- because of this return type
+  |    --- in this function
+  ...
+4 |     true
+  |     ^^^^ expected `i32`
+";
+        assert_eq!(diagnostic.render(&file), expected);
+    }
+
+    #[test]
+    fn test_render_notes() {
+        let file = SourceFile::new("test.rx", "let a = b;");
+        let diagnostic = Diagnostic::warning("unused variable", 4..5)
+            .with_note("first note")
+            .with_note("second note")
+            .with_help("remove it");
+        let expected = "\
+warning: unused variable
+ --> test.rx:1:5
+  |
+1 | let a = b;
+  |     ^
+  |
+  = note: first note
+  = note: second note
+  = help: remove it
+";
+        assert_eq!(diagnostic.render(&file), expected);
+    }
+
+    #[test]
+    fn test_render_multiline_span() {
+        let file = SourceFile::new("test.rx", "x = {\r\n    1\r\n};");
+        let diagnostic = Diagnostic::error("invalid block", 4..15).with_label("here");
+        let expected = "\
+error: invalid block
+ --> test.rx:1:5
+  |
+1 | x = {
+  |     ^ here
+";
+        assert_eq!(diagnostic.render(&file), expected);
+    }
+
+    #[test]
+    fn test_render_end_of_file() {
+        let file = SourceFile::new("test.rx", "fn foo() {");
+        let diagnostic = Diagnostic::error("unexpected end of file", 10..10);
+        let expected = "\
+error: unexpected end of file
+ --> test.rx:1:11
+  |
+1 | fn foo() {
+  |           ^
+";
+        assert_eq!(diagnostic.render(&file), expected);
+
+        // A span after the last line break points at the empty line after it
+        let file = SourceFile::new("test.rx", "fn foo() {\n");
+        let diagnostic = Diagnostic::error("unexpected end of file", 11..11).with_label("here");
+        let expected = "\
+error: unexpected end of file
+ --> test.rx:2:1
+  |
+2 | 
+  | ^ here
+";
+        assert_eq!(diagnostic.render(&file), expected);
+    }
+}
@@ -1,8 +1,12 @@
 use std::ops::Range;
 
+pub mod diagnostic;
 pub mod literal;
 pub mod types;
-pub use {literal::*, types::*};
+pub use {diagnostic::*, literal::*, types::*};
+
+/// A byte range into the source file
+pub type Span = Range<usize>;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Attribute {}
@@ -26,10 +30,24 @@ impl<T> Wrapped<T> {
     }
 }
 
+/// A value that isn't an expression, but still needs to point back to the source, such as a type
+/// annotation
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(value: T, span: Span) -> Self {
+        Self { value, span }
+    }
+}
+
 impl ExprKind {
     fn get_children(&self) -> Vec<Box<Expr>> {
         match self {
-            ExprKind::Literal(_) | ExprKind::Variable(_) => Vec::new(),
+            ExprKind::Literal(_) | ExprKind::Variable(_) | ExprKind::Continue { .. } => Vec::new(),
             ExprKind::Return(expr) => {
                 if let Some(expr) = expr {
                     vec![expr.clone()]
@@ -37,14 +55,138 @@ impl ExprKind {
                     Vec::new()
                 }
             }
+            ExprKind::Let(binding) => vec![binding.value.clone()],
+            ExprKind::Assign { target, value, .. } => vec![target.clone(), value.clone()],
+            ExprKind::Binary { lhs, rhs, .. } => vec![lhs.clone(), rhs.clone()],
+            ExprKind::Unary { expr, .. }
+            | ExprKind::Cast { expr, .. }
+            | ExprKind::Field { expr, .. }
+            | ExprKind::ArrayRepeat { value: expr, .. }
+            | ExprKind::Ref { expr, .. }
+            | ExprKind::Deref(expr) => vec![expr.clone()],
+            ExprKind::Index { expr, index } => vec![expr.clone(), index.clone()],
+            ExprKind::Array(elements) => elements.iter().cloned().map(Box::new).collect(),
+            ExprKind::MethodCall {
+                receiver,
+                arguments,
+                ..
+            } => std::iter::once(receiver.clone())
+                .chain(arguments.iter().cloned().map(Box::new))
+                .collect(),
+            ExprKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => std::iter::once(condition.clone())
+                .chain(then_block.expressions().cloned().map(Box::new))
+                .chain(
+                    else_block
+                        .iter()
+                        .flat_map(|block| block.expressions().cloned().map(Box::new)),
+                )
+                .collect(),
+            ExprKind::While {
+                condition, body, ..
+            } => std::iter::once(condition.clone())
+                .chain(body.expressions().cloned().map(Box::new))
+                .collect(),
+            ExprKind::Block(block)
+            | ExprKind::Unsafe(block)
+            | ExprKind::Loop { body: block, .. } => {
+                block.expressions().cloned().map(Box::new).collect()
+            }
+            ExprKind::Break { value, .. } => value.iter().cloned().collect(),
+            ExprKind::Call { callee, arguments } => std::iter::once(callee.clone())
+                .chain(arguments.iter().cloned().map(Box::new))
+                .collect(),
+            ExprKind::StructLiteral { fields, .. } => fields
+                .iter()
+                .map(|field| Box::new(field.value.clone()))
+                .collect(),
+            ExprKind::Tuple(elements) => elements.iter().cloned().map(Box::new).collect(),
+            ExprKind::Variant { fields, .. } => fields
+                .iter()
+                .map(|field| Box::new(field.value.clone()))
+                .collect(),
+            ExprKind::Match { scrutinee, arms } => std::iter::once(scrutinee.clone())
+                .chain(arms.iter().map(|arm| Box::new(arm.body.clone())))
+                .collect(),
             ExprKind::FunctionDef(function_def) => function_def
                 .body
-                .statements
-                .iter()
+                .expressions()
                 .map(|expr| Box::new(expr.clone()))
                 .collect(),
         }
     }
+    /// Gets mutable references to the direct children of the expression, in evaluation order
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            ExprKind::Literal(_) | ExprKind::Variable(_) | ExprKind::Continue { .. } => Vec::new(),
+            ExprKind::Return(expr) | ExprKind::Break { value: expr, .. } => {
+                expr.iter_mut().map(|expr| &mut **expr).collect()
+            }
+            ExprKind::Let(binding) => vec![&mut *binding.value],
+            ExprKind::Assign { target, value, .. } => vec![&mut **target, &mut **value],
+            ExprKind::Binary { lhs, rhs, .. } => vec![&mut **lhs, &mut **rhs],
+            ExprKind::Unary { expr, .. }
+            | ExprKind::Cast { expr, .. }
+            | ExprKind::Field { expr, .. }
+            | ExprKind::ArrayRepeat { value: expr, .. }
+            | ExprKind::Ref { expr, .. }
+            | ExprKind::Deref(expr) => vec![&mut **expr],
+            ExprKind::Index { expr, index } => vec![&mut **expr, &mut **index],
+            ExprKind::Array(elements) => elements.iter_mut().collect(),
+            ExprKind::MethodCall {
+                receiver,
+                arguments,
+                ..
+            } => std::iter::once(&mut **receiver)
+                .chain(arguments.iter_mut())
+                .collect(),
+            ExprKind::Block(block)
+            | ExprKind::Unsafe(block)
+            | ExprKind::Loop { body: block, .. } => block.expressions_mut().collect(),
+            ExprKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => std::iter::once(&mut **condition)
+                .chain(then_block.expressions_mut())
+                .chain(else_block.iter_mut().flat_map(Block::expressions_mut))
+                .collect(),
+            ExprKind::While {
+                condition, body, ..
+            } => std::iter::once(&mut **condition)
+                .chain(body.expressions_mut())
+                .collect(),
+            ExprKind::Call { callee, arguments } => std::iter::once(&mut **callee)
+                .chain(arguments.iter_mut())
+                .collect(),
+            ExprKind::StructLiteral { fields, .. } | ExprKind::Variant { fields, .. } => {
+                fields.iter_mut().map(|field| &mut field.value).collect()
+            }
+            ExprKind::Tuple(elements) => elements.iter_mut().collect(),
+            ExprKind::Match { scrutinee, arms } => std::iter::once(&mut **scrutinee)
+                .chain(arms.iter_mut().map(|arm| &mut arm.body))
+                .collect(),
+            ExprKind::FunctionDef(function_def) => function_def.body.expressions_mut().collect(),
+        }
+    }
+
+    /// Whether the expression ends with a block, so it doesn't need a semicolon to be used as a
+    /// statement
+    pub fn ends_with_block(&self) -> bool {
+        matches!(
+            self,
+            ExprKind::Block(_)
+                | ExprKind::Unsafe(_)
+                | ExprKind::If { .. }
+                | ExprKind::While { .. }
+                | ExprKind::Loop { .. }
+                | ExprKind::Match { .. }
+        )
+    }
+
     fn is_top_level(&self) -> bool {
         matches!(self, ExprKind::FunctionDef { .. })
     }
@@ -55,14 +197,280 @@ pub enum ExprKind {
     Literal(Literal),
     Variable(String),
     Return(Option<Box<Expr>>),
+    Let(LetBinding),
+    /// An assignment `target = value`, or a compound assignment like `target += value` if there is
+    /// an operator
+    Assign {
+        op: Option<BinaryOp>,
+        target: Box<Expr>,
+        value: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    },
+    Unary {
+        op: UnaryOp,
+        expr: Box<Expr>,
+    },
+    /// A conversion `expr as ty` between primitive types
+    Cast {
+        expr: Box<Expr>,
+        ty: Spanned<Type>,
+    },
+    /// A block used as an expression, its value is the value of the tail expression
+    Block(Block),
+    /// An `if` expression, `else if` is represented as an else block that only contains the
+    /// nested `if` as its value
+    If {
+        condition: Box<Expr>,
+        then_block: Block,
+        else_block: Option<Block>,
+    },
+    While {
+        label: Option<String>,
+        condition: Box<Expr>,
+        body: Block,
+    },
+    Loop {
+        label: Option<String>,
+        body: Block,
+    },
+    /// Exits the innermost loop, or the loop with the label, optionally with a value for `loop`
+    Break {
+        label: Option<String>,
+        value: Option<Box<Expr>>,
+    },
+    Continue {
+        label: Option<String>,
+    },
+    /// A function call, only functions that are referred to by name can be called
+    Call {
+        callee: Box<Expr>,
+        arguments: Vec<Expr>,
+    },
+    /// A struct literal `Name { field: value }`, the fields can be in any order
+    StructLiteral {
+        name: Spanned<String>,
+        fields: Vec<FieldInit>,
+    },
+    /// A tuple `(a, b)`, `()` is the empty tuple
+    Tuple(Vec<Expr>),
+    /// A value of an enum `Enum::Variant`, with its fields in parentheses or braces depending on
+    /// the kind of the variant. The fields of a tuple variant are named by their index
+    Variant {
+        path: VariantPath,
+        kind: VariantKind,
+        fields: Vec<FieldInit>,
+    },
+    /// A `match` expression, the value of the first arm whose pattern matches the scrutinee
+    Match {
+        scrutinee: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+    /// Accessing a field of a struct `expr.field`, or an element of a tuple `expr.0`
+    Field {
+        expr: Box<Expr>,
+        field: Spanned<String>,
+    },
+    /// An array literal `[a, b, c]`
+    Array(Vec<Expr>),
+    /// An array `[value; count]` that repeats a value, the count is an integer literal
+    ArrayRepeat {
+        value: Box<Expr>,
+        count: u64,
+    },
+    /// Indexing an array or slice `expr[index]`
+    Index {
+        expr: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// Calling a method `receiver.method(arguments)`, only the built-in `len` of arrays and
+    /// slices exists for now
+    MethodCall {
+        receiver: Box<Expr>,
+        method: Spanned<String>,
+        arguments: Vec<Expr>,
+    },
+    /// Borrowing a value `&expr`, or `&mut expr` if it is mutable
+    Ref {
+        mutable: bool,
+        expr: Box<Expr>,
+    },
+    /// Reading or writing the value behind a reference or raw pointer `*expr`
+    Deref(Box<Expr>),
+    /// A block `unsafe { ... }`, where raw pointers can be dereferenced
+    Unsafe(Block),
     FunctionDef(FunctionDef),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Short-circuiting logical and
+    And,
+    /// Short-circuiting logical or
+    Or,
+}
+
+impl BinaryOp {
+    pub fn is_arithmetic(&self) -> bool {
+        matches!(
+            self,
+            Self::Add | Self::Sub | Self::Mul | Self::Div | Self::Rem
+        )
+    }
+
+    pub fn is_bitwise(&self) -> bool {
+        matches!(
+            self,
+            Self::BitAnd | Self::BitOr | Self::BitXor | Self::Shl | Self::Shr
+        )
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Self::Eq | Self::Ne | Self::Lt | Self::Le | Self::Gt | Self::Ge
+        )
+    }
+
+    pub fn is_logical(&self) -> bool {
+        matches!(self, Self::And | Self::Or)
+    }
+}
+
+impl std::fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Add => f.write_str("+"),
+            Self::Sub => f.write_str("-"),
+            Self::Mul => f.write_str("*"),
+            Self::Div => f.write_str("/"),
+            Self::Rem => f.write_str("%"),
+            Self::BitAnd => f.write_str("&"),
+            Self::BitOr => f.write_str("|"),
+            Self::BitXor => f.write_str("^"),
+            Self::Shl => f.write_str("<<"),
+            Self::Shr => f.write_str(">>"),
+            Self::Eq => f.write_str("=="),
+            Self::Ne => f.write_str("!="),
+            Self::Lt => f.write_str("<"),
+            Self::Le => f.write_str("<="),
+            Self::Gt => f.write_str(">"),
+            Self::Ge => f.write_str(">="),
+            Self::And => f.write_str("&&"),
+            Self::Or => f.write_str("||"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    /// Arithmetic negation, `-x`
+    Neg,
+    /// Bitwise not for integers, `!x`
+    Not,
+}
+
+impl std::fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Neg => f.write_str("-"),
+            Self::Not => f.write_str("!"),
+        }
+    }
+}
+
+/// A `let` statement, declaring the local variables in the pattern
+#[derive(Debug, Clone, PartialEq)]
+pub struct LetBinding {
+    pub pattern: Pattern,
+    /// The type annotation, if there is one, otherwise the type is taken from the value
+    pub ty: Option<Spanned<Type>>,
+    pub value: Box<Expr>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TopLevelKind {
     Expr(Box<Expr>),
+    Struct(StructDef),
+    Enum(EnumDef),
+}
+
+/// A pattern that a value is matched against, which can bind parts of the value to variables
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternKind {
+    /// Binds the whole value to a variable
+    Binding {
+        name: String,
+        mutable: bool,
+    },
+    /// `_`, which matches anything without binding it
+    Wildcard,
+    Tuple(Vec<Pattern>),
+    /// A number or `bool` literal, which matches values that are equal to it. Negative numbers
+    /// like `-1` are `negated`
+    Literal {
+        value: Literal,
+        negated: bool,
+    },
+    /// A variant of an enum, with patterns for its fields. The fields of a tuple variant are named
+    /// by their index
+    Variant {
+        path: VariantPath,
+        kind: VariantKind,
+        fields: Vec<FieldPattern>,
+    },
+}
+
+/// A pattern for a field of an enum variant, `Shape::Rect { w }` is a shorthand for
+/// `Shape::Rect { w: w }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldPattern {
+    pub name: String,
+    pub pattern: Pattern,
+    pub span: Span,
+}
+
+/// A variant of an enum referred to by its path, `Enum::Variant`
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantPath {
+    pub enum_name: Spanned<String>,
+    pub variant: Spanned<String>,
 }
 
+impl std::fmt::Display for VariantPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}::{}", self.enum_name.value, self.variant.value)
+    }
+}
+
+/// An arm of a `match` expression, `pattern => body`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Expr,
+    pub span: Span,
+}
+
+pub type Pattern = Wrapped<PatternKind>;
 pub type Expr = Wrapped<ExprKind>;
 pub type TopLevel = Wrapped<TopLevelKind>;
 pub type Ast = Vec<TopLevel>;
@@ -77,25 +485,103 @@ impl TopLevel {
 #[derive(Debug, Clone, PartialEq)]
 pub struct FunctionDef {
     pub name: String,
-    pub arguments: Vec<(String, Type)>,
-    pub return_ty: Option<Type>,
+    pub arguments: Vec<Argument>,
+    pub return_ty: Option<Spanned<Type>>,
     pub attributes: Attributes,
     pub body: Block,
+    /// The span of the signature, from the `fn` keyword to the end of the return type
+    pub span: Span,
+}
+
+/// A struct declaration `struct Name { field: Type }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructDef {
+    pub name: String,
+    pub fields: Vec<StructField>,
+    /// The span of the name in the declaration
+    pub span: Span,
+}
+
+/// An enum declaration `enum Name { Variant, Variant(Type), Variant { field: Type } }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumDef {
+    pub name: String,
+    pub variants: Vec<Variant>,
+    /// The span of the name in the declaration
+    pub span: Span,
+}
+
+/// A variant of an enum declaration. The fields of a tuple variant are named by their index, like
+/// the elements of a tuple
+#[derive(Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub name: String,
+    pub kind: VariantKind,
+    pub fields: Vec<StructField>,
+    pub span: Span,
+}
+
+/// How the fields of an enum variant are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariantKind {
+    /// A variant without fields, `Variant`
+    Unit,
+    /// `Variant(A, B)`
+    Tuple,
+    /// `Variant { a: A, b: B }`
+    Struct,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructField {
+    pub name: String,
+    pub ty: Spanned<Type>,
+    pub span: Span,
+}
+
+/// A field in a struct literal, `Point { x }` is a shorthand for `Point { x: x }`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldInit {
+    pub name: String,
+    pub value: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Argument {
+    pub name: String,
+    pub ty: Spanned<Type>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Block {
     pub statements: Vec<Expr>,
+    /// The final expression without a semicolon, which is the value of the block
+    pub tail: Option<Box<Expr>>,
     pub attributes: Attributes,
+    /// The span of the block, including the braces
+    pub span: Span,
 }
 
 impl Block {
-    pub const fn empty() -> Self {
+    pub const fn empty(span: Span) -> Self {
         Self {
             statements: Vec::new(),
+            tail: None,
             attributes: Vec::new(),
+            span,
         }
     }
+
+    /// Iterates over the statements, followed by the tail expression
+    pub fn expressions(&self) -> impl Iterator<Item = &Expr> {
+        self.statements.iter().chain(self.tail.as_deref())
+    }
+
+    pub fn expressions_mut(&mut self) -> impl Iterator<Item = &mut Expr> {
+        self.statements.iter_mut().chain(self.tail.as_deref_mut())
+    }
 }
 
 pub mod utils {
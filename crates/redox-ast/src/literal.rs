@@ -1,30 +1,103 @@
-use crate::{NumberType, Type};
+use crate::{NumberKind, NumberType, Type};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberValue {
+    Int(u128),
+    Float(f64),
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct NumberLiteral {
-    pub kind: NumberType,
-    pub value: u64,
+    pub value: NumberValue,
+    /// The type from the suffix, like `u8` in `1u8`
+    pub suffix: Option<NumberType>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     Number(NumberLiteral),
+    Bool(bool),
+    /// A string literal with its escapes replaced, which is a `&str`
+    Str(String),
+    /// A byte string literal `b"..."`, which is a `&[u8]`
+    ByteStr(Vec<u8>),
 }
 
 impl Literal {
     pub fn ty(&self) -> Type {
         match self {
-            Self::Number(number) => Type::Number(number.kind.clone()),
+            Self::Number(number) => Type::Number(number.ty()),
+            Self::Bool(_) => Type::Bool,
+            Self::Str(_) => Type::Str,
+            Self::ByteStr(_) => Type::Slice {
+                ty: Box::new(Type::Number(NumberType::U8)),
+                mutable: false,
+            },
         }
     }
 }
 
 impl NumberLiteral {
-    pub const fn new(kind: NumberType, value: u64) -> Self {
-        Self { kind, value }
+    pub const fn new(value: NumberValue, suffix: Option<NumberType>) -> Self {
+        Self { value, suffix }
+    }
+
+    /// An integer literal without a suffix
+    pub const fn int(value: u128) -> Self {
+        Self::new(NumberValue::Int(value), None)
+    }
+
+    /// A float literal without a suffix
+    pub const fn float(value: f64) -> Self {
+        Self::new(NumberValue::Float(value), None)
     }
 
-    pub const fn int32(value: u64) -> Self {
-        Self::new(NumberType::I32, value)
+    /// The type of the literal, which is the suffix if it has one, otherwise `i32` for integers and
+    /// `f64` for floats
+    pub fn ty(&self) -> NumberType {
+        match (&self.suffix, self.value) {
+            (Some(suffix), _) => suffix.clone(),
+            (None, NumberValue::Int(_)) => NumberType::I32,
+            (None, NumberValue::Float(_)) => NumberType::F64,
+        }
+    }
+
+    /// Whether the value can be represented by `ty`. If the literal is `negated`, signed integers
+    /// can hold one more than their maximum, so the minimum like `-128i8` is allowed
+    pub fn fits(&self, ty: &NumberType, negated: bool) -> bool {
+        match (self.value, &ty.kind) {
+            (NumberValue::Float(value), NumberKind::Float) => {
+                ty.bits == 64 || (value as f32).is_finite()
+            }
+            (NumberValue::Float(_), _) => false,
+            (NumberValue::Int(_), NumberKind::Float) => true,
+            (NumberValue::Int(value), NumberKind::Unsigned) => {
+                ty.bits == 128 || value < 1 << ty.bits
+            }
+            (NumberValue::Int(value), NumberKind::Signed) => {
+                let limit = 1 << (ty.bits - 1);
+                value < limit || (negated && value == limit)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for NumberValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{value}"),
+            // Debug formatting keeps the decimal point, so `1.0` isn't printed as `1`
+            Self::Float(value) => write!(f, "{value:?}"),
+        }
+    }
+}
+
+impl std::fmt::Display for NumberLiteral {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.value)?;
+        if let Some(suffix) = &self.suffix {
+            write!(f, "{suffix}")?;
+        }
+        Ok(())
     }
 }
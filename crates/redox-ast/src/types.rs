@@ -11,6 +11,9 @@ pub enum NumberKind {
 pub struct NumberType {
     pub kind: NumberKind,
     pub bits: u32,
+    /// `isize` and `usize`, which are as wide as a pointer. They have the same size as the integer
+    /// type with `bits`, but are still different types
+    pub pointer_sized: bool,
 }
 
 impl From<NumberType> for Type {
@@ -20,34 +23,69 @@ impl From<NumberType> for Type {
 }
 
 impl NumberType {
+    /// The width of a pointer, only 64-bit targets are supported for now
+    pub const POINTER_BITS: u32 = 64;
+
+    pub const fn new(kind: NumberKind, bits: u32) -> Self {
+        Self {
+            kind,
+            bits,
+            pointer_sized: false,
+        }
+    }
+
     pub const I32: Self = Self {
         kind: NumberKind::Signed,
         bits: 32,
+        pointer_sized: false,
     };
 
     pub const I64: Self = Self {
         kind: NumberKind::Signed,
         bits: 64,
+        pointer_sized: false,
+    };
+
+    pub const U8: Self = Self {
+        kind: NumberKind::Unsigned,
+        bits: 8,
+        pointer_sized: false,
     };
 
     pub const U32: Self = Self {
         kind: NumberKind::Unsigned,
         bits: 32,
+        pointer_sized: false,
     };
 
     pub const U64: Self = Self {
         kind: NumberKind::Unsigned,
         bits: 64,
+        pointer_sized: false,
+    };
+
+    pub const ISIZE: Self = Self {
+        kind: NumberKind::Signed,
+        bits: Self::POINTER_BITS,
+        pointer_sized: true,
+    };
+
+    pub const USIZE: Self = Self {
+        kind: NumberKind::Unsigned,
+        bits: Self::POINTER_BITS,
+        pointer_sized: true,
     };
 
     pub const F32: Self = Self {
         kind: NumberKind::Float,
         bits: 32,
+        pointer_sized: false,
     };
 
     pub const F64: Self = Self {
         kind: NumberKind::Float,
         bits: 64,
+        pointer_sized: false,
     };
 }
 
@@ -55,12 +93,120 @@ impl NumberType {
 pub enum Type {
     Tuple(Vec<Type>),
     Number(NumberType),
+    Bool,
+    /// A struct or enum declared in the module, referred to by its name
+    Named(String),
+    /// An array `[T; N]` with a fixed number of elements
+    Array(Box<Type>, u64),
+    /// A borrowed slice `&[T]` or `&mut [T]`, a pointer to elements and their number, which is
+    /// only known at runtime
+    Slice {
+        ty: Box<Type>,
+        mutable: bool,
+    },
+    /// A borrowed string `&str`, a pointer to UTF-8 bytes and their number, like a `&[u8]` that
+    /// always holds valid UTF-8
+    Str,
+    /// A reference `&T` or `&mut T`, a pointer that the type checker knows is valid
+    Ref {
+        ty: Box<Type>,
+        mutable: bool,
+    },
+    /// A raw pointer `*const T` or `*mut T`, which can only be dereferenced in an `unsafe` block
+    Pointer {
+        ty: Box<Type>,
+        mutable: bool,
+    },
+    /// The type of expressions that never produce a value, like `break` or a `loop` that is never
+    /// exited
+    Never,
+    /// A number type that isn't known yet, like the type of a literal without a suffix. The type
+    /// checker replaces it with a concrete type before the AST is passed on
+    Infer(InferVar),
+}
+
+/// A type variable that is resolved by the type checker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InferVar {
+    pub id: usize,
+    pub kind: InferKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InferKind {
+    /// Any integer type, `i32` if nothing else is required
+    Int,
+    /// Any float type, `f64` if nothing else is required
+    Float,
+}
+
+impl InferKind {
+    /// Whether a number type can be used for this kind of variable
+    pub fn accepts(&self, ty: &NumberType) -> bool {
+        match self {
+            InferKind::Int => ty.kind != NumberKind::Float,
+            InferKind::Float => ty.kind == NumberKind::Float,
+        }
+    }
+
+    /// The type that is used if the variable isn't constrained
+    pub fn default_type(&self) -> NumberType {
+        match self {
+            InferKind::Int => NumberType::I32,
+            InferKind::Float => NumberType::F64,
+        }
+    }
+}
+
+impl std::fmt::Display for NumberType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let prefix = match self.kind {
+            NumberKind::Signed => 'i',
+            NumberKind::Unsigned => 'u',
+            NumberKind::Float => 'f',
+        };
+        if self.pointer_sized {
+            write!(f, "{prefix}size")
+        } else {
+            write!(f, "{prefix}{}", self.bits)
+        }
+    }
 }
 
 impl std::fmt::Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        // TODO: Do proper type display
-        write!(f, "{:?}", self)
+        match self {
+            Type::Tuple(types) => {
+                f.write_str("(")?;
+                for (idx, ty) in types.iter().enumerate() {
+                    if idx != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{ty}")?;
+                }
+                // A tuple with a single element needs a trailing comma, like `(i32,)`
+                if types.len() == 1 {
+                    f.write_str(",")?;
+                }
+                f.write_str(")")
+            }
+            Type::Number(ty) => write!(f, "{ty}"),
+            Type::Bool => f.write_str("bool"),
+            Type::Named(name) => f.write_str(name),
+            Type::Array(ty, len) => write!(f, "[{ty}; {len}]"),
+            Type::Slice { ty, mutable: false } => write!(f, "&[{ty}]"),
+            Type::Slice { ty, mutable: true } => write!(f, "&mut [{ty}]"),
+            Type::Str => f.write_str("&str"),
+            Type::Ref { ty, mutable: false } => write!(f, "&{ty}"),
+            Type::Ref { ty, mutable: true } => write!(f, "&mut {ty}"),
+            Type::Pointer { ty, mutable: false } => write!(f, "*const {ty}"),
+            Type::Pointer { ty, mutable: true } => write!(f, "*mut {ty}"),
+            Type::Never => f.write_str("!"),
+            Type::Infer(var) => match var.kind {
+                InferKind::Int => f.write_str("{integer}"),
+                InferKind::Float => f.write_str("{float}"),
+            },
+        }
     }
 }
 
@@ -82,13 +228,42 @@ impl FromStr for Type {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
-            "i32" => Ok(Type::Number(NumberType::I32)),
-            "i64" => Ok(Type::Number(NumberType::I64)),
-            "u32" => Ok(Type::Number(NumberType::U32)),
-            "u64" => Ok(Type::Number(NumberType::U64)),
-            "f32" => Ok(Type::Number(NumberType::F32)),
-            "f64" => Ok(Type::Number(NumberType::F64)),
-            _ => Err(format!("Struct types are not yet supported")),
+            "bool" => Ok(Type::Bool),
+            _ => match NumberType::from_str(s) {
+                Ok(ty) => Ok(Type::Number(ty)),
+                // Names like `i7` are a mistake in a number type rather than a declared type
+                Err(err) if looks_like_number_type(s) => Err(err),
+                Err(_) => Ok(Type::Named(s.to_string())),
+            },
+        }
+    }
+}
+
+/// Whether the name is a number kind followed by a size, like `u` or `i7`
+fn looks_like_number_type(s: &str) -> bool {
+    let Some(bits) = s.strip_prefix(['i', 'u', 'f']) else {
+        return false;
+    };
+    bits == "size" || bits.chars().all(|c| c.is_ascii_digit())
+}
+
+impl FromStr for NumberType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid number type `{s}`");
+        let (kind, bits) = match s.split_at_checked(1).ok_or_else(invalid)? {
+            ("i", bits) => (NumberKind::Signed, bits),
+            ("u", bits) => (NumberKind::Unsigned, bits),
+            ("f", bits) => (NumberKind::Float, bits),
+            _ => return Err(invalid()),
+        };
+        match (&kind, bits) {
+            (NumberKind::Signed, "size") => Ok(Self::ISIZE),
+            (NumberKind::Unsigned, "size") => Ok(Self::USIZE),
+            (NumberKind::Signed | NumberKind::Unsigned, "8" | "16" | "32" | "64" | "128")
+            | (NumberKind::Float, "32" | "64") => Ok(Self::new(kind, bits.parse().unwrap())),
+            _ => Err(invalid()),
         }
     }
 }
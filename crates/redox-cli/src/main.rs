@@ -6,6 +6,7 @@ use redox_codegen::{
 };
 use std::io::Read;
 
+use redox_ast::{SourceFile, ToDiagnostic};
 use redox_ir_generator::{IrGenerator, ModuleOps};
 use redox_lexer::{LexerTrait, Token};
 use redox_parser::Parser as RedoxParser;
@@ -16,6 +17,9 @@ struct Args {
     input: InputPath,
     #[clap(short, long, default_value = "0")]
     verbose: u32,
+    /// Don't check that indices are in bounds of the array or slice they index
+    #[clap(long)]
+    no_bounds_checks: bool,
 }
 
 fn main() {
@@ -58,14 +62,23 @@ fn main() {
         }
     }
 
-    let mut ast = RedoxParser::with_source(&contents).parse().unwrap();
+    let file = SourceFile::new(args.input.path().to_string_lossy(), contents.as_str());
+
+    let mut ast = RedoxParser::with_source(&contents)
+        .parse()
+        .into_result()
+        .unwrap_or_else(|errors| report(&file, &errors));
     if args.verbose >= 1 {
         let path = std::path::PathBuf::from("build/main.rxast");
         std::fs::write(path, redox_ast::utils::to_string(&ast)).unwrap();
     }
 
     let mut type_checker = TypeChecker::new();
-    type_checker.type_check(&mut ast).unwrap();
+    let result = type_checker.type_check(&mut ast);
+    for warning in type_checker.warnings() {
+        eprintln!("{}", warning.to_diagnostic().render(&file));
+    }
+    result.unwrap_or_else(|err| report(&file, &[err]));
 
     if args.verbose >= 1 {
         let path = std::path::PathBuf::from("build/main_typed.rxast");
@@ -73,12 +86,15 @@ fn main() {
     }
 
     let mut ir_generator = IrGenerator::new();
-    let module = ir_generator.generate_module(
-        ModuleOps {
-            name: "main".to_string(),
-        },
-        ast,
-    );
+    let module = ir_generator
+        .generate_module(
+            ModuleOps {
+                name: "main".to_string(),
+                bounds_checks: !args.no_bounds_checks,
+            },
+            ast,
+        )
+        .unwrap_or_else(|err| report(&file, &[err]));
 
     if args.verbose >= 1 {
         let path = std::path::PathBuf::from("build/main.rxir");
@@ -88,13 +104,28 @@ fn main() {
     let context = LLVMContext::default();
     let mut codegen = LLVMCodegenBackend::new(&context);
 
-    codegen.gen_module(&module).unwrap();
-    codegen
-        .write_intermediate(std::path::PathBuf::from("build/main.ll"))
-        .unwrap();
     codegen
-        .write_object(std::path::PathBuf::from("build/main.o"))
-        .unwrap();
+        .gen_module(&module)
+        .and_then(|_| codegen.write_intermediate(std::path::PathBuf::from("build/main.ll")))
+        .and_then(|_| codegen.write_object(std::path::PathBuf::from("build/main.o")))
+        .unwrap_or_else(|err| {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        });
 
     // Then we link with clang
 }
+
+/// Prints the diagnostics of the errors, and exits with a failure status
+fn report<E: ToDiagnostic>(file: &SourceFile, errors: &[E]) -> ! {
+    for error in errors {
+        eprintln!("{}", error.to_diagnostic().render(file));
+    }
+    let plural = if errors.len() == 1 { "" } else { "s" };
+    eprintln!(
+        "error: could not compile `{}` due to {} previous error{plural}",
+        file.name,
+        errors.len()
+    );
+    std::process::exit(1);
+}
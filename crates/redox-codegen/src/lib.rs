@@ -1,12 +1,33 @@
 use std::path::PathBuf;
 
+#[derive(Debug, Clone)]
+pub enum CodegenError {
+    /// An RXIR construct that the backend can't lower yet
+    Unsupported(String),
+    /// An error reported by the backend itself, such as a module that fails verification
+    Backend(String),
+}
+
+impl std::fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsupported(construct) => {
+                write!(f, "{construct} are not supported by the backend yet")
+            }
+            Self::Backend(err) => f.write_str(err),
+        }
+    }
+}
+
+impl std::error::Error for CodegenError {}
+
 /// This is the trait that codegen backends must implement
 /// It is intended to have one per module
 pub trait CodegenBackend {
-    fn gen_module(&mut self, module: &rxir::Module) -> Result<(), String>;
+    fn gen_module(&mut self, module: &rxir::Module) -> Result<(), CodegenError>;
 
-    fn write_intermediate(&mut self, path: PathBuf) -> Result<(), String>;
-    fn write_object(&mut self, path: PathBuf) -> Result<(), String>;
+    fn write_intermediate(&mut self, path: PathBuf) -> Result<(), CodegenError>;
+    fn write_object(&mut self, path: PathBuf) -> Result<(), CodegenError>;
 }
 
 pub mod llvm;
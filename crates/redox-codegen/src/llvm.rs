@@ -1,18 +1,29 @@
-use crate::CodegenBackend;
+use crate::{CodegenBackend, CodegenError};
 use std::{collections::HashMap, path::PathBuf};
 
 use inkwell::{
-    builder::Builder,
+    basic_block::BasicBlock,
+    builder::{Builder, BuilderError},
     context::Context,
+    intrinsics::Intrinsic,
     llvm_sys::LLVMCallConv,
     module::Module,
     targets::{Target, TargetMachine},
     types::{BasicMetadataTypeEnum, BasicType, BasicTypeEnum},
-    values::{BasicValue, BasicValueEnum},
-    AddressSpace,
+    values::{
+        BasicMetadataValueEnum, BasicValue, BasicValueEnum, FloatValue, FunctionValue, IntValue,
+        PhiValue, PointerValue,
+    },
+    AddressSpace, FloatPredicate, IntPredicate,
 };
 use rxir::Operand;
 
+impl From<BuilderError> for CodegenError {
+    fn from(err: BuilderError) -> Self {
+        Self::Backend(err.to_string())
+    }
+}
+
 pub struct LLVMContext {
     context: Context,
 }
@@ -29,16 +40,28 @@ pub struct LLVMCodegenBackend<'ctx> {
     context: &'ctx Context,
     builder: Builder<'ctx>,
     module: Module<'ctx>,
+    /// The machine that the code is generated for, its data layout decides the sizes of types
+    target_machine: TargetMachine,
+    /// The LLVM globals of the constants in the module that is being compiled
+    globals: HashMap<rxir::GlobalId, PointerValue<'ctx>>,
 }
 
 struct BlockMeta<'ctx> {
     variables: HashMap<rxir::TempVarId, BasicValueEnum<'ctx>>,
+    /// The LLVM block of every RXIR block in the function
+    blocks: HashMap<rxir::BlockId, BasicBlock<'ctx>>,
+    entry: BasicBlock<'ctx>,
+    /// Phis whose incoming values still need to be added, once every block has been compiled
+    phis: Vec<(PhiValue<'ctx>, Vec<(Operand, rxir::BlockId)>)>,
 }
 
 impl<'ctx> BlockMeta<'ctx> {
-    pub fn new() -> Self {
+    pub fn new(entry: BasicBlock<'ctx>) -> Self {
         Self {
             variables: HashMap::new(),
+            blocks: HashMap::new(),
+            entry,
+            phis: Vec::new(),
         }
     }
 }
@@ -48,27 +71,61 @@ impl<'ctx> LLVMCodegenBackend<'ctx> {
         let builder = context.context.create_builder();
         // TODO: This will be the name of the executable / library
         let module = context.context.create_module("main");
+        let target_machine = Self::create_target_machine();
+        Self::set_target(&module, &target_machine);
         Self {
             context: &context.context,
             builder,
             module,
+            target_machine,
+            globals: HashMap::new(),
+        }
+    }
+
+    fn create_target_machine() -> TargetMachine {
+        // TODO: Set target, optimization level, etc...
+        let cpu = "generic";
+        let features = "";
+        let optimization = inkwell::OptimizationLevel::Default;
+        let triple = TargetMachine::get_default_triple();
+        #[cfg(target_arch = "aarch64")]
+        {
+            Target::initialize_aarch64(&inkwell::targets::InitializationConfig::default());
         }
+        let target = Target::from_triple(&triple).unwrap();
+        target
+            .create_target_machine(
+                &triple,
+                &cpu,
+                features,
+                optimization,
+                inkwell::targets::RelocMode::Default,
+                inkwell::targets::CodeModel::Default,
+            )
+            .unwrap()
+    }
+
+    /// The layout of structs depends on the target, so it matches the C layout
+    fn set_target(module: &Module<'ctx>, target_machine: &TargetMachine) {
+        module.set_triple(&target_machine.get_triple());
+        module.set_data_layout(&target_machine.get_target_data().get_data_layout());
     }
 }
 
 impl<'ctx> LLVMCodegenBackend<'ctx> {
-    fn compile_function(
+    /// Adds the function to the module without a body, so calls can refer to it before it is
+    /// compiled
+    fn declare_function(
         &self,
-        module: &rxir::Module,
         function: &rxir::Function,
-    ) -> Result<(), String> {
+    ) -> Result<FunctionValue<'ctx>, CodegenError> {
         // FIXME: This is a hack to get the correct signature for the main function, we should
         // probbaly make our own entrypoint and avoid libc stuff
         let args: Vec<BasicMetadataTypeEnum> = function
             .arguments
             .iter()
-            .map(|(_id, ty)| self.llvm_type(ty).unwrap().into())
-            .collect();
+            .map(|(_id, ty)| self.llvm_basic_type(ty).map(|ty| ty.into()))
+            .collect::<Result<_, _>>()?;
         let fn_type = if let Some(ty) = self.llvm_type(&function.return_ty) {
             ty.fn_type(&args, false)
         } else {
@@ -80,19 +137,54 @@ impl<'ctx> LLVMCodegenBackend<'ctx> {
         // For now we just C calling convention beacuse we are using clang to link
         llvm_fn.set_linkage(inkwell::module::Linkage::External);
         llvm_fn.set_call_conventions(LLVMCallConv::LLVMCCallConv as u32);
+        Ok(llvm_fn)
+    }
+
+    fn compile_function(
+        &self,
+        module: &rxir::Module,
+        function: &rxir::Function,
+    ) -> Result<(), CodegenError> {
+        let llvm_fn = self
+            .module
+            .get_function(function.signature.as_str())
+            .expect("Functions are declared before they are compiled");
         let entry = self.context.append_basic_block(llvm_fn, "entry");
-        let mut meta = BlockMeta::new();
+        let mut meta = BlockMeta::new(entry);
         for (idx, (id, _ty)) in function.arguments.iter().enumerate() {
             let value = llvm_fn.get_nth_param(idx as u32).unwrap();
             meta.variables.insert(id.clone(), value);
         }
-        self.builder.position_at_end(entry);
-        let block = module.blocks.get(&function.entry).unwrap();
-        self.compile_block(block, &meta)?;
+        // Every block is created up front, so jumps can refer to blocks that come later
+        let blocks = rxir::utils::get_related_blocks(module, function);
+        for id in &blocks {
+            let block = if *id == function.entry {
+                entry
+            } else {
+                self.context.append_basic_block(llvm_fn, &id.to_string())
+            };
+            meta.blocks.insert(id.clone(), block);
+        }
+        for id in &blocks {
+            self.builder.position_at_end(meta.blocks[id]);
+            let block = module.blocks.get(id).unwrap();
+            self.compile_block(block, &mut meta)?;
+        }
+        // The incoming values of a phi can come from blocks that are compiled after it
+        for (phi, incoming) in std::mem::take(&mut meta.phis) {
+            for (value, block) in &incoming {
+                let value = self.operand_value(value, &meta)?;
+                phi.add_incoming(&[(&value as &dyn BasicValue, meta.blocks[block])]);
+            }
+        }
         Ok(())
     }
 
-    fn compile_block(&self, block: &rxir::Block, meta: &BlockMeta) -> Result<(), String> {
+    fn compile_block(
+        &self,
+        block: &rxir::Block,
+        meta: &mut BlockMeta<'ctx>,
+    ) -> Result<(), CodegenError> {
         for instruction in &block.instructions {
             self.compile_instruction(block, instruction, meta)?;
         }
@@ -101,105 +193,533 @@ impl<'ctx> LLVMCodegenBackend<'ctx> {
 
     fn compile_instruction(
         &self,
-        block: &rxir::Block,
+        _block: &rxir::Block,
         instruction: &rxir::Instruction,
-        meta: &BlockMeta,
-    ) -> Result<(), String> {
+        meta: &mut BlockMeta<'ctx>,
+    ) -> Result<(), CodegenError> {
         match instruction {
-            rxir::Instruction::Alloca { dest, ty } => unimplemented!(),
+            rxir::Instruction::Alloca { dest, ty } => {
+                // Stack slots are placed at the start of the entry block, so they are only
+                // allocated once even if they are declared in a loop, and LLVM can promote them
+                // to registers
+                let builder = self.context.create_builder();
+                match meta.entry.get_first_instruction() {
+                    Some(first) => builder.position_before(&first),
+                    None => builder.position_at_end(meta.entry),
+                }
+                let ptr = builder.build_alloca(self.llvm_basic_type(ty)?, &dest.to_string())?;
+                meta.variables.insert(dest.clone(), ptr.into());
+            }
             rxir::Instruction::Return { value } => match value {
-                None => self.builder.build_return(None).unwrap(),
-                Some(Operand::Immediate { ty, value }) => self
-                    .builder
-                    .build_return(Some(&self.llvm_value(ty, *value)?))
-                    .unwrap(),
-                Some(Operand::TempVar { ty: _, id }) => {
-                    let value = meta.variables.get(id).unwrap();
-                    self.builder.build_return(Some(value)).unwrap()
+                None => {
+                    self.builder.build_return(None)?;
+                }
+                Some(value) => {
+                    let value = self.operand_value(value, meta)?;
+                    self.builder.build_return(Some(&value))?;
                 }
             },
-
-            rxir::Instruction::Load { dest, src } => unimplemented!(),
-            rxir::Instruction::Store { dest, src } => unimplemented!(),
+            rxir::Instruction::Load { dest, src, ty } => {
+                let ptr = meta.variables.get(src).unwrap().into_pointer_value();
+                let value =
+                    self.builder
+                        .build_load(self.llvm_basic_type(ty)?, ptr, &dest.to_string())?;
+                meta.variables.insert(dest.clone(), value);
+            }
+            rxir::Instruction::Store { dest, src } => {
+                let ptr = meta.variables.get(dest).unwrap().into_pointer_value();
+                let value = self.operand_value(src, meta)?;
+                self.builder.build_store(ptr, value)?;
+            }
+            rxir::Instruction::Binary { dest, op, lhs, rhs } => {
+                // Both operands have the same type
+                let ty = lhs.ty();
+                let lhs = self.operand_value(lhs, meta)?;
+                let rhs = self.operand_value(rhs, meta)?;
+                let name = dest.to_string();
+                let value = if let rxir::Type::Float(_) = ty {
+                    self.compile_float_binary(
+                        *op,
+                        lhs.into_float_value(),
+                        rhs.into_float_value(),
+                        &name,
+                    )?
+                } else {
+                    let signed = matches!(ty, rxir::Type::Signed(_));
+                    self.compile_int_binary(
+                        *op,
+                        lhs.into_int_value(),
+                        rhs.into_int_value(),
+                        signed,
+                        &name,
+                    )?
+                };
+                meta.variables.insert(dest.clone(), value);
+            }
+            rxir::Instruction::Compare { dest, op, lhs, rhs } => {
+                let ty = lhs.ty();
+                let lhs = self.operand_value(lhs, meta)?;
+                let rhs = self.operand_value(rhs, meta)?;
+                let name = dest.to_string();
+                let value = if let rxir::Type::Float(_) = ty {
+                    // Ordered comparisons are false if either operand is NaN, except for `!=`
+                    let predicate = match op {
+                        rxir::CompareOp::Eq => FloatPredicate::OEQ,
+                        rxir::CompareOp::Ne => FloatPredicate::UNE,
+                        rxir::CompareOp::Lt => FloatPredicate::OLT,
+                        rxir::CompareOp::Le => FloatPredicate::OLE,
+                        rxir::CompareOp::Gt => FloatPredicate::OGT,
+                        rxir::CompareOp::Ge => FloatPredicate::OGE,
+                    };
+                    self.builder.build_float_compare(
+                        predicate,
+                        lhs.into_float_value(),
+                        rhs.into_float_value(),
+                        &name,
+                    )?
+                } else {
+                    let signed = matches!(ty, rxir::Type::Signed(_));
+                    let predicate = match (op, signed) {
+                        (rxir::CompareOp::Eq, _) => IntPredicate::EQ,
+                        (rxir::CompareOp::Ne, _) => IntPredicate::NE,
+                        (rxir::CompareOp::Lt, true) => IntPredicate::SLT,
+                        (rxir::CompareOp::Le, true) => IntPredicate::SLE,
+                        (rxir::CompareOp::Gt, true) => IntPredicate::SGT,
+                        (rxir::CompareOp::Ge, true) => IntPredicate::SGE,
+                        (rxir::CompareOp::Lt, false) => IntPredicate::ULT,
+                        (rxir::CompareOp::Le, false) => IntPredicate::ULE,
+                        (rxir::CompareOp::Gt, false) => IntPredicate::UGT,
+                        (rxir::CompareOp::Ge, false) => IntPredicate::UGE,
+                    };
+                    self.builder.build_int_compare(
+                        predicate,
+                        lhs.into_int_value(),
+                        rhs.into_int_value(),
+                        &name,
+                    )?
+                };
+                meta.variables.insert(dest.clone(), value.into());
+            }
+            rxir::Instruction::Branch {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                let condition = self.operand_value(condition, meta)?.into_int_value();
+                self.builder.build_conditional_branch(
+                    condition,
+                    meta.blocks[then_block],
+                    meta.blocks[else_block],
+                )?;
+            }
+            rxir::Instruction::Jump { target } => {
+                self.builder
+                    .build_unconditional_branch(meta.blocks[target])?;
+            }
+            rxir::Instruction::Switch {
+                value,
+                default,
+                cases,
+            } => {
+                let value = self.operand_value(value, meta)?.into_int_value();
+                let cases: Vec<_> = cases
+                    .iter()
+                    .map(|(case, block)| {
+                        let case = value
+                            .get_type()
+                            .const_int_arbitrary_precision(&[*case as u64, (*case >> 64) as u64]);
+                        (case, meta.blocks[block])
+                    })
+                    .collect();
+                self.builder
+                    .build_switch(value, meta.blocks[default], &cases)?;
+            }
+            rxir::Instruction::Unreachable => {
+                self.builder.build_unreachable()?;
+            }
+            rxir::Instruction::Trap => {
+                let trap = Intrinsic::find("llvm.trap")
+                    .and_then(|trap| trap.get_declaration(&self.module, &[]))
+                    .expect("LLVM has a trap intrinsic");
+                self.builder.build_call(trap, &[], "")?;
+                // The trap never returns
+                self.builder.build_unreachable()?;
+            }
+            rxir::Instruction::Phi { dest, ty, incoming } => {
+                let phi = self
+                    .builder
+                    .build_phi(self.llvm_basic_type(ty)?, &dest.to_string())?;
+                meta.variables.insert(dest.clone(), phi.as_basic_value());
+                meta.phis.push((phi, incoming.clone()));
+            }
+            rxir::Instruction::Call {
+                dest,
+                function,
+                arguments,
+                return_ty: _,
+            } => {
+                let llvm_fn = self
+                    .module
+                    .get_function(function.as_str())
+                    .expect("Called functions are declared in the module");
+                let arguments: Vec<BasicMetadataValueEnum> = arguments
+                    .iter()
+                    .map(|argument| self.operand_value(argument, meta).map(|value| value.into()))
+                    .collect::<Result<_, _>>()?;
+                // Calls returning void can't have a name
+                let name = dest.as_ref().map(ToString::to_string).unwrap_or_default();
+                let call = self.builder.build_call(llvm_fn, &arguments, &name)?;
+                call.set_call_convention(LLVMCallConv::LLVMCCallConv as u32);
+                if let (Some(dest), Some(value)) = (dest, call.try_as_basic_value().left()) {
+                    meta.variables.insert(dest.clone(), value);
+                }
+            }
+            rxir::Instruction::Unary { dest, op, operand } => {
+                let ty = operand.ty();
+                let operand = self.operand_value(operand, meta)?;
+                let name = dest.to_string();
+                let value: BasicValueEnum = match (op, ty) {
+                    (rxir::UnaryOp::Neg, rxir::Type::Float(_)) => self
+                        .builder
+                        .build_float_neg(operand.into_float_value(), &name)?
+                        .into(),
+                    (rxir::UnaryOp::Neg, _) => self
+                        .builder
+                        .build_int_neg(operand.into_int_value(), &name)?
+                        .into(),
+                    (rxir::UnaryOp::Not, _) => self
+                        .builder
+                        .build_not(operand.into_int_value(), &name)?
+                        .into(),
+                };
+                meta.variables.insert(dest.clone(), value);
+            }
+            rxir::Instruction::FieldAddress {
+                dest,
+                src,
+                ty,
+                index,
+            } => {
+                let ptr = meta.variables.get(src).unwrap().into_pointer_value();
+                let value = match ty {
+                    // Every member of a union starts at the start of the union
+                    rxir::Type::Union(_) => ptr,
+                    _ => self.builder.build_struct_gep(
+                        self.llvm_basic_type(ty)?,
+                        ptr,
+                        *index,
+                        &dest.to_string(),
+                    )?,
+                };
+                meta.variables.insert(dest.clone(), value.into());
+            }
+            rxir::Instruction::ElementAddress {
+                dest,
+                src,
+                ty,
+                index,
+            } => {
+                let ptr = meta.variables.get(src).unwrap().into_pointer_value();
+                let index = self.operand_value(index, meta)?.into_int_value();
+                // SAFETY: The IR generator checks that the index is in bounds, unless bounds
+                // checks are disabled
+                let value = unsafe {
+                    self.builder.build_in_bounds_gep(
+                        self.llvm_basic_type(ty)?,
+                        ptr,
+                        &[index],
+                        &dest.to_string(),
+                    )?
+                };
+                meta.variables.insert(dest.clone(), value.into());
+            }
+            rxir::Instruction::ExtractField { dest, value, index } => {
+                let value = self.operand_value(value, meta)?.into_struct_value();
+                let value = self
+                    .builder
+                    .build_extract_value(value, *index, &dest.to_string())?;
+                meta.variables.insert(dest.clone(), value);
+            }
+            rxir::Instruction::Cast {
+                dest,
+                op,
+                value,
+                ty,
+            } => {
+                let value = self.operand_value(value, meta)?;
+                let ty = self.llvm_basic_type(ty)?;
+                let name = dest.to_string();
+                let value: BasicValueEnum = match op {
+                    rxir::CastOp::Trunc => self
+                        .builder
+                        .build_int_truncate(value.into_int_value(), ty.into_int_type(), &name)?
+                        .into(),
+                    rxir::CastOp::ZExt => self
+                        .builder
+                        .build_int_z_extend(value.into_int_value(), ty.into_int_type(), &name)?
+                        .into(),
+                    rxir::CastOp::SExt => self
+                        .builder
+                        .build_int_s_extend(value.into_int_value(), ty.into_int_type(), &name)?
+                        .into(),
+                    rxir::CastOp::FpToSi => self
+                        .builder
+                        .build_float_to_signed_int(
+                            value.into_float_value(),
+                            ty.into_int_type(),
+                            &name,
+                        )?
+                        .into(),
+                    rxir::CastOp::FpToUi => self
+                        .builder
+                        .build_float_to_unsigned_int(
+                            value.into_float_value(),
+                            ty.into_int_type(),
+                            &name,
+                        )?
+                        .into(),
+                    rxir::CastOp::SiToFp => self
+                        .builder
+                        .build_signed_int_to_float(
+                            value.into_int_value(),
+                            ty.into_float_type(),
+                            &name,
+                        )?
+                        .into(),
+                    rxir::CastOp::UiToFp => self
+                        .builder
+                        .build_unsigned_int_to_float(
+                            value.into_int_value(),
+                            ty.into_float_type(),
+                            &name,
+                        )?
+                        .into(),
+                    rxir::CastOp::FpTrunc => self
+                        .builder
+                        .build_float_trunc(value.into_float_value(), ty.into_float_type(), &name)?
+                        .into(),
+                    rxir::CastOp::FpExt => self
+                        .builder
+                        .build_float_ext(value.into_float_value(), ty.into_float_type(), &name)?
+                        .into(),
+                };
+                meta.variables.insert(dest.clone(), value);
+            }
         };
         Ok(())
     }
 
-    fn llvm_value(&self, ty: &rxir::Type, value: u64) -> Result<BasicValueEnum<'ctx>, String> {
+    /// Compiles an arithmetic or bitwise operation on integers, `signed` selects the division,
+    /// remainder and right shift that are used
+    fn compile_int_binary(
+        &self,
+        op: rxir::BinaryOp,
+        lhs: IntValue<'ctx>,
+        rhs: IntValue<'ctx>,
+        signed: bool,
+        name: &str,
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let value = match op {
+            rxir::BinaryOp::Add => self.builder.build_int_add(lhs, rhs, name)?,
+            rxir::BinaryOp::Sub => self.builder.build_int_sub(lhs, rhs, name)?,
+            rxir::BinaryOp::Mul => self.builder.build_int_mul(lhs, rhs, name)?,
+            rxir::BinaryOp::Div if signed => self.builder.build_int_signed_div(lhs, rhs, name)?,
+            rxir::BinaryOp::Div => self.builder.build_int_unsigned_div(lhs, rhs, name)?,
+            rxir::BinaryOp::Rem if signed => self.builder.build_int_signed_rem(lhs, rhs, name)?,
+            rxir::BinaryOp::Rem => self.builder.build_int_unsigned_rem(lhs, rhs, name)?,
+            rxir::BinaryOp::And => self.builder.build_and(lhs, rhs, name)?,
+            rxir::BinaryOp::Or => self.builder.build_or(lhs, rhs, name)?,
+            rxir::BinaryOp::Xor => self.builder.build_xor(lhs, rhs, name)?,
+            rxir::BinaryOp::Shl => self.builder.build_left_shift(lhs, rhs, name)?,
+            // Signed integers use an arithmetic shift, which keeps the sign bit
+            rxir::BinaryOp::Shr => self.builder.build_right_shift(lhs, rhs, signed, name)?,
+        };
+        Ok(value.into())
+    }
+
+    fn compile_float_binary(
+        &self,
+        op: rxir::BinaryOp,
+        lhs: FloatValue<'ctx>,
+        rhs: FloatValue<'ctx>,
+        name: &str,
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        let value = match op {
+            rxir::BinaryOp::Add => self.builder.build_float_add(lhs, rhs, name)?,
+            rxir::BinaryOp::Sub => self.builder.build_float_sub(lhs, rhs, name)?,
+            rxir::BinaryOp::Mul => self.builder.build_float_mul(lhs, rhs, name)?,
+            rxir::BinaryOp::Div => self.builder.build_float_div(lhs, rhs, name)?,
+            rxir::BinaryOp::Rem => self.builder.build_float_rem(lhs, rhs, name)?,
+            _ => return Err(CodegenError::Unsupported(format!("`{op}` on floats"))),
+        };
+        Ok(value.into())
+    }
+
+    fn operand_value(
+        &self,
+        operand: &Operand,
+        meta: &BlockMeta<'ctx>,
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
+        match operand {
+            Operand::Immediate { ty, value } => self.llvm_value(ty, *value),
+            Operand::TempVar { ty: _, id } => Ok(*meta.variables.get(id).unwrap()),
+            Operand::Global { ty: _, id } => Ok(self.globals[id].into()),
+        }
+    }
+
+    fn llvm_value(
+        &self,
+        ty: &rxir::Type,
+        value: u128,
+    ) -> Result<BasicValueEnum<'ctx>, CodegenError> {
         match ty {
-            rxir::Type::Void | rxir::Type::Pointer(_) => unreachable!(),
-            rxir::Type::Signed32 => {
-                // We need to bitcast the value to i32
-                Ok(self.context.i32_type().const_int(value, false).into())
-            }
+            rxir::Type::Void
+            | rxir::Type::Pointer(_)
+            | rxir::Type::Struct(_)
+            | rxir::Type::Union(_)
+            | rxir::Type::Array(..) => Err(CodegenError::Unsupported(format!(
+                "immediates of type `{ty}`"
+            ))),
+            rxir::Type::Bool => Ok(self
+                .context
+                .bool_type()
+                .const_int(value as u64, false)
+                .into()),
+            // The value is split into 64-bit words, least significant first, and truncated to the
+            // width of the type
+            rxir::Type::Signed(_) | rxir::Type::Unsigned(_) => Ok(self
+                .llvm_basic_type(ty)?
+                .into_int_type()
+                .const_int_arbitrary_precision(&[value as u64, (value >> 64) as u64])
+                .into()),
+            rxir::Type::Float(_) => Ok(self
+                .llvm_basic_type(ty)?
+                .into_float_type()
+                .const_float(f64::from_bits(value as u64))
+                .into()),
         }
     }
 
+    /// Adds a constant to the module as a private global, which is placed in read-only memory
+    fn compile_global(&mut self, module: &rxir::Module, global: &rxir::Global) {
+        let value = self.context.const_string(&global.data, false);
+        let name = format!("{}.const.{}", module.name, global.id.0);
+        let llvm_global = self.module.add_global(value.get_type(), None, &name);
+        llvm_global.set_initializer(&value);
+        llvm_global.set_constant(true);
+        llvm_global.set_linkage(inkwell::module::Linkage::Private);
+        // Only the contents matter, so LLVM can merge identical constants
+        llvm_global.set_unnamed_addr(true);
+        self.globals
+            .insert(global.id.clone(), llvm_global.as_pointer_value());
+    }
+
+    /// Gets the LLVM type of a value, which can't be void
+    fn llvm_basic_type(&self, ty: &rxir::Type) -> Result<BasicTypeEnum<'ctx>, CodegenError> {
+        self.llvm_type(ty)
+            .ok_or_else(|| CodegenError::Unsupported(format!("values of type `{ty}`")))
+    }
+
     fn llvm_type(&self, ty: &rxir::Type) -> Option<BasicTypeEnum<'ctx>> {
         match ty {
             rxir::Type::Void => None,
-            rxir::Type::Signed32 => Some(self.context.i32_type().into()),
+            rxir::Type::Bool => Some(self.context.bool_type().into()),
+            rxir::Type::Signed(bits) | rxir::Type::Unsigned(bits) => {
+                Some(self.context.custom_width_int_type(*bits).into())
+            }
+            rxir::Type::Float(32) => Some(self.context.f32_type().into()),
+            rxir::Type::Float(_) => Some(self.context.f64_type().into()),
             rxir::Type::Pointer(_) => Some(self.context.ptr_type(AddressSpace::default()).into()),
+            // LLVM lays out structs that aren't packed like C does, using the data layout of the
+            // target
+            rxir::Type::Struct(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|ty| self.llvm_type(ty))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(self.context.struct_type(&fields, false).into())
+            }
+            // LLVM has no unions, so a union is a struct with its most aligned member, padded
+            // with bytes to the size of its largest member
+            rxir::Type::Union(members) => {
+                let members = members
+                    .iter()
+                    .map(|ty| self.llvm_type(ty))
+                    .collect::<Option<Vec<_>>>()?;
+                let target_data = self.target_machine.get_target_data();
+                let size = members
+                    .iter()
+                    .map(|ty| target_data.get_abi_size(ty))
+                    .max()
+                    .unwrap_or(0);
+                let mut fields = Vec::new();
+                if let Some(aligned) = members
+                    .iter()
+                    .max_by_key(|ty| target_data.get_abi_alignment(*ty))
+                {
+                    fields.push(*aligned);
+                    let padding = size - target_data.get_abi_size(aligned);
+                    if padding > 0 {
+                        fields.push(self.context.i8_type().array_type(padding as u32).into());
+                    }
+                }
+                Some(self.context.struct_type(&fields, false).into())
+            }
+            rxir::Type::Array(ty, len) => {
+                let len = u32::try_from(*len).ok()?;
+                Some(self.llvm_type(ty)?.array_type(len).into())
+            }
         }
     }
 }
 
 impl CodegenBackend for LLVMCodegenBackend<'_> {
-    fn gen_module(&mut self, module: &rxir::Module) -> Result<(), String> {
+    fn gen_module(&mut self, module: &rxir::Module) -> Result<(), CodegenError> {
         let llvm_module = self.context.create_module(module.name.as_str());
+        Self::set_target(&llvm_module, &self.target_machine);
+        self.globals.clear();
+        for global in &module.globals {
+            self.compile_global(module, global);
+        }
+        for function in &module.functions {
+            self.declare_function(function)?;
+        }
         for function in &module.functions {
             self.compile_function(module, function)?;
         }
 
         // Verification
-        llvm_module.verify().map_err(|err| err.to_string())?;
+        llvm_module
+            .verify()
+            .map_err(|err| CodegenError::Backend(err.to_string()))?;
         for function in llvm_module.get_functions() {
             if !function.verify(false) {
-                return Err("Function verification failed".to_string());
+                return Err(CodegenError::Backend(
+                    "Function verification failed".to_string(),
+                ));
             }
         }
 
         // Linking into the module
         self.module
             .link_in_module(llvm_module)
-            .map_err(|err| err.to_string())?;
+            .map_err(|err| CodegenError::Backend(err.to_string()))?;
 
         Ok(())
     }
 
-    fn write_intermediate(&mut self, path: PathBuf) -> Result<(), String> {
+    fn write_intermediate(&mut self, path: PathBuf) -> Result<(), CodegenError> {
         let string = self.module.print_to_string().to_string();
-        std::fs::write(path, string).map_err(|err| err.to_string())
+        std::fs::write(path, string).map_err(|err| CodegenError::Backend(err.to_string()))
     }
 
-    fn write_object(&mut self, path: PathBuf) -> Result<(), String> {
-        // TODO: Set target, optimization level, etc...
-        let cpu = "generic";
-        let features = "";
-        let optimization = inkwell::OptimizationLevel::Default;
-        let triple = TargetMachine::get_default_triple();
-        #[cfg(target_arch = "aarch64")]
-        {
-            Target::initialize_aarch64(&inkwell::targets::InitializationConfig::default());
-        }
-        let target = Target::from_triple(&triple).unwrap();
-        let target_machine = target
-            .create_target_machine(
-                &triple,
-                &cpu,
-                features,
-                optimization,
-                inkwell::targets::RelocMode::Default,
-                inkwell::targets::CodeModel::Default,
-            )
-            .unwrap();
-
-        target_machine
+    fn write_object(&mut self, path: PathBuf) -> Result<(), CodegenError> {
+        self.target_machine
             .write_to_file(
                 &self.module,
                 inkwell::targets::FileType::Object,
                 path.as_path(),
             )
-            .map_err(|err| err.to_string())
+            .map_err(|err| CodegenError::Backend(err.to_string()))
     }
 }
@@ -1,140 +1,2838 @@
-use redox_ast::{Block, Expr, ExprKind, Literal, TopLevel, TopLevelKind, Type as AstType};
-use rxir::{BlockId, Module, ModuleBuilder, Operand, TempVarId};
-use std::collections::HashMap;
+use redox_ast::{
+    BinaryOp, Block, Diagnostic, Expr, ExprKind, Literal, NumberType, NumberValue, Pattern,
+    PatternKind, Span, ToDiagnostic, TopLevel, TopLevelKind, Type as AstType, UnaryOp, VariantPath,
+};
+use rxir::{BlockId, Instruction, Module, ModuleBuilder, Operand, TempVarId};
+use std::collections::{HashMap, HashSet};
 
-// Now it has been type checked, any additional errors are panics
-pub struct IrGenerator {}
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum IrGenError {
+    /// A construct that passed type checking, but can't be lowered to RXIR yet
+    Unsupported(String, Span),
+}
+
+impl std::fmt::Display for IrGenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unsupported(construct, _) => write!(f, "Unsupported: {construct}"),
+        }
+    }
+}
+
+impl ToDiagnostic for IrGenError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            Self::Unsupported(construct, span) => Diagnostic::error(
+                format!("{construct} are not supported by the IR generator yet"),
+                span.clone(),
+            )
+            .with_label("not supported"),
+        }
+    }
+}
+
+/// The names and types of the fields of a struct or variant, in the order they are declared
+type Fields = Vec<(String, AstType)>;
+
+// Now it has been type checked, any errors are either unsupported constructs or panics
+pub struct IrGenerator {
+    /// The fields of every struct in the module
+    structs: HashMap<String, Fields>,
+    /// The variants of every enum in the module and their fields, in the order they are declared
+    enums: HashMap<String, Vec<(String, Fields)>>,
+    /// Whether indexing checks that the index is in bounds, and traps if it isn't
+    bounds_checks: bool,
+}
 
 pub struct ModuleOps {
     pub name: String,
+    /// Check that indices are in bounds of the array or slice they index
+    pub bounds_checks: bool,
 }
 
 pub struct BlockMeta {
-    pub variables: HashMap<String, TempVarId>,
+    /// Every named variable (arguments included) lives in a stack slot, this maps the name to the
+    /// pointer returned by the `alloca`. Variables of type `()` have no value, so they have no slot
+    pub variables: HashMap<String, Option<TempVarId>>,
+    /// The loops that enclose the current expression, the innermost loop is last
+    loops: Vec<LoopMeta>,
 }
 
 impl BlockMeta {
     pub fn new() -> Self {
         Self {
             variables: HashMap::new(),
+            loops: Vec::new(),
         }
     }
+
+    /// Gets the stack slot of a variable, or `None` if it has type `()`
+    fn slot(&self, name: &str) -> Option<TempVarId> {
+        // The type checker rejects variables that aren't declared in an enclosing scope, and every
+        // binding it accepts is recorded here, including the ones of type `()`
+        self.variables
+            .get(name)
+            .cloned()
+            .expect("The type checker checks that variables are declared before they are used")
+    }
+}
+
+/// A value in memory that is matched against patterns
+#[derive(Clone)]
+struct Place {
+    ptr: TempVarId,
+    ty: AstType,
+}
+
+/// An arm of a `match` while its patterns are being checked, with a pattern for each place that is
+/// still left to check. `None` matches anything
+struct MatchRow<'a> {
+    patterns: Vec<Option<&'a Pattern>>,
+    arm: usize,
+    /// The binding patterns that the arm has matched so far, and the places they are bound to
+    bindings: Vec<(&'a Pattern, Place)>,
+}
+
+/// The block that runs the body of a `match` arm, and the stack slots of the variables it binds
+struct MatchArmMeta {
+    block: BlockId,
+    slots: HashMap<String, Option<TempVarId>>,
+}
+
+/// The blocks that `break` and `continue` jump to
+struct LoopMeta {
+    label: Option<String>,
+    continue_block: BlockId,
+    exit_block: BlockId,
+    /// The values that the loop is exited with, and the blocks that exit it
+    incoming: Vec<(Operand, BlockId)>,
 }
 
 impl IrGenerator {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            bounds_checks: true,
+        }
     }
 
-    pub fn generate_module(&mut self, ops: ModuleOps, ast: Vec<TopLevel>) -> Module {
+    pub fn generate_module(
+        &mut self,
+        ops: ModuleOps,
+        ast: Vec<TopLevel>,
+    ) -> Result<Module, IrGenError> {
         let mut module_builder = ModuleBuilder::new();
+        self.bounds_checks = ops.bounds_checks;
 
+        // Structs and enums can be used before they are declared
+        for node in &ast {
+            match &node.kind {
+                TopLevelKind::Struct(def) => {
+                    let fields = def
+                        .fields
+                        .iter()
+                        .map(|field| (field.name.clone(), field.ty.value.clone()))
+                        .collect();
+                    self.structs.insert(def.name.clone(), fields);
+                }
+                TopLevelKind::Enum(def) => {
+                    let variants = def
+                        .variants
+                        .iter()
+                        .map(|variant| {
+                            let fields = variant
+                                .fields
+                                .iter()
+                                .map(|field| (field.name.clone(), field.ty.value.clone()))
+                                .collect();
+                            (variant.name.clone(), fields)
+                        })
+                        .collect();
+                    self.enums.insert(def.name.clone(), variants);
+                }
+                TopLevelKind::Expr(_) => (),
+            }
+        }
         for node in ast {
-            self.generate_top_level(&mut module_builder, node);
+            self.generate_top_level(&mut module_builder, node)?;
         }
 
-        module_builder.build(ops.name.parse().expect("Invalid module name"))
+        let mut module = module_builder.build(ops.name.parse().expect("Invalid module name"));
+        Self::prune_phis(&mut module);
+        Ok(module)
     }
 
-    fn generate_top_level(&mut self, builder: &mut ModuleBuilder, node: TopLevel) {
+    /// Removes the incoming values of phis from blocks that are never reached. Code after an
+    /// expression of type `!` is still generated, but nothing jumps to it, so its blocks aren't
+    /// part of the function, even if they jump to a block that is
+    fn prune_phis(module: &mut Module) {
+        for function in &module.functions {
+            let reachable: HashSet<BlockId> = rxir::utils::get_related_blocks(module, function)
+                .into_iter()
+                .collect();
+            for block in &reachable {
+                let block = module.blocks.get_mut(block).unwrap();
+                for instruction in &mut block.instructions {
+                    if let Instruction::Phi { incoming, .. } = instruction {
+                        incoming.retain(|(_, block)| reachable.contains(block));
+                    }
+                }
+            }
+        }
+    }
+
+    fn generate_top_level(
+        &mut self,
+        builder: &mut ModuleBuilder,
+        node: TopLevel,
+    ) -> Result<(), IrGenError> {
         match &node.kind {
             // We need a seperate top level expr generator
             TopLevelKind::Expr(expr) => match &expr.kind {
                 ExprKind::FunctionDef(function) => {
                     let entry = builder.create_block(None);
                     let mut block_meta = BlockMeta::new();
-                    let arguments = function
-                        .arguments
-                        .iter()
-                        .map(|(name, ty)| {
-                            let ty = Self::rxir_type(ty);
-                            let id = builder.create_value(&entry, ty.clone(), None);
-                            block_meta.variables.insert(name.clone(), id.clone());
-                            (id, ty)
-                        })
-                        .collect();
+                    let mut arguments = Vec::new();
+                    for arg in &function.arguments {
+                        // Arguments of type `()` have no value, so they aren't passed
+                        match self.rxir_type(&arg.ty.value, &arg.ty.span)? {
+                            rxir::Type::Void => {
+                                block_meta.variables.insert(arg.name.clone(), None);
+                            }
+                            ty => {
+                                let id = builder.create_value(&entry, ty.clone(), None);
+                                arguments.push((arg.name.clone(), id, ty));
+                            }
+                        }
+                    }
+                    // Arguments are spilled to the stack, so they are accessed in the same way as
+                    // locals, the backend is expected to promote them back to registers
+                    for (name, id, ty) in &arguments {
+                        let ptr = builder.build_alloca(&entry, ty.clone(), None);
+                        builder.build_instruction(
+                            &entry,
+                            Instruction::Store {
+                                dest: ptr.clone(),
+                                src: Operand::TempVar {
+                                    ty: ty.clone(),
+                                    id: id.clone(),
+                                },
+                            },
+                        );
+                        block_meta.variables.insert(name.clone(), Some(ptr));
+                    }
+                    // Functions without a return type return `()`, which is lowered to void
+                    let return_ty = match &function.return_ty {
+                        Some(ty) => self.rxir_type(&ty.value, &ty.span)?,
+                        None => rxir::Type::Void,
+                    };
                     builder.build_function(
                         function.name.parse().expect("Invalid function name"),
-                        arguments,
-                        Self::rxir_type(function.return_ty.as_ref().unwrap()),
+                        arguments.into_iter().map(|(_, id, ty)| (id, ty)).collect(),
+                        return_ty,
                         entry.clone(),
                     );
 
-                    self.generate_block(builder, &entry, &function.body, &mut block_meta);
+                    let mut block = entry;
+                    let value =
+                        self.generate_block(builder, &mut block, &function.body, &mut block_meta)?;
+                    // The value of the body is returned if its end is reachable
+                    if !builder.is_terminated(&block) {
+                        builder.build_instruction(&block, Instruction::Return { value });
+                    }
+                    Ok(())
                 }
-                _ => todo!(),
+                _ => Err(IrGenError::Unsupported(
+                    "top level expressions".to_string(),
+                    expr.span.clone(),
+                )),
             },
+            // Struct and enum types are lowered where they are used
+            TopLevelKind::Struct(_) | TopLevelKind::Enum(_) => Ok(()),
         }
     }
 
+    /// Generates the statements of a block, and returns its value unless it is `()`. `block` is
+    /// updated to the block where control flow continues after the body
     fn generate_block(
         &mut self,
         builder: &mut ModuleBuilder,
-        block: &BlockId,
+        block: &mut BlockId,
         body: &Block,
         meta: &mut BlockMeta,
-    ) {
+    ) -> Result<Option<Operand>, IrGenError> {
+        // Bindings in the block go out of scope at the end of it
+        let outer = meta.variables.clone();
         for statement in &body.statements {
-            self.generate_instruction(builder, &block, statement, meta);
+            // Anything after a return is unreachable, and a block can't continue after it
+            if builder.is_terminated(block) {
+                break;
+            }
+            self.generate_instruction(builder, block, statement, meta)?;
+        }
+        let value = match &body.tail {
+            Some(tail) if !builder.is_terminated(block) => {
+                self.generate_value(builder, block, tail, meta)?
+            }
+            _ => None,
+        };
+        meta.variables = outer;
+        Ok(value)
+    }
+
+    /// Generates an expression that might have the type `()` or `!`, which have no RXIR operand
+    fn generate_value(
+        &mut self,
+        builder: &mut ModuleBuilder,
+        block: &mut BlockId,
+        expr: &Expr,
+        meta: &mut BlockMeta,
+    ) -> Result<Option<Operand>, IrGenError> {
+        let ty = expr.ty.as_ref().unwrap();
+        if ty.is_empty() || *ty == AstType::Never {
+            self.generate_instruction(builder, block, expr, meta)?;
+            Ok(None)
+        } else {
+            self.generate_expr(builder, block, expr, meta).map(Some)
+        }
+    }
+
+    /// Generates the value of a field or element of an aggregate, or `None` if it has the type
+    /// `()`, which takes no space in the aggregate so there is nothing to store
+    fn generate_field(
+        &mut self,
+        builder: &mut ModuleBuilder,
+        block: &mut BlockId,
+        expr: &Expr,
+        meta: &mut BlockMeta,
+    ) -> Result<Option<Operand>, IrGenError> {
+        if expr.ty.as_ref().unwrap().is_empty() {
+            self.generate_instruction(builder, block, expr, meta)?;
+            Ok(None)
+        } else {
+            self.generate_expr(builder, block, expr, meta).map(Some)
         }
     }
 
     fn generate_instruction(
         &mut self,
         builder: &mut ModuleBuilder,
-        block: &BlockId,
+        block: &mut BlockId,
         expr: &Expr,
         meta: &mut BlockMeta,
-    ) {
+    ) -> Result<(), IrGenError> {
         match &expr.kind {
             ExprKind::Return(expr) => {
-                let value = if let Some(expr) = expr {
-                    let value = match &expr.kind {
-                        ExprKind::Literal(literal) => match literal {
-                            Literal::Number(number) => Operand::Immediate {
-                                ty: Self::rxir_type(&number.kind.clone().into()),
-                                value: number.value,
-                            },
+                let value = match expr {
+                    Some(expr) => self.generate_value(builder, block, expr, meta)?,
+                    None => None,
+                };
+                // The value might leave the function itself, so the return is never reached
+                if builder.is_terminated(block) {
+                    return Ok(());
+                }
+                builder.build_instruction(block, Instruction::Return { value });
+                Ok(())
+            }
+            ExprKind::Let(binding) => {
+                let value = self.generate_value(builder, block, &binding.value, meta)?;
+                match value {
+                    Some(value) => self.bind_pattern(builder, block, &binding.pattern, value, meta),
+                    // The value might never be produced, if it returns or breaks out of a loop
+                    None if *binding.value.ty.as_ref().unwrap() == AstType::Never => Ok(()),
+                    None => {
+                        Self::bind_unit(&binding.pattern, meta);
+                        Ok(())
+                    }
+                }
+            }
+            // The elements are still evaluated for their side effects, and `()` has no operand
+            ExprKind::Tuple(elements) => {
+                for element in elements {
+                    self.generate_value(builder, block, element, meta)?;
+                }
+                Ok(())
+            }
+            // A variable of type `()` has no value, so using it does nothing
+            ExprKind::Variable(_) if expr.ty.as_ref().unwrap().is_empty() => Ok(()),
+            // A field of type `()` has no value either, but what it is read from is still evaluated
+            // for its side effects
+            ExprKind::Field { expr: parent, .. } if expr.ty.as_ref().unwrap().is_empty() => self
+                .generate_value(builder, block, parent, meta)
+                .map(|_| ()),
+            // Other places of type `()` are only evaluated for their side effects, like the bounds
+            // check of an index
+            ExprKind::Index { .. } | ExprKind::Deref(_) if expr.ty.as_ref().unwrap().is_empty() => {
+                self.generate_place(builder, block, expr, meta).map(|_| ())
+            }
+            // There is no value of type `()` to store, but both sides are still evaluated for their
+            // side effects
+            ExprKind::Assign { target, value, .. } if target.ty.as_ref().unwrap().is_empty() => {
+                self.generate_instruction(builder, block, target, meta)?;
+                self.generate_value(builder, block, value, meta).map(|_| ())
+            }
+            ExprKind::Assign { op, target, value } => {
+                let ptr = self.generate_place(builder, block, target, meta)?;
+                let mut value = self.generate_expr(builder, block, value, meta)?;
+                if let Some(op) = op {
+                    // The target is loaded through the pointer, so an index in it is only
+                    // evaluated once
+                    let place = Place {
+                        ptr: ptr.clone(),
+                        ty: target.ty.clone().unwrap(),
+                    };
+                    let current = self
+                        .load_place(builder, block, &place, &target.span)?
+                        .expect("Compound assignments only assign numbers and booleans");
+                    value = Self::build_binary(
+                        builder,
+                        block,
+                        Self::rxir_binary_op(*op).expect(
+                            "Compound assignments only use arithmetic and bitwise operators",
+                        ),
+                        current,
+                        value,
+                    );
+                }
+                builder.build_instruction(
+                    block,
+                    Instruction::Store {
+                        dest: ptr,
+                        src: value,
+                    },
+                );
+                Ok(())
+            }
+            ExprKind::Block(body) | ExprKind::Unsafe(body) => {
+                self.generate_block(builder, block, body, meta).map(|_| ())
+            }
+            ExprKind::If { .. } => self.generate_if(builder, block, expr, meta).map(|_| ()),
+            ExprKind::Match { .. } => self.generate_match(builder, block, expr, meta).map(|_| ()),
+            ExprKind::While {
+                label,
+                condition,
+                body,
+            } => {
+                let header = builder.create_block(None);
+                let body_id = builder.create_block(None);
+                let exit = builder.create_block(None);
+                builder.build_instruction(
+                    block,
+                    Instruction::Jump {
+                        target: header.clone(),
+                    },
+                );
+                // The condition is checked in its own block, which `continue` jumps back to
+                let mut current = header.clone();
+                let condition = self.generate_expr(builder, &mut current, condition, meta)?;
+                builder.build_instruction(
+                    &current,
+                    Instruction::Branch {
+                        condition,
+                        then_block: body_id.clone(),
+                        else_block: exit.clone(),
+                    },
+                );
+                meta.loops.push(LoopMeta {
+                    label: label.clone(),
+                    continue_block: header,
+                    exit_block: exit.clone(),
+                    incoming: Vec::new(),
+                });
+                self.generate_loop_body(builder, body_id, body, meta)?;
+                *block = exit;
+                Ok(())
+            }
+            ExprKind::Loop { .. } => self.generate_loop(builder, block, expr, meta).map(|_| ()),
+            ExprKind::Call { .. } => self.generate_call(builder, block, expr, meta).map(|_| ()),
+            ExprKind::Break { label, value } => {
+                let value = match value {
+                    Some(value) => self.generate_value(builder, block, value, meta)?,
+                    None => None,
+                };
+                // The value might leave the loop itself, so the `break` is never reached
+                if builder.is_terminated(block) {
+                    return Ok(());
+                }
+                let loop_meta = Self::find_loop(meta, label);
+                let target = loop_meta.exit_block.clone();
+                if let Some(value) = value {
+                    loop_meta.incoming.push((value, block.clone()));
+                }
+                builder.build_instruction(block, Instruction::Jump { target });
+                Ok(())
+            }
+            ExprKind::Continue { label } => {
+                let target = Self::find_loop(meta, label).continue_block.clone();
+                builder.build_instruction(block, Instruction::Jump { target });
+                Ok(())
+            }
+            _ => self.generate_expr(builder, block, expr, meta).map(|_| ()),
+        }
+    }
+
+    /// Stores the parts of a value into the stack slots of the variables that a pattern binds. The
+    /// pattern can't be refutable
+    fn bind_pattern(
+        &self,
+        builder: &mut ModuleBuilder,
+        block: &BlockId,
+        pattern: &Pattern,
+        value: Operand,
+        meta: &mut BlockMeta,
+    ) -> Result<(), IrGenError> {
+        match &pattern.kind {
+            PatternKind::Binding { name, .. } => {
+                let ptr = builder.build_alloca(block, value.ty(), None);
+                builder.build_instruction(
+                    block,
+                    Instruction::Store {
+                        dest: ptr.clone(),
+                        src: value,
+                    },
+                );
+                meta.variables.insert(name.clone(), Some(ptr));
+            }
+            // A literal pattern can only be irrefutable if its type has a single value, so it
+            // doesn't bind anything
+            PatternKind::Wildcard | PatternKind::Literal { .. } => (),
+            PatternKind::Tuple(elements) => {
+                let rxir::Type::Struct(types) = value.ty() else {
+                    unreachable!("The type checker only allows tuple patterns for tuples");
+                };
+                for (index, (element, ty)) in elements.iter().zip(types).enumerate() {
+                    if let PatternKind::Wildcard = element.kind {
+                        continue;
+                    }
+                    // `()` takes no space in the tuple, so there is no value to extract
+                    if element.ty.as_ref().unwrap().is_empty() {
+                        Self::bind_unit(element, meta);
+                        continue;
+                    }
+                    let dest = builder.create_value(block, ty.clone(), None);
+                    builder.build_instruction(
+                        block,
+                        Instruction::ExtractField {
+                            dest: dest.clone(),
+                            value: value.clone(),
+                            index: index as u32,
                         },
-                        ExprKind::Variable(name) => {
-                            let id = meta.variables.get(name).unwrap();
-                            Operand::TempVar {
-                                ty: builder.get_var_type(block, id),
-                                id: id.clone(),
-                            }
+                    );
+                    let element_value = Operand::TempVar { ty, id: dest };
+                    self.bind_pattern(builder, block, element, element_value, meta)?;
+                }
+            }
+            // The fields of a variant are only in memory, so the value is spilled to read them
+            PatternKind::Variant { path, fields, .. } => {
+                let ptr = builder.build_alloca(block, value.ty(), None);
+                builder.build_instruction(
+                    block,
+                    Instruction::Store {
+                        dest: ptr.clone(),
+                        src: value,
+                    },
+                );
+                let place = Place {
+                    ptr,
+                    ty: pattern.ty.clone().unwrap(),
+                };
+                let variant = self.variant_index(path);
+                let places = self.variant_places(builder, block, &place, variant, &pattern.span)?;
+                for field in fields {
+                    let index = self.enums[&path.enum_name.value][variant]
+                        .1
+                        .iter()
+                        .position(|(name, _)| *name == field.name)
+                        .unwrap();
+                    let Some(value) =
+                        self.load_place(builder, block, &places[index], &field.span)?
+                    else {
+                        Self::bind_unit(&field.pattern, meta);
+                        continue;
+                    };
+                    self.bind_pattern(builder, block, &field.pattern, value, meta)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Records the variables that a pattern of type `()` binds, which have no stack slot
+    fn bind_unit(pattern: &Pattern, meta: &mut BlockMeta) {
+        match &pattern.kind {
+            PatternKind::Binding { name, .. } => {
+                meta.variables.insert(name.clone(), None);
+            }
+            PatternKind::Tuple(elements) => {
+                for element in elements {
+                    Self::bind_unit(element, meta);
+                }
+            }
+            PatternKind::Wildcard | PatternKind::Literal { .. } | PatternKind::Variant { .. } => (),
+        }
+    }
+
+    /// Builds a struct or tuple value of type `ty` from the values of its fields, by storing them
+    /// into a temporary and loading it back
+    fn build_aggregate(
+        builder: &mut ModuleBuilder,
+        block: &BlockId,
+        ty: rxir::Type,
+        fields: Vec<(u32, Operand)>,
+    ) -> Operand {
+        let ptr = builder.build_alloca(block, ty.clone(), None);
+        for (index, value) in fields {
+            let dest = Self::build_field_address(builder, block, ptr.clone(), &ty, index);
+            builder.build_instruction(block, Instruction::Store { dest, src: value });
+        }
+        let dest = builder.create_value(block, ty.clone(), None);
+        builder.build_instruction(
+            block,
+            Instruction::Load {
+                dest: dest.clone(),
+                src: ptr,
+                ty: ty.clone(),
+            },
+        );
+        Operand::TempVar { ty, id: dest }
+    }
+
+    /// Puts the bytes of a string literal in a global constant, and builds a string or slice that
+    /// points to them
+    fn build_string(builder: &mut ModuleBuilder, block: &BlockId, data: Vec<u8>) -> Operand {
+        let len = data.len();
+        let ptr_ty = rxir::Type::pointer(rxir::Type::Unsigned(8));
+        let ty = rxir::Type::Struct(vec![ptr_ty.clone(), usize_type()]);
+        let id = builder.build_global(data);
+        let fields = vec![
+            (0, Operand::Global { ty: ptr_ty, id }),
+            (
+                1,
+                Operand::Immediate {
+                    ty: usize_type(),
+                    value: len as u128,
+                },
+            ),
+        ];
+        Self::build_aggregate(builder, block, ty, fields)
+    }
+
+    /// Gets a pointer to the field at `index` of the struct of type `ty` that `src` points to, or to
+    /// a member of a union
+    fn build_field_address(
+        builder: &mut ModuleBuilder,
+        block: &BlockId,
+        src: TempVarId,
+        ty: &rxir::Type,
+        index: u32,
+    ) -> TempVarId {
+        let field_ty = match ty {
+            rxir::Type::Struct(fields) | rxir::Type::Union(fields) => {
+                fields[index as usize].clone()
+            }
+            _ => unreachable!("Only structs and unions have fields"),
+        };
+        let dest = builder.create_value(block, rxir::Type::pointer(field_ty), None);
+        builder.build_instruction(
+            block,
+            Instruction::FieldAddress {
+                dest: dest.clone(),
+                src,
+                ty: ty.clone(),
+                index,
+            },
+        );
+        dest
+    }
+
+    /// Loads the value in a place, or returns `None` if it is `()`
+    fn load_place(
+        &self,
+        builder: &mut ModuleBuilder,
+        block: &BlockId,
+        place: &Place,
+        span: &Span,
+    ) -> Result<Option<Operand>, IrGenError> {
+        let ty = self.rxir_type(&place.ty, span)?;
+        if let rxir::Type::Void = ty {
+            return Ok(None);
+        }
+        let dest = builder.create_value(block, ty.clone(), None);
+        builder.build_instruction(
+            block,
+            Instruction::Load {
+                dest: dest.clone(),
+                src: place.ptr.clone(),
+                ty: ty.clone(),
+            },
+        );
+        Ok(Some(Operand::TempVar { ty, id: dest }))
+    }
+
+    /// Gets the position of a variant in the declaration of its enum, which is also its tag
+    fn variant_index(&self, path: &VariantPath) -> usize {
+        self.enums[&path.enum_name.value]
+            .iter()
+            .position(|(name, _)| *name == path.variant.value)
+            .expect("The type checker checks that the variant exists")
+    }
+
+    /// Gets the places of the fields of a variant, in the order they are declared, for an enum
+    /// value in `place` that holds the variant
+    fn variant_places(
+        &self,
+        builder: &mut ModuleBuilder,
+        block: &BlockId,
+        place: &Place,
+        variant: usize,
+        span: &Span,
+    ) -> Result<Vec<Place>, IrGenError> {
+        let AstType::Named(name) = &place.ty else {
+            unreachable!("Variants are only matched against enums");
+        };
+        let fields = &self.enums[name][variant].1;
+        if fields.is_empty() {
+            return Ok(Vec::new());
+        }
+        let enum_ty = self.rxir_type(&place.ty, span)?;
+        let rxir::Type::Struct(parts) = &enum_ty else {
+            unreachable!("Enums are lowered to a tag and a union");
+        };
+        let union = Self::build_field_address(builder, block, place.ptr.clone(), &enum_ty, 1);
+        let rxir::Type::Union(payloads) = &parts[1] else {
+            unreachable!("Enums are lowered to a tag and a union");
+        };
+        let payload = Self::build_field_address(builder, block, union, &parts[1], variant as u32);
+        Ok(fields
+            .iter()
+            .enumerate()
+            .map(|(index, (_, ty))| Place {
+                ptr: Self::build_field_address(
+                    builder,
+                    block,
+                    payload.clone(),
+                    &payloads[variant],
+                    index as u32,
+                ),
+                ty: ty.clone(),
+            })
+            .collect())
+    }
+
+    /// Gets the pointer to the memory that an expression refers to. Expressions that can't be
+    /// assigned to are evaluated into a temporary, and the pointer to it is returned
+    fn generate_place(
+        &mut self,
+        builder: &mut ModuleBuilder,
+        block: &mut BlockId,
+        expr: &Expr,
+        meta: &mut BlockMeta,
+    ) -> Result<TempVarId, IrGenError> {
+        match &expr.kind {
+            ExprKind::Variable(name) => match meta.slot(name) {
+                Some(ptr) => Ok(ptr),
+                // A variable of type `()` has no stack slot, but a reference to it still needs an
+                // address, so it points to an empty temporary
+                None => Ok(builder.build_alloca(block, rxir::Type::Struct(Vec::new()), None)),
+            },
+            ExprKind::Field {
+                expr: parent,
+                field,
+            } => {
+                let src = self.generate_place(builder, block, parent, meta)?;
+                let parent_ty = parent.ty.as_ref().unwrap();
+                let index = self.field_index(parent_ty, &field.value);
+                let field_ty = self.rxir_field(expr.ty.as_ref().unwrap(), &expr.span)?;
+                let dest = builder.create_value(block, rxir::Type::pointer(field_ty), None);
+                builder.build_instruction(
+                    block,
+                    Instruction::FieldAddress {
+                        dest: dest.clone(),
+                        src,
+                        ty: self.rxir_type(parent_ty, &parent.span)?,
+                        index,
+                    },
+                );
+                Ok(dest)
+            }
+            ExprKind::Index { .. } => self.generate_element_address(builder, block, expr, meta),
+            // The value of a reference or pointer is the address it points to
+            ExprKind::Deref(operand) => match self.generate_expr(builder, block, operand, meta)? {
+                Operand::TempVar { id, .. } => Ok(id),
+                Operand::Immediate { .. } | Operand::Global { .. } => {
+                    unreachable!("References and pointers are only created from places")
+                }
+            },
+            _ => {
+                let Some(value) = self.generate_value(builder, block, expr, meta)? else {
+                    return Ok(builder.build_alloca(block, rxir::Type::Struct(Vec::new()), None));
+                };
+                let ptr = builder.build_alloca(block, value.ty(), None);
+                builder.build_instruction(
+                    block,
+                    Instruction::Store {
+                        dest: ptr.clone(),
+                        src: value,
+                    },
+                );
+                Ok(ptr)
+            }
+        }
+    }
+
+    /// Gets the pointer to the element that an index expression refers to. The index is checked
+    /// against the length of the array or slice unless bounds checks are disabled, so control flow
+    /// continues in a new block
+    fn generate_element_address(
+        &mut self,
+        builder: &mut ModuleBuilder,
+        block: &mut BlockId,
+        expr: &Expr,
+        meta: &mut BlockMeta,
+    ) -> Result<TempVarId, IrGenError> {
+        let ExprKind::Index {
+            expr: parent,
+            index,
+        } = &expr.kind
+        else {
+            unreachable!("Expected an index expression");
+        };
+        let element_ty = self.rxir_field(expr.ty.as_ref().unwrap(), &expr.span)?;
+        let (src, len) = match parent.ty.as_ref().unwrap() {
+            // An array starts with its first element
+            AstType::Array(_, len) => {
+                let src = self.generate_place(builder, block, parent, meta)?;
+                let len = Operand::Immediate {
+                    ty: usize_type(),
+                    value: *len as u128,
+                };
+                (src, len)
+            }
+            _ => {
+                let slice = self.generate_expr(builder, block, parent, meta)?;
+                let src =
+                    builder.create_value(block, rxir::Type::pointer(element_ty.clone()), None);
+                builder.build_instruction(
+                    block,
+                    Instruction::ExtractField {
+                        dest: src.clone(),
+                        value: slice.clone(),
+                        index: 0,
+                    },
+                );
+                let len = builder.create_value(block, usize_type(), None);
+                builder.build_instruction(
+                    block,
+                    Instruction::ExtractField {
+                        dest: len.clone(),
+                        value: slice,
+                        index: 1,
+                    },
+                );
+                let len = Operand::TempVar {
+                    ty: usize_type(),
+                    id: len,
+                };
+                (src, len)
+            }
+        };
+        let index = self.generate_expr(builder, block, index, meta)?;
+        if self.bounds_checks {
+            Self::build_bounds_check(builder, block, index.clone(), len);
+        }
+        let dest = builder.create_value(block, rxir::Type::pointer(element_ty.clone()), None);
+        builder.build_instruction(
+            block,
+            Instruction::ElementAddress {
+                dest: dest.clone(),
+                src,
+                ty: element_ty,
+                index,
+            },
+        );
+        Ok(dest)
+    }
+
+    /// Traps if `index` isn't less than `len`, control flow continues in a new block where the
+    /// index is in bounds
+    fn build_bounds_check(
+        builder: &mut ModuleBuilder,
+        block: &mut BlockId,
+        index: Operand,
+        len: Operand,
+    ) {
+        // A constant index into an array doesn't need to be checked if it is in bounds
+        if let (Operand::Immediate { value: index, .. }, Operand::Immediate { value: len, .. }) =
+            (&index, &len)
+        {
+            if index < len {
+                return;
+            }
+        }
+        let in_bounds = Self::build_compare(
+            builder,
+            block,
+            rxir::CompareOp::Lt,
+            index,
+            len,
+            rxir::Type::Bool,
+        );
+        let ok = builder.create_block(None);
+        let trap = builder.create_block(None);
+        builder.build_instruction(
+            block,
+            Instruction::Branch {
+                condition: in_bounds,
+                then_block: ok.clone(),
+                else_block: trap.clone(),
+            },
+        );
+        builder.build_instruction(&trap, Instruction::Trap);
+        *block = ok;
+    }
+
+    /// Fills an array of type `ty` with `count` copies of a value, with a loop that stores the
+    /// value into every element. Control flow continues in a new block after the loop
+    fn build_array_repeat(
+        builder: &mut ModuleBuilder,
+        block: &mut BlockId,
+        ty: rxir::Type,
+        value: Operand,
+        count: u64,
+    ) -> Operand {
+        let usize_ty = usize_type();
+        let ptr = builder.build_alloca(block, ty.clone(), None);
+        let counter = builder.build_alloca(block, usize_ty.clone(), None);
+        builder.build_instruction(
+            block,
+            Instruction::Store {
+                dest: counter.clone(),
+                src: Operand::Immediate {
+                    ty: usize_ty.clone(),
+                    value: 0,
+                },
+            },
+        );
+        let header = builder.create_block(None);
+        let body = builder.create_block(None);
+        let exit = builder.create_block(None);
+        builder.build_instruction(
+            block,
+            Instruction::Jump {
+                target: header.clone(),
+            },
+        );
+        let index = builder.create_value(&header, usize_ty.clone(), None);
+        builder.build_instruction(
+            &header,
+            Instruction::Load {
+                dest: index.clone(),
+                src: counter.clone(),
+                ty: usize_ty.clone(),
+            },
+        );
+        let index = Operand::TempVar {
+            ty: usize_ty.clone(),
+            id: index,
+        };
+        let count = Operand::Immediate {
+            ty: usize_ty.clone(),
+            value: count as u128,
+        };
+        let condition = Self::build_compare(
+            builder,
+            &header,
+            rxir::CompareOp::Lt,
+            index.clone(),
+            count,
+            rxir::Type::Bool,
+        );
+        builder.build_instruction(
+            &header,
+            Instruction::Branch {
+                condition,
+                then_block: body.clone(),
+                else_block: exit.clone(),
+            },
+        );
+
+        let element_ty = value.ty();
+        let dest = builder.create_value(&body, rxir::Type::pointer(element_ty.clone()), None);
+        builder.build_instruction(
+            &body,
+            Instruction::ElementAddress {
+                dest: dest.clone(),
+                src: ptr.clone(),
+                ty: element_ty,
+                index: index.clone(),
+            },
+        );
+        builder.build_instruction(&body, Instruction::Store { dest, src: value });
+        let one = Operand::Immediate {
+            ty: usize_ty,
+            value: 1,
+        };
+        let next = Self::build_binary(builder, &body, rxir::BinaryOp::Add, index, one);
+        builder.build_instruction(
+            &body,
+            Instruction::Store {
+                dest: counter,
+                src: next,
+            },
+        );
+        builder.build_instruction(&body, Instruction::Jump { target: header });
+
+        *block = exit;
+        let dest = builder.create_value(block, ty.clone(), None);
+        builder.build_instruction(
+            block,
+            Instruction::Load {
+                dest: dest.clone(),
+                src: ptr,
+                ty: ty.clone(),
+            },
+        );
+        Operand::TempVar { ty, id: dest }
+    }
+
+    fn build_binary(
+        builder: &mut ModuleBuilder,
+        block: &BlockId,
+        op: rxir::BinaryOp,
+        lhs: Operand,
+        rhs: Operand,
+    ) -> Operand {
+        let ty = lhs.ty();
+        let dest = builder.create_value(block, ty.clone(), None);
+        builder.build_instruction(
+            block,
+            Instruction::Binary {
+                dest: dest.clone(),
+                op,
+                lhs,
+                rhs,
+            },
+        );
+        Operand::TempVar { ty, id: dest }
+    }
+
+    /// Generates an `if` expression, and returns its value unless it is `()`. Control flow
+    /// continues in a new block after the `if`
+    fn generate_if(
+        &mut self,
+        builder: &mut ModuleBuilder,
+        block: &mut BlockId,
+        expr: &Expr,
+        meta: &mut BlockMeta,
+    ) -> Result<Option<Operand>, IrGenError> {
+        let ExprKind::If {
+            condition,
+            then_block,
+            else_block,
+        } = &expr.kind
+        else {
+            unreachable!("Expected an if expression");
+        };
+        let condition = self.generate_expr(builder, block, condition, meta)?;
+        let then_id = builder.create_block(None);
+        let else_id = else_block.as_ref().map(|_| builder.create_block(None));
+        let merge_id = builder.create_block(None);
+        builder.build_instruction(
+            block,
+            Instruction::Branch {
+                condition,
+                then_block: then_id.clone(),
+                else_block: else_id.clone().unwrap_or(merge_id.clone()),
+            },
+        );
+
+        let mut incoming = Vec::new();
+        let mut current = then_id;
+        let value = self.generate_block(builder, &mut current, then_block, meta)?;
+        Self::build_jump(builder, &current, &merge_id, value, &mut incoming);
+        if let (Some(else_block), Some(else_id)) = (else_block, else_id) {
+            let mut current = else_id;
+            let value = self.generate_block(builder, &mut current, else_block, meta)?;
+            Self::build_jump(builder, &current, &merge_id, value, &mut incoming);
+        }
+
+        *block = merge_id;
+        if expr.ty.as_ref().unwrap().is_empty() {
+            return Ok(None);
+        }
+        let ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+        Ok(Some(Self::build_phi(builder, block, ty, incoming)))
+    }
+
+    /// Generates a `match` expression, and returns its value unless it is `()`. The arms are chosen
+    /// by a decision tree that checks each part of the scrutinee at most once, and control flow
+    /// continues in a new block after the `match`
+    fn generate_match(
+        &mut self,
+        builder: &mut ModuleBuilder,
+        block: &mut BlockId,
+        expr: &Expr,
+        meta: &mut BlockMeta,
+    ) -> Result<Option<Operand>, IrGenError> {
+        let ExprKind::Match { scrutinee, arms } = &expr.kind else {
+            unreachable!("Expected a match expression");
+        };
+        let value = self.generate_value(builder, block, scrutinee, meta)?;
+        if builder.is_terminated(block) {
+            return Ok(None);
+        }
+        let merge_id = builder.create_block(None);
+        let mut incoming = Vec::new();
+        let Some(value) = value else {
+            // A value of type `()` matches every pattern, so the first arm is always chosen
+            let mut current = block.clone();
+            let value = self.generate_value(builder, &mut current, &arms[0].body, meta)?;
+            Self::build_jump(builder, &current, &merge_id, value, &mut incoming);
+            return self.finish_match(builder, block, expr, merge_id, incoming);
+        };
+        let ptr = builder.build_alloca(block, value.ty(), None);
+        builder.build_instruction(
+            block,
+            Instruction::Store {
+                dest: ptr.clone(),
+                src: value,
+            },
+        );
+        let place = Place {
+            ptr,
+            ty: scrutinee.ty.clone().unwrap(),
+        };
+
+        // The variables of each arm get their slots up front, since the decision tree can reach
+        // an arm in more than one way
+        let mut arm_metas = Vec::new();
+        let mut rows = Vec::new();
+        for (index, arm) in arms.iter().enumerate() {
+            let mut slots = HashMap::new();
+            self.allocate_bindings(builder, block, &arm.pattern, &mut slots)?;
+            arm_metas.push(MatchArmMeta {
+                block: builder.create_block(None),
+                slots,
+            });
+            rows.push(MatchRow {
+                patterns: vec![Some(&arm.pattern)],
+                arm: index,
+                bindings: Vec::new(),
+            });
+        }
+        self.generate_decision(builder, block.clone(), vec![place], rows, &arm_metas)?;
+
+        for (arm, arm_meta) in arms.iter().zip(arm_metas) {
+            let outer = meta.variables.clone();
+            meta.variables.extend(arm_meta.slots);
+            let mut current = arm_meta.block;
+            let value = self.generate_value(builder, &mut current, &arm.body, meta)?;
+            Self::build_jump(builder, &current, &merge_id, value, &mut incoming);
+            meta.variables = outer;
+        }
+        self.finish_match(builder, block, expr, merge_id, incoming)
+    }
+
+    /// Continues after a `match` in the block where its arms meet, and merges their values
+    fn finish_match(
+        &self,
+        builder: &mut ModuleBuilder,
+        block: &mut BlockId,
+        expr: &Expr,
+        merge_id: BlockId,
+        incoming: Vec<(Operand, BlockId)>,
+    ) -> Result<Option<Operand>, IrGenError> {
+        *block = merge_id;
+        let ty = expr.ty.as_ref().unwrap();
+        if ty.is_empty() || *ty == AstType::Never {
+            return Ok(None);
+        }
+        let ty = self.rxir_type(ty, &expr.span)?;
+        Ok(Some(Self::build_phi(builder, block, ty, incoming)))
+    }
+
+    /// Allocates a stack slot for each variable that a pattern binds
+    fn allocate_bindings(
+        &self,
+        builder: &mut ModuleBuilder,
+        block: &BlockId,
+        pattern: &Pattern,
+        slots: &mut HashMap<String, Option<TempVarId>>,
+    ) -> Result<(), IrGenError> {
+        match &pattern.kind {
+            PatternKind::Binding { name, .. } => {
+                let ty = self.rxir_type(pattern.ty.as_ref().unwrap(), &pattern.span)?;
+                // `()` has no value to store, so the variable has no slot
+                let slot = match ty {
+                    rxir::Type::Void => None,
+                    ty => Some(builder.build_alloca(block, ty, None)),
+                };
+                slots.insert(name.clone(), slot);
+            }
+            PatternKind::Wildcard | PatternKind::Literal { .. } => (),
+            PatternKind::Tuple(elements) => {
+                for element in elements {
+                    self.allocate_bindings(builder, block, element, slots)?;
+                }
+            }
+            PatternKind::Variant { fields, .. } => {
+                for field in fields {
+                    self.allocate_bindings(builder, block, &field.pattern, slots)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Generates the checks that choose the first row whose patterns match the places, starting in
+    /// `block`. Each check looks at one place, and continues in a block for each outcome with the
+    /// rows that can still match
+    fn generate_decision<'a>(
+        &self,
+        builder: &mut ModuleBuilder,
+        block: BlockId,
+        mut places: Vec<Place>,
+        mut rows: Vec<MatchRow<'a>>,
+        arms: &[MatchArmMeta],
+    ) -> Result<(), IrGenError> {
+        // The type checker checks that the arms cover every value
+        if rows.is_empty() {
+            builder.build_instruction(&block, Instruction::Unreachable);
+            return Ok(());
+        }
+        // Bindings match anything, the variables are only stored once the arm is chosen
+        for row in &mut rows {
+            for (pattern, place) in row.patterns.iter_mut().zip(&places) {
+                match pattern.map(|pattern| &pattern.kind) {
+                    Some(PatternKind::Binding { .. }) => {
+                        row.bindings.push((pattern.unwrap(), place.clone()));
+                        *pattern = None;
+                    }
+                    Some(PatternKind::Wildcard) => *pattern = None,
+                    _ => (),
+                }
+            }
+        }
+        let Some(column) = rows[0].patterns.iter().position(Option::is_some) else {
+            // Everything in the first row matches, so its arm is chosen
+            let row = &rows[0];
+            let arm = &arms[row.arm];
+            for (pattern, place) in &row.bindings {
+                let PatternKind::Binding { name, .. } = &pattern.kind else {
+                    unreachable!("Only bindings are recorded");
+                };
+                let Some(Some(slot)) = arm.slots.get(name) else {
+                    continue;
+                };
+                let value = self
+                    .load_place(builder, &block, place, &pattern.span)?
+                    .unwrap();
+                builder.build_instruction(
+                    &block,
+                    Instruction::Store {
+                        dest: slot.clone(),
+                        src: value,
+                    },
+                );
+            }
+            builder.build_instruction(
+                &block,
+                Instruction::Jump {
+                    target: arm.block.clone(),
+                },
+            );
+            return Ok(());
+        };
+        let pattern = rows[0].patterns[column].unwrap();
+        let place = places.remove(column);
+        match &pattern.kind {
+            // A tuple always matches, its elements are checked as separate places
+            PatternKind::Tuple(elements) => {
+                let ty = self.rxir_type(&place.ty, &pattern.span)?;
+                let AstType::Tuple(types) = &place.ty else {
+                    unreachable!("The type checker only allows tuple patterns for tuples");
+                };
+                let element_places: Vec<Place> = types
+                    .iter()
+                    .enumerate()
+                    .map(|(index, ty_element)| Place {
+                        ptr: Self::build_field_address(
+                            builder,
+                            &block,
+                            place.ptr.clone(),
+                            &ty,
+                            index as u32,
+                        ),
+                        ty: ty_element.clone(),
+                    })
+                    .collect();
+                places.splice(column..column, element_places);
+                for row in &mut rows {
+                    let expanded = match row.patterns.remove(column).map(|p| &p.kind) {
+                        Some(PatternKind::Tuple(elements)) => elements.iter().map(Some).collect(),
+                        _ => vec![None; elements.len()],
+                    };
+                    row.patterns.splice(column..column, expanded);
+                }
+                self.generate_decision(builder, block, places, rows, arms)
+            }
+            PatternKind::Literal { .. } => {
+                let value = self
+                    .load_place(builder, &block, &place, &pattern.span)?
+                    .unwrap();
+                let bits = match value.ty() {
+                    rxir::Type::Signed(bits) | rxir::Type::Unsigned(bits) => bits,
+                    _ => 1,
+                };
+                // The values that some row checks for, in the order they are first checked
+                let mut values = Vec::new();
+                for row in &rows {
+                    if let Some(PatternKind::Literal { value, negated }) =
+                        row.patterns[column].map(|p| &p.kind)
+                    {
+                        let value = Self::literal_bits(value, *negated, bits);
+                        if !values.contains(&value) {
+                            values.push(value);
                         }
-                        _ => todo!(),
+                    }
+                }
+                let mut cases = Vec::new();
+                for value in values {
+                    let case_rows = Self::specialize_rows(&rows, column, |pattern| {
+                        let PatternKind::Literal {
+                            value: other,
+                            negated,
+                        } = &pattern.kind
+                        else {
+                            unreachable!("Literal patterns are only mixed with other literals");
+                        };
+                        (Self::literal_bits(other, *negated, bits) == value).then(Vec::new)
+                    });
+                    let case_block = builder.create_block(None);
+                    self.generate_decision(
+                        builder,
+                        case_block.clone(),
+                        places.clone(),
+                        case_rows,
+                        arms,
+                    )?;
+                    cases.push((value, case_block));
+                }
+                let is_bool = matches!(value.ty(), rxir::Type::Bool);
+                // When both booleans are checked for, no value is left for the default
+                let default = if is_bool && cases.len() == 2 {
+                    None
+                } else {
+                    // Only the rows that match anything are left for the values no row checks for
+                    let default_rows = Self::specialize_rows(&rows, column, |_| None);
+                    let default = builder.create_block(None);
+                    self.generate_decision(builder, default.clone(), places, default_rows, arms)?;
+                    Some(default)
+                };
+                let instruction = if is_bool {
+                    let block_for = |bit| {
+                        cases
+                            .iter()
+                            .find(|(value, _)| *value == bit)
+                            .map(|(_, block)| block.clone())
+                            .or_else(|| default.clone())
+                            .unwrap()
                     };
-                    Some(value)
+                    Instruction::Branch {
+                        condition: value,
+                        then_block: block_for(1),
+                        else_block: block_for(0),
+                    }
                 } else {
-                    None
+                    Instruction::Switch {
+                        value,
+                        default: default.unwrap(),
+                        cases,
+                    }
+                };
+                builder.build_instruction(&block, instruction);
+                Ok(())
+            }
+            PatternKind::Variant { .. } => {
+                let AstType::Named(name) = &place.ty else {
+                    unreachable!("Variants are only matched against enums");
+                };
+                let enum_ty = self.rxir_type(&place.ty, &pattern.span)?;
+                let tag_ptr =
+                    Self::build_field_address(builder, &block, place.ptr.clone(), &enum_ty, 0);
+                let rxir::Type::Struct(parts) = &enum_ty else {
+                    unreachable!("Enums are lowered to a tag and a union");
+                };
+                let tag = builder.create_value(&block, parts[0].clone(), None);
+                builder.build_instruction(
+                    &block,
+                    Instruction::Load {
+                        dest: tag.clone(),
+                        src: tag_ptr,
+                        ty: parts[0].clone(),
+                    },
+                );
+                // The variants that some row checks for, in the order they are declared
+                let mut variants: Vec<usize> = rows
+                    .iter()
+                    .filter_map(|row| match row.patterns[column].map(|p| &p.kind) {
+                        Some(PatternKind::Variant { path, .. }) => Some(self.variant_index(path)),
+                        _ => None,
+                    })
+                    .collect();
+                variants.sort_unstable();
+                variants.dedup();
+                let mut cases = Vec::new();
+                for variant in variants.iter().copied() {
+                    let case_block = builder.create_block(None);
+                    let field_places =
+                        self.variant_places(builder, &case_block, &place, variant, &pattern.span)?;
+                    let field_names = &self.enums[name][variant].1;
+                    let case_rows = Self::specialize_rows(&rows, column, |pattern| {
+                        let PatternKind::Variant { path, fields, .. } = &pattern.kind else {
+                            unreachable!("Variant patterns are only mixed with other variants");
+                        };
+                        (self.variant_index(path) == variant).then(|| {
+                            field_names
+                                .iter()
+                                .map(|(name, _)| {
+                                    fields
+                                        .iter()
+                                        .find(|field| field.name == *name)
+                                        .map(|field| &field.pattern)
+                                })
+                                .collect()
+                        })
+                    });
+                    let mut case_places = places.clone();
+                    case_places.splice(column..column, field_places);
+                    self.generate_decision(
+                        builder,
+                        case_block.clone(),
+                        case_places,
+                        case_rows,
+                        arms,
+                    )?;
+                    cases.push((variant as u128, case_block));
+                }
+                // When every variant is checked for, the last one doesn't need to be compared
+                let default = if variants.len() == self.enums[name].len() {
+                    cases.pop().unwrap().1
+                } else {
+                    let default_rows = Self::specialize_rows(&rows, column, |_| None);
+                    let default = builder.create_block(None);
+                    self.generate_decision(builder, default.clone(), places, default_rows, arms)?;
+                    default
+                };
+                builder.build_instruction(
+                    &block,
+                    Instruction::Switch {
+                        value: Operand::TempVar {
+                            ty: parts[0].clone(),
+                            id: tag,
+                        },
+                        default,
+                        cases,
+                    },
+                );
+                Ok(())
+            }
+            PatternKind::Binding { .. } | PatternKind::Wildcard => {
+                unreachable!("Bindings and wildcards are removed above")
+            }
+        }
+    }
+
+    /// Gets the rows that can still match after checking the place at `column`. `matches` gets the
+    /// patterns for the parts of the place that a pattern checks next, or `None` if the pattern
+    /// doesn't match. Rows that match anything at `column` are kept, and match anything in the
+    /// parts too
+    fn specialize_rows<'a>(
+        rows: &[MatchRow<'a>],
+        column: usize,
+        mut matches: impl FnMut(&'a Pattern) -> Option<Vec<Option<&'a Pattern>>>,
+    ) -> Vec<MatchRow<'a>> {
+        let arity = rows
+            .iter()
+            .find_map(|row| row.patterns[column].and_then(&mut matches))
+            .map_or(0, |parts| parts.len());
+        rows.iter()
+            .filter_map(|row| {
+                let parts = match row.patterns[column] {
+                    Some(pattern) => matches(pattern)?,
+                    None => vec![None; arity],
+                };
+                let mut patterns = row.patterns.clone();
+                patterns.splice(column..=column, parts);
+                Some(MatchRow {
+                    patterns,
+                    arm: row.arm,
+                    bindings: row.bindings.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Gets the bits of a literal pattern as a value of a type with `bits` bits
+    fn literal_bits(value: &Literal, negated: bool, bits: u32) -> u128 {
+        let value = match value {
+            Literal::Bool(value) => *value as u128,
+            Literal::Number(number) => match number.value {
+                NumberValue::Int(value) if negated => value.wrapping_neg(),
+                NumberValue::Int(value) => value,
+                NumberValue::Float(_) => unreachable!("The type checker rejects float patterns"),
+            },
+            Literal::Str(_) | Literal::ByteStr(_) => {
+                unreachable!("String literal patterns aren't parsed")
+            }
+        };
+        if bits >= 128 {
+            value
+        } else {
+            value & ((1 << bits) - 1)
+        }
+    }
+
+    /// Generates a `loop`, and returns the value it is exited with unless it is `()`. Control flow
+    /// continues in a new block after the loop, which is unreachable if the loop is never exited
+    fn generate_loop(
+        &mut self,
+        builder: &mut ModuleBuilder,
+        block: &mut BlockId,
+        expr: &Expr,
+        meta: &mut BlockMeta,
+    ) -> Result<Option<Operand>, IrGenError> {
+        let ExprKind::Loop { label, body } = &expr.kind else {
+            unreachable!("Expected a loop");
+        };
+        let body_id = builder.create_block(None);
+        let exit = builder.create_block(None);
+        builder.build_instruction(
+            block,
+            Instruction::Jump {
+                target: body_id.clone(),
+            },
+        );
+        meta.loops.push(LoopMeta {
+            label: label.clone(),
+            continue_block: body_id.clone(),
+            exit_block: exit.clone(),
+            incoming: Vec::new(),
+        });
+        let incoming = self.generate_loop_body(builder, body_id, body, meta)?;
+        *block = exit;
+
+        let ty = expr.ty.as_ref().unwrap();
+        if ty.is_empty() || *ty == AstType::Never {
+            return Ok(None);
+        }
+        let ty = self.rxir_type(ty, &expr.span)?;
+        Ok(Some(Self::build_phi(builder, block, ty, incoming)))
+    }
+
+    /// Generates a call, and returns the value it returns unless it is `()`
+    fn generate_call(
+        &mut self,
+        builder: &mut ModuleBuilder,
+        block: &mut BlockId,
+        expr: &Expr,
+        meta: &mut BlockMeta,
+    ) -> Result<Option<Operand>, IrGenError> {
+        let ExprKind::Call { callee, arguments } = &expr.kind else {
+            unreachable!("Expected a call");
+        };
+        let ExprKind::Variable(name) = &callee.kind else {
+            unreachable!("The type checker only allows calling functions by name");
+        };
+        // Arguments of type `()` are still evaluated, but have no value to pass
+        let mut values = Vec::new();
+        for argument in arguments {
+            if argument.ty.as_ref().unwrap().is_empty() {
+                self.generate_instruction(builder, block, argument, meta)?;
+            } else {
+                values.push(self.generate_expr(builder, block, argument, meta)?);
+            }
+        }
+        let return_ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+        let dest = match return_ty {
+            rxir::Type::Void => None,
+            _ => Some(builder.create_value(block, return_ty.clone(), None)),
+        };
+        builder.build_instruction(
+            block,
+            Instruction::Call {
+                dest: dest.clone(),
+                function: name.parse().expect("Invalid function name"),
+                arguments: values,
+                return_ty: return_ty.clone(),
+            },
+        );
+        Ok(dest.map(|id| Operand::TempVar { ty: return_ty, id }))
+    }
+
+    /// Generates the body of the innermost loop in `meta`, which jumps back to the start of the
+    /// loop at the end. Returns the values that the loop is exited with
+    fn generate_loop_body(
+        &mut self,
+        builder: &mut ModuleBuilder,
+        body_id: BlockId,
+        body: &Block,
+        meta: &mut BlockMeta,
+    ) -> Result<Vec<(Operand, BlockId)>, IrGenError> {
+        let mut current = body_id;
+        let result = self.generate_block(builder, &mut current, body, meta);
+        let loop_meta = meta.loops.pop().unwrap();
+        result?;
+        Self::build_jump(
+            builder,
+            &current,
+            &loop_meta.continue_block,
+            None,
+            &mut Vec::new(),
+        );
+        Ok(loop_meta.incoming)
+    }
+
+    /// Finds the loop that a `break` or `continue` refers to, the type checker makes sure it
+    /// exists
+    fn find_loop<'a>(meta: &'a mut BlockMeta, label: &Option<String>) -> &'a mut LoopMeta {
+        meta.loops
+            .iter_mut()
+            .rev()
+            .find(|loop_meta| label.is_none() || loop_meta.label == *label)
+            .unwrap()
+    }
+
+    /// Generates `&&` and `||`, the right side is only evaluated if the left side doesn't already
+    /// decide the result
+    fn generate_logical(
+        &mut self,
+        builder: &mut ModuleBuilder,
+        block: &mut BlockId,
+        op: BinaryOp,
+        lhs: &Expr,
+        rhs: &Expr,
+        meta: &mut BlockMeta,
+    ) -> Result<Operand, IrGenError> {
+        let lhs = self.generate_expr(builder, block, lhs, meta)?;
+        let rhs_id = builder.create_block(None);
+        let merge_id = builder.create_block(None);
+        let (then_block, else_block) = match op {
+            BinaryOp::And => (rhs_id.clone(), merge_id.clone()),
+            _ => (merge_id.clone(), rhs_id.clone()),
+        };
+        builder.build_instruction(
+            block,
+            Instruction::Branch {
+                condition: lhs,
+                then_block,
+                else_block,
+            },
+        );
+        // When the right side is skipped, the result is false for `&&` and true for `||`
+        let skipped = Operand::Immediate {
+            ty: rxir::Type::Bool,
+            value: (op == BinaryOp::Or) as u128,
+        };
+        let mut incoming = vec![(skipped, block.clone())];
+
+        let mut current = rhs_id;
+        let rhs = self.generate_expr(builder, &mut current, rhs, meta)?;
+        Self::build_jump(builder, &current, &merge_id, Some(rhs), &mut incoming);
+
+        *block = merge_id;
+        Ok(Self::build_phi(builder, block, rxir::Type::Bool, incoming))
+    }
+
+    /// Ends a branch of control flow with a jump to `target`, and records the value that the branch
+    /// produces. Branches that have already returned are left as they are
+    /// Continues after an expression of type `!`. The code that uses its value is never reached,
+    /// but it is still generated into a new block that nothing jumps to, so the returned operand is
+    /// only a placeholder
+    fn build_never(builder: &mut ModuleBuilder, block: &mut BlockId) -> Operand {
+        *block = builder.create_block(None);
+        Operand::Immediate {
+            ty: rxir::Type::Void,
+            value: 0,
+        }
+    }
+
+    fn build_jump(
+        builder: &mut ModuleBuilder,
+        block: &BlockId,
+        target: &BlockId,
+        value: Option<Operand>,
+        incoming: &mut Vec<(Operand, BlockId)>,
+    ) {
+        if builder.is_terminated(block) {
+            return;
+        }
+        builder.build_instruction(
+            block,
+            Instruction::Jump {
+                target: target.clone(),
+            },
+        );
+        if let Some(value) = value {
+            incoming.push((value, block.clone()));
+        }
+    }
+
+    /// Merges the values coming from multiple blocks, a single value doesn't need a phi
+    fn build_phi(
+        builder: &mut ModuleBuilder,
+        block: &BlockId,
+        ty: rxir::Type,
+        mut incoming: Vec<(Operand, BlockId)>,
+    ) -> Operand {
+        if incoming.len() == 1 {
+            return incoming.pop().unwrap().0;
+        }
+        let dest = builder.create_value(block, ty.clone(), None);
+        builder.build_instruction(
+            block,
+            Instruction::Phi {
+                dest: dest.clone(),
+                ty: ty.clone(),
+                incoming,
+            },
+        );
+        Operand::TempVar { ty, id: dest }
+    }
+
+    /// Builds a comparison, `ty` is the type of the result
+    fn build_compare(
+        builder: &mut ModuleBuilder,
+        block: &BlockId,
+        op: rxir::CompareOp,
+        lhs: Operand,
+        rhs: Operand,
+        ty: rxir::Type,
+    ) -> Operand {
+        let dest = builder.create_value(block, ty.clone(), None);
+        builder.build_instruction(
+            block,
+            Instruction::Compare {
+                dest: dest.clone(),
+                op,
+                lhs,
+                rhs,
+            },
+        );
+        Operand::TempVar { ty, id: dest }
+    }
+
+    /// Gets the RXIR operator for arithmetic and bitwise operators
+    fn rxir_binary_op(op: BinaryOp) -> Option<rxir::BinaryOp> {
+        let op = match op {
+            BinaryOp::Add => rxir::BinaryOp::Add,
+            BinaryOp::Sub => rxir::BinaryOp::Sub,
+            BinaryOp::Mul => rxir::BinaryOp::Mul,
+            BinaryOp::Div => rxir::BinaryOp::Div,
+            BinaryOp::Rem => rxir::BinaryOp::Rem,
+            BinaryOp::BitAnd => rxir::BinaryOp::And,
+            BinaryOp::BitOr => rxir::BinaryOp::Or,
+            BinaryOp::BitXor => rxir::BinaryOp::Xor,
+            BinaryOp::Shl => rxir::BinaryOp::Shl,
+            BinaryOp::Shr => rxir::BinaryOp::Shr,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    fn rxir_compare_op(op: BinaryOp) -> Option<rxir::CompareOp> {
+        let op = match op {
+            BinaryOp::Eq => rxir::CompareOp::Eq,
+            BinaryOp::Ne => rxir::CompareOp::Ne,
+            BinaryOp::Lt => rxir::CompareOp::Lt,
+            BinaryOp::Le => rxir::CompareOp::Le,
+            BinaryOp::Gt => rxir::CompareOp::Gt,
+            BinaryOp::Ge => rxir::CompareOp::Ge,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// Gets the conversion from one primitive type to another, or `None` if the value doesn't need
+    /// to change, like between integers of the same size
+    fn rxir_cast_op(from: &rxir::Type, to: &rxir::Type) -> Option<rxir::CastOp> {
+        use rxir::Type::{Bool, Float, Signed, Unsigned};
+        let op = match (from, to) {
+            (Bool, Signed(_) | Unsigned(_)) => rxir::CastOp::ZExt,
+            (Signed(from_bits) | Unsigned(from_bits), Signed(to_bits) | Unsigned(to_bits)) => {
+                match from_bits.cmp(to_bits) {
+                    std::cmp::Ordering::Greater => rxir::CastOp::Trunc,
+                    std::cmp::Ordering::Less if matches!(from, Signed(_)) => rxir::CastOp::SExt,
+                    std::cmp::Ordering::Less => rxir::CastOp::ZExt,
+                    std::cmp::Ordering::Equal => return None,
+                }
+            }
+            (Signed(_), Float(_)) => rxir::CastOp::SiToFp,
+            (Unsigned(_), Float(_)) => rxir::CastOp::UiToFp,
+            (Float(_), Signed(_)) => rxir::CastOp::FpToSi,
+            (Float(_), Unsigned(_)) => rxir::CastOp::FpToUi,
+            (Float(from_bits), Float(to_bits)) if from_bits > to_bits => rxir::CastOp::FpTrunc,
+            (Float(from_bits), Float(to_bits)) if from_bits < to_bits => rxir::CastOp::FpExt,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    /// Generates the instructions to evaluate an expression, and returns the operand holding the
+    /// result
+    fn generate_expr(
+        &mut self,
+        builder: &mut ModuleBuilder,
+        block: &mut BlockId,
+        expr: &Expr,
+        meta: &mut BlockMeta,
+    ) -> Result<Operand, IrGenError> {
+        match &expr.kind {
+            ExprKind::Literal(literal) => match literal {
+                Literal::Number(number) => Ok(Operand::Immediate {
+                    ty: self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?,
+                    value: match number.value {
+                        NumberValue::Int(value) => value,
+                        NumberValue::Float(value) => value.to_bits() as u128,
+                    },
+                }),
+                Literal::Bool(value) => Ok(Operand::Immediate {
+                    ty: rxir::Type::Bool,
+                    value: *value as u128,
+                }),
+                Literal::Str(value) => Ok(Self::build_string(
+                    builder,
+                    block,
+                    value.as_bytes().to_vec(),
+                )),
+                Literal::ByteStr(value) => Ok(Self::build_string(builder, block, value.clone())),
+            },
+            ExprKind::Variable(name) => {
+                // Values of type `()` are generated with `generate_value`, which doesn't load them
+                let Some(ptr) = meta.slot(name) else {
+                    return Err(IrGenError::Unsupported(
+                        "values of type `()`".to_string(),
+                        expr.span.clone(),
+                    ));
+                };
+                let ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+                let dest = builder.create_value(block, ty.clone(), None);
+                builder.build_instruction(
+                    block,
+                    Instruction::Load {
+                        dest: dest.clone(),
+                        src: ptr,
+                        ty: ty.clone(),
+                    },
+                );
+                Ok(Operand::TempVar { ty, id: dest })
+            }
+            ExprKind::Binary { op, lhs, rhs } if op.is_logical() => {
+                self.generate_logical(builder, block, *op, lhs, rhs, meta)
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                let ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+                let lhs = self.generate_expr(builder, block, lhs, meta)?;
+                let rhs = self.generate_expr(builder, block, rhs, meta)?;
+                match (Self::rxir_binary_op(*op), Self::rxir_compare_op(*op)) {
+                    (Some(op), _) => Ok(Self::build_binary(builder, block, op, lhs, rhs)),
+                    (_, Some(op)) => Ok(Self::build_compare(builder, block, op, lhs, rhs, ty)),
+                    _ => unreachable!("Logical operators are handled above"),
+                }
+            }
+            ExprKind::Unary { op, expr } => {
+                let operand = self.generate_expr(builder, block, expr, meta)?;
+                let ty = operand.ty();
+                let dest = builder.create_value(block, ty.clone(), None);
+                let op = match op {
+                    UnaryOp::Neg => rxir::UnaryOp::Neg,
+                    UnaryOp::Not => rxir::UnaryOp::Not,
+                };
+                builder.build_instruction(
+                    block,
+                    Instruction::Unary {
+                        dest: dest.clone(),
+                        op,
+                        operand,
+                    },
+                );
+                Ok(Operand::TempVar { ty, id: dest })
+            }
+            ExprKind::Cast { expr: operand, .. } => {
+                let ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+                let mut value = self.generate_expr(builder, block, operand, meta)?;
+                // A slice or a string is cast to the pointer to its first element
+                if let AstType::Slice { .. } | AstType::Str = operand.ty.as_ref().unwrap() {
+                    let dest = builder.create_value(block, ty.clone(), None);
+                    builder.build_instruction(
+                        block,
+                        Instruction::ExtractField {
+                            dest: dest.clone(),
+                            value,
+                            index: 0,
+                        },
+                    );
+                    value = Operand::TempVar {
+                        ty: ty.clone(),
+                        id: dest,
+                    };
+                }
+                let Some(op) = Self::rxir_cast_op(&value.ty(), &ty) else {
+                    // Integers with the same size and pointers only differ in how they are used,
+                    // so the value is reused with the new type
+                    return Ok(match value {
+                        Operand::Immediate { value, .. } => Operand::Immediate { ty, value },
+                        Operand::TempVar { id, .. } => Operand::TempVar { ty, id },
+                        Operand::Global { id, .. } => Operand::Global { ty, id },
+                    });
+                };
+                let dest = builder.create_value(block, ty.clone(), None);
+                builder.build_instruction(
+                    block,
+                    Instruction::Cast {
+                        dest: dest.clone(),
+                        op,
+                        value,
+                        ty: ty.clone(),
+                    },
+                );
+                Ok(Operand::TempVar { ty, id: dest })
+            }
+            ExprKind::StructLiteral { fields, .. } => {
+                let ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+                // The fields are evaluated in the order they are written
+                let mut values = Vec::new();
+                for field in fields {
+                    let index = self.field_index(expr.ty.as_ref().unwrap(), &field.name);
+                    if let Some(value) = self.generate_field(builder, block, &field.value, meta)? {
+                        values.push((index, value));
+                    }
+                }
+                Ok(Self::build_aggregate(builder, block, ty, values))
+            }
+            ExprKind::Variant { path, fields, .. } => {
+                let ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+                let variant = self.variant_index(path);
+                let declared = self.enums[&path.enum_name.value][variant].1.clone();
+                // The fields are evaluated in the order they are written
+                let mut values = Vec::new();
+                for field in fields {
+                    let index = declared
+                        .iter()
+                        .position(|(name, _)| *name == field.name)
+                        .expect("The type checker checks that the field exists");
+                    if let Some(value) = self.generate_field(builder, block, &field.value, meta)? {
+                        values.push((index, value));
+                    }
+                }
+                let ptr = builder.build_alloca(block, ty.clone(), None);
+                let rxir::Type::Struct(parts) = &ty else {
+                    unreachable!("Enums are lowered to a tag and a union");
+                };
+                let tag = Self::build_field_address(builder, block, ptr.clone(), &ty, 0);
+                builder.build_instruction(
+                    block,
+                    Instruction::Store {
+                        dest: tag,
+                        src: Operand::Immediate {
+                            ty: parts[0].clone(),
+                            value: variant as u128,
+                        },
+                    },
+                );
+                let place = Place {
+                    ptr: ptr.clone(),
+                    ty: expr.ty.clone().unwrap(),
+                };
+                let places = self.variant_places(builder, block, &place, variant, &expr.span)?;
+                for (index, value) in values {
+                    builder.build_instruction(
+                        block,
+                        Instruction::Store {
+                            dest: places[index].ptr.clone(),
+                            src: value,
+                        },
+                    );
+                }
+                let dest = builder.create_value(block, ty.clone(), None);
+                builder.build_instruction(
+                    block,
+                    Instruction::Load {
+                        dest: dest.clone(),
+                        src: ptr,
+                        ty: ty.clone(),
+                    },
+                );
+                Ok(Operand::TempVar { ty, id: dest })
+            }
+            ExprKind::Tuple(elements) if !elements.is_empty() => {
+                let ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+                let mut values = Vec::new();
+                for (index, element) in elements.iter().enumerate() {
+                    if let Some(value) = self.generate_field(builder, block, element, meta)? {
+                        values.push((index as u32, value));
+                    }
+                }
+                Ok(Self::build_aggregate(builder, block, ty, values))
+            }
+            ExprKind::Field {
+                expr: parent,
+                field,
+            } => {
+                let ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+                let value = self.generate_expr(builder, block, parent, meta)?;
+                let index = self.field_index(parent.ty.as_ref().unwrap(), &field.value);
+                let dest = builder.create_value(block, ty.clone(), None);
+                builder.build_instruction(
+                    block,
+                    Instruction::ExtractField {
+                        dest: dest.clone(),
+                        value,
+                        index,
+                    },
+                );
+                Ok(Operand::TempVar { ty, id: dest })
+            }
+            ExprKind::Array(elements) => {
+                let ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+                let rxir::Type::Array(element_ty, _) = &ty else {
+                    unreachable!("Arrays are lowered to RXIR arrays");
+                };
+                let element_ty = (**element_ty).clone();
+                let ptr = builder.build_alloca(block, ty.clone(), None);
+                for (index, element) in elements.iter().enumerate() {
+                    let Some(value) = self.generate_field(builder, block, element, meta)? else {
+                        continue;
+                    };
+                    let dest =
+                        builder.create_value(block, rxir::Type::pointer(element_ty.clone()), None);
+                    builder.build_instruction(
+                        block,
+                        Instruction::ElementAddress {
+                            dest: dest.clone(),
+                            src: ptr.clone(),
+                            ty: element_ty.clone(),
+                            index: Operand::Immediate {
+                                ty: usize_type(),
+                                value: index as u128,
+                            },
+                        },
+                    );
+                    builder.build_instruction(block, Instruction::Store { dest, src: value });
+                }
+                let dest = builder.create_value(block, ty.clone(), None);
+                builder.build_instruction(
+                    block,
+                    Instruction::Load {
+                        dest: dest.clone(),
+                        src: ptr,
+                        ty: ty.clone(),
+                    },
+                );
+                Ok(Operand::TempVar { ty, id: dest })
+            }
+            ExprKind::ArrayRepeat { value, count } => {
+                let ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+                // The value is evaluated once, and copied into every element
+                let Some(value) = self.generate_field(builder, block, value, meta)? else {
+                    return Ok(Self::build_aggregate(builder, block, ty, Vec::new()));
+                };
+                Ok(Self::build_array_repeat(builder, block, ty, value, *count))
+            }
+            ExprKind::Index { .. } => {
+                let ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+                let ptr = self.generate_element_address(builder, block, expr, meta)?;
+                let dest = builder.create_value(block, ty.clone(), None);
+                builder.build_instruction(
+                    block,
+                    Instruction::Load {
+                        dest: dest.clone(),
+                        src: ptr,
+                        ty: ty.clone(),
+                    },
+                );
+                Ok(Operand::TempVar { ty, id: dest })
+            }
+            // The type checker makes sure the receiver of `len` is an array, a slice or a string,
+            // and the receiver of `as_bytes` is a string
+            ExprKind::MethodCall {
+                receiver, method, ..
+            } => match (method.value.as_str(), receiver.ty.as_ref().unwrap()) {
+                // A string is already a pointer to its bytes and their number
+                ("as_bytes", _) => self.generate_expr(builder, block, receiver, meta),
+                (_, AstType::Array(_, len)) => {
+                    // The receiver is still evaluated for its side effects, without loading it
+                    self.generate_place(builder, block, receiver, meta)?;
+                    Ok(Operand::Immediate {
+                        ty: usize_type(),
+                        value: *len as u128,
+                    })
+                }
+                _ => {
+                    let slice = self.generate_expr(builder, block, receiver, meta)?;
+                    let dest = builder.create_value(block, usize_type(), None);
+                    builder.build_instruction(
+                        block,
+                        Instruction::ExtractField {
+                            dest: dest.clone(),
+                            value: slice,
+                            index: 1,
+                        },
+                    );
+                    Ok(Operand::TempVar {
+                        ty: usize_type(),
+                        id: dest,
+                    })
+                }
+            },
+            ExprKind::Ref { expr: operand, .. } => {
+                let ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+                let AstType::Array(_, len) = operand.ty.as_ref().unwrap() else {
+                    let ptr = self.generate_place(builder, block, operand, meta)?;
+                    return Ok(Operand::TempVar { ty, id: ptr });
+                };
+                // Borrowing an array makes a slice that points to its first element
+                let rxir::Type::Struct(parts) = &ty else {
+                    unreachable!("Slices are lowered to a pointer and a length");
+                };
+                let ptr = self.generate_place(builder, block, operand, meta)?;
+                let fields = vec![
+                    (
+                        0,
+                        Operand::TempVar {
+                            ty: parts[0].clone(),
+                            id: ptr,
+                        },
+                    ),
+                    (
+                        1,
+                        Operand::Immediate {
+                            ty: usize_type(),
+                            value: *len as u128,
+                        },
+                    ),
+                ];
+                Ok(Self::build_aggregate(builder, block, ty, fields))
+            }
+            ExprKind::Deref(_) => {
+                let ty = self.rxir_type(expr.ty.as_ref().unwrap(), &expr.span)?;
+                let ptr = self.generate_place(builder, block, expr, meta)?;
+                let dest = builder.create_value(block, ty.clone(), None);
+                builder.build_instruction(
+                    block,
+                    Instruction::Load {
+                        dest: dest.clone(),
+                        src: ptr,
+                        ty: ty.clone(),
+                    },
+                );
+                Ok(Operand::TempVar { ty, id: dest })
+            }
+            ExprKind::Block(_)
+            | ExprKind::Unsafe(_)
+            | ExprKind::If { .. }
+            | ExprKind::Match { .. }
+            | ExprKind::Loop { .. }
+            | ExprKind::Call { .. } => {
+                let value = match &expr.kind {
+                    ExprKind::Block(body) | ExprKind::Unsafe(body) => {
+                        self.generate_block(builder, block, body, meta)?
+                    }
+                    ExprKind::If { .. } => self.generate_if(builder, block, expr, meta)?,
+                    ExprKind::Match { .. } => self.generate_match(builder, block, expr, meta)?,
+                    ExprKind::Loop { .. } => self.generate_loop(builder, block, expr, meta)?,
+                    _ => self.generate_call(builder, block, expr, meta)?,
                 };
-                builder.build_instruction(block, rxir::Instruction::Return { value });
+                match value {
+                    Some(value) => Ok(value),
+                    None if *expr.ty.as_ref().unwrap() == AstType::Never => {
+                        Ok(Self::build_never(builder, block))
+                    }
+                    // There is no operand for `()`, so it can't be used as a value yet
+                    None => Err(IrGenError::Unsupported(
+                        "values of type `()`".to_string(),
+                        expr.span.clone(),
+                    )),
+                }
+            }
+            // Control flow leaves the expression, so it never produces a value
+            ExprKind::Return(_) | ExprKind::Break { .. } | ExprKind::Continue { .. } => {
+                self.generate_instruction(builder, block, expr, meta)?;
+                Ok(Self::build_never(builder, block))
             }
-            _ => todo!(),
+            _ => Err(IrGenError::Unsupported(
+                "expressions of this kind".to_string(),
+                expr.span.clone(),
+            )),
         }
     }
 
-    fn rxir_type(ty: &AstType) -> rxir::Type {
+    /// Gets the position of a field in the declaration of a struct type, or of an element of a
+    /// tuple
+    fn field_index(&self, ty: &AstType, field: &str) -> u32 {
+        match ty {
+            AstType::Named(name) => self.structs[name]
+                .iter()
+                .position(|(name, _)| name == field)
+                .expect("The type checker checks that the field exists")
+                as u32,
+            AstType::Tuple(_) => field.parse().expect("Tuple fields are indices"),
+            _ => unreachable!("The type checker only allows fields on structs and tuples"),
+        }
+    }
+
+    /// Converts the type of a field of an aggregate. A field of type `()` is an empty struct, which
+    /// takes no space
+    fn rxir_field(&self, ty: &AstType, span: &Span) -> Result<rxir::Type, IrGenError> {
+        match self.rxir_type(ty, span)? {
+            rxir::Type::Void => Ok(rxir::Type::Struct(Vec::new())),
+            ty => Ok(ty),
+        }
+    }
+
+    /// Converts the types of the fields of a struct or tuple
+    fn rxir_fields<'a>(
+        &self,
+        types: impl Iterator<Item = &'a AstType>,
+        span: &Span,
+    ) -> Result<Vec<rxir::Type>, IrGenError> {
+        types.map(|ty| self.rxir_field(ty, span)).collect()
+    }
+
+    /// Converts a type to the RXIR type, the span is the node that the type came from
+    fn rxir_type(&self, ty: &AstType, span: &Span) -> Result<rxir::Type, IrGenError> {
         use redox_ast::NumberKind;
         match ty {
             AstType::Tuple(types) => {
                 if types.is_empty() {
-                    rxir::Type::Void
+                    Ok(rxir::Type::Void)
                 } else {
-                    unimplemented!("Tuple types are not supported in the IR yet")
+                    Ok(rxir::Type::Struct(self.rxir_fields(types.iter(), span)?))
                 }
             }
-            AstType::Number(ty) => match ty.kind {
-                NumberKind::Signed => {
-                    if ty.bits == 32 {
-                        rxir::Type::Signed32
-                    } else {
-                        unimplemented!("Only signed 32-bit integers are supported in the IR yet")
-                    }
+            AstType::Number(number) => Ok(match number.kind {
+                NumberKind::Signed => rxir::Type::Signed(number.bits),
+                NumberKind::Unsigned => rxir::Type::Unsigned(number.bits),
+                NumberKind::Float => rxir::Type::Float(number.bits),
+            }),
+            AstType::Bool => Ok(rxir::Type::Bool),
+            AstType::Named(name) => match self.structs.get(name) {
+                Some(fields) => {
+                    let fields = self.rxir_fields(fields.iter().map(|(_, ty)| ty), span)?;
+                    Ok(rxir::Type::Struct(fields))
+                }
+                // An enum is a tag that says which variant it holds, followed by a union of the
+                // fields of every variant
+                None => {
+                    let variants = &self.enums[name];
+                    let tag_bits = match variants.len() {
+                        0..=0x100 => 8,
+                        0x101..=0x10000 => 16,
+                        _ => 32,
+                    };
+                    let payloads = variants
+                        .iter()
+                        .map(|(_, fields)| {
+                            let fields = self.rxir_fields(fields.iter().map(|(_, ty)| ty), span)?;
+                            Ok(rxir::Type::Struct(fields))
+                        })
+                        .collect::<Result<_, IrGenError>>()?;
+                    Ok(rxir::Type::Struct(vec![
+                        rxir::Type::Unsigned(tag_bits),
+                        rxir::Type::Union(payloads),
+                    ]))
                 }
-                _ => unimplemented!("Only signed integers are supported in the IR yet"),
             },
+            // The elements of an empty array literal have the type `!`, so like `()` they take no
+            // space
+            AstType::Array(ty, len) => {
+                let element_ty = self.rxir_field(ty, span)?;
+                Ok(rxir::Type::Array(Box::new(element_ty), *len))
+            }
+            // A slice is a pointer to its first element, and the number of elements
+            AstType::Slice { ty, .. } => {
+                let element_ty = self.rxir_field(ty, span)?;
+                Ok(rxir::Type::Struct(vec![
+                    rxir::Type::pointer(element_ty),
+                    usize_type(),
+                ]))
+            }
+            // A string is a slice of its UTF-8 bytes
+            AstType::Str => Ok(rxir::Type::Struct(vec![
+                rxir::Type::pointer(rxir::Type::Unsigned(8)),
+                usize_type(),
+            ])),
+            // Mutability is only checked by the type checker, so references and raw pointers are
+            // the same. Like a field, a `()` they point to is an empty struct
+            AstType::Ref { ty, .. } | AstType::Pointer { ty, .. } => {
+                Ok(rxir::Type::pointer(self.rxir_field(ty, span)?))
+            }
+            // Values of type `!` never exist, so they are never stored
+            AstType::Never => Ok(rxir::Type::Void),
+            AstType::Infer(_) => unreachable!("The type checker resolves every inference variable"),
         }
     }
 }
+
+/// The RXIR type of `usize`, which is used for lengths and indices
+fn usize_type() -> rxir::Type {
+    rxir::Type::Unsigned(NumberType::POINTER_BITS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redox_parser::Parser;
+    use redox_type_checker::TypeChecker;
+
+    fn generate(source: &str) -> Result<Module, IrGenError> {
+        let mut ast = Parser::with_source(source).parse().into_result().unwrap();
+        TypeChecker::new().type_check(&mut ast).unwrap();
+        let ops = ModuleOps {
+            name: "test".to_string(),
+            bounds_checks: true,
+        };
+        IrGenerator::new().generate_module(ops, ast)
+    }
+
+    /// Checks the RXIR that a module is lowered to
+    fn assert_ir(source: &str, expected: &str) {
+        assert_eq!(generate(source).unwrap().to_string(), expected);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        // The parser only accepts functions at the top level, so the statement is moved out of the
+        // function once it has been type checked
+        let source = "fn foo() -> i32 { return 1; }";
+        let mut ast = Parser::with_source(source).parse().into_result().unwrap();
+        TypeChecker::new().type_check(&mut ast).unwrap();
+        let TopLevelKind::Expr(function) = &mut ast[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &mut function.kind else {
+            panic!("Expected a function");
+        };
+        let statement = function.body.statements.remove(0);
+        ast.push(TopLevel::expr(statement));
+        let ops = ModuleOps {
+            name: "test".to_string(),
+            bounds_checks: true,
+        };
+        let Err(err) = IrGenerator::new().generate_module(ops, ast) else {
+            panic!("Expected an unsupported error");
+        };
+        let IrGenError::Unsupported(construct, span) = &err;
+        assert_eq!(construct, "top level expressions");
+        assert_eq!(&source[span.clone()], "return 1");
+        assert_eq!(
+            err.to_diagnostic().message,
+            "top level expressions are not supported by the IR generator yet"
+        );
+    }
+
+    #[test]
+    fn test_logical() {
+        // `&&` and `||` only evaluate the right side when it decides the result
+        assert_ir(
+            "fn f(a: bool, b: bool) -> bool { return a && b || !a; }",
+            "\
+module test
+fn bool f (%1: bool, %2: bool) {
+@0:
+\t%3 = alloca bool
+\tstore bool %1, %3
+\t%4 = alloca bool
+\tstore bool %2, %4
+\t%5 = load bool, %3
+\tbranch bool %5, @6, @7
+@6:
+\t%8 = load bool, %4
+\tjump @7
+@7:
+\t%9 = phi bool [false, @0], [%8, @6]
+\tbranch bool %9, @11, @10
+@10:
+\t%12 = load bool, %3
+\t%13 = not bool %12
+\tjump @11
+@11:
+\t%14 = phi bool [true, @7], [%13, @10]
+\treturn bool %14
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_if() {
+        // The value of an `if` is a phi of the values of its branches
+        assert_ir(
+            "fn f(a: i32) -> i32 { return if a > 0 { 1 } else { 2 }; }",
+            "\
+module test
+fn i32 f (%1: i32) {
+@0:
+\t%2 = alloca i32
+\tstore i32 %1, %2
+\t%3 = load i32, %2
+\t%4 = cmp gt i32 %3, 0i32
+\tbranch bool %4, @5, @6
+@5:
+\tjump @7
+@6:
+\tjump @7
+@7:
+\t%8 = phi i32 [1i32, @5], [2i32, @6]
+\treturn i32 %8
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_loops() {
+        // `continue 'outer` jumps back to the start of the outer loop from the inner one
+        assert_ir(
+            "fn f() -> i32 { let mut i = 0; return 'outer: loop { while i < 10 { i += 1; if i == 5 { continue 'outer; } } break i; }; }",
+            "\
+module test
+fn i32 f () {
+@0:
+\t%1 = alloca i32
+\tstore i32 0i32, %1
+\tjump @2
+@2:
+\tjump @4
+@4:
+\t%7 = load i32, %1
+\t%8 = cmp lt i32 %7, 10i32
+\tbranch bool %8, @5, @6
+@5:
+\t%9 = load i32, %1
+\t%10 = add i32 %9, 1i32
+\tstore i32 %10, %1
+\t%11 = load i32, %1
+\t%12 = cmp eq i32 %11, 5i32
+\tbranch bool %12, @13, @14
+@13:
+\tjump @2
+@14:
+\tjump @4
+@6:
+\t%15 = load i32, %1
+\tjump @3
+@3:
+\treturn i32 %15
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_unit_variables() {
+        // Variables of type `()` have no stack slot, so using or assigning them does nothing
+        assert_ir(
+            "fn f(a: bool) -> i32 { let mut u = loop { break; }; u = if a {}; u; return 1; }",
+            "\
+module test
+fn i32 f (%1: bool) {
+@0:
+\t%2 = alloca bool
+\tstore bool %1, %2
+\tjump @3
+@3:
+\tjump @4
+@4:
+\t%5 = load bool, %2
+\tbranch bool %5, @6, @7
+@6:
+\tjump @7
+@7:
+\treturn i32 1i32
+}
+",
+        );
+        // A reference to one still needs an address, which points to an empty temporary
+        assert_ir(
+            "fn f() { let u = (); let r = &u; *r; let p = &(); }",
+            "\
+module test
+fn void f () {
+@0:
+\t%1 = alloca {}
+\t%2 = alloca {}*
+\tstore {}* %1, %2
+\t%3 = load {}*, %2
+\t%4 = alloca {}
+\t%5 = alloca {}*
+\tstore {}* %4, %5
+\treturn void
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_unit_calls() {
+        // The result of a call that returns `()` can be bound, passed and returned, and arguments
+        // of type `()` aren't passed
+        assert_ir(
+            "fn g() {} fn h(a: (), b: i32) -> i32 { a; b } fn k() { let x = g(); return x; } \
+             fn f() -> i32 { let x = g(); let y = x; k(); h(y, 1) }",
+            "\
+module test
+fn void g () {
+@0:
+\treturn void
+}
+fn i32 h (%2: i32) {
+@1:
+\t%3 = alloca i32
+\tstore i32 %2, %3
+\t%4 = load i32, %3
+\treturn i32 %4
+}
+fn void k () {
+@5:
+\tcall void g()
+\treturn void
+}
+fn i32 f () {
+@6:
+\tcall void g()
+\tcall void k()
+\t%7 = call i32 h(i32 1i32)
+\treturn i32 %7
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_number_types() {
+        // The signedness of division, remainder, shifts and comparisons comes from the type of
+        // their operands, which the backend picks the instruction from
+        assert_ir(
+            "fn f(a: u8, b: u8) -> bool { a / b >> b < a % b } \
+             fn g(a: i64, b: i64) -> bool { a / b >> b < a % b }",
+            "\
+module test
+fn bool f (%1: u8, %2: u8) {
+@0:
+\t%3 = alloca u8
+\tstore u8 %1, %3
+\t%4 = alloca u8
+\tstore u8 %2, %4
+\t%5 = load u8, %3
+\t%6 = load u8, %4
+\t%7 = div u8 %5, %6
+\t%8 = load u8, %4
+\t%9 = shr u8 %7, %8
+\t%10 = load u8, %3
+\t%11 = load u8, %4
+\t%12 = rem u8 %10, %11
+\t%13 = cmp lt u8 %9, %12
+\treturn bool %13
+}
+fn bool g (%15: i64, %16: i64) {
+@14:
+\t%17 = alloca i64
+\tstore i64 %15, %17
+\t%18 = alloca i64
+\tstore i64 %16, %18
+\t%19 = load i64, %17
+\t%20 = load i64, %18
+\t%21 = div i64 %19, %20
+\t%22 = load i64, %18
+\t%23 = shr i64 %21, %22
+\t%24 = load i64, %17
+\t%25 = load i64, %18
+\t%26 = rem i64 %24, %25
+\t%27 = cmp lt i64 %23, %26
+\treturn bool %27
+}
+",
+        );
+        assert_ir(
+            "fn f(a: f64, b: f64) -> bool { -a * b % a >= b } \
+             fn g(a: f32, b: f32) -> f32 { a / b }",
+            "\
+module test
+fn bool f (%1: f64, %2: f64) {
+@0:
+\t%3 = alloca f64
+\tstore f64 %1, %3
+\t%4 = alloca f64
+\tstore f64 %2, %4
+\t%5 = load f64, %3
+\t%6 = neg f64 %5
+\t%7 = load f64, %4
+\t%8 = mul f64 %6, %7
+\t%9 = load f64, %3
+\t%10 = rem f64 %8, %9
+\t%11 = load f64, %4
+\t%12 = cmp ge f64 %10, %11
+\treturn bool %12
+}
+fn f32 g (%14: f32, %15: f32) {
+@13:
+\t%16 = alloca f32
+\tstore f32 %14, %16
+\t%17 = alloca f32
+\tstore f32 %15, %17
+\t%18 = load f32, %16
+\t%19 = load f32, %17
+\t%20 = div f32 %18, %19
+\treturn f32 %20
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_diverging_operands() {
+        // The code that uses an operand of type `!` is never reached, and the `if` only gets a
+        // value from the branch that produces one
+        assert_ir(
+            "fn f(a: bool) -> i32 { loop { let y = if a { 1 + break 3 } else { 2 }; return y; } }",
+            "\
+module test
+fn i32 f (%1: bool) {
+@0:
+\t%2 = alloca bool
+\tstore bool %1, %2
+\tjump @3
+@3:
+\t%5 = load bool, %2
+\tbranch bool %5, @6, @7
+@6:
+\tjump @4
+@4:
+\treturn i32 3i32
+@7:
+\tjump @8
+@8:
+\t%11 = phi i32 [2i32, @7]
+\t%12 = alloca i32
+\tstore i32 %11, %12
+\t%13 = load i32, %12
+\treturn i32 %13
+}
+",
+        );
+        assert_ir(
+            "fn f() -> i32 { let x = 1 + return 2; x }",
+            "\
+module test
+fn i32 f () {
+@0:
+\treturn i32 2i32
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_unit_fields() {
+        // Fields and elements of type `()` are empty structs, which are never stored
+        assert_ir(
+            "struct S { a: i32, u: () } \
+             fn f() -> i32 { let t = (1, ()); let (a, u) = t; let s = S { a, u }; s.u; s.a }",
+            "\
+module test
+fn i32 f () {
+@0:
+\t%1 = alloca {i32, {}}
+\t%2 = fieldaddr {i32, {}} %1, 0
+\tstore i32 1i32, %2
+\t%3 = load {i32, {}}, %1
+\t%4 = alloca {i32, {}}
+\tstore {i32, {}} %3, %4
+\t%5 = load {i32, {}}, %4
+\t%6 = extract {i32, {}} %5, 0
+\t%7 = alloca i32
+\tstore i32 %6, %7
+\t%8 = load i32, %7
+\t%9 = alloca {i32, {}}
+\t%10 = fieldaddr {i32, {}} %9, 0
+\tstore i32 %8, %10
+\t%11 = load {i32, {}}, %9
+\t%12 = alloca {i32, {}}
+\tstore {i32, {}} %11, %12
+\t%13 = load {i32, {}}, %12
+\t%14 = load {i32, {}}, %12
+\t%15 = extract {i32, {}} %14, 0
+\treturn i32 %15
+}
+",
+        );
+        assert_ir(
+            "enum E { A(()), B } \
+             fn f(e: E) -> i32 { let v = E::A(()); match e { E::A(u) => { u; 1 } E::B => 0 } }",
+            "\
+module test
+fn i32 f (%1: {u8, union {{{}}, {}}}) {
+@0:
+\t%2 = alloca {u8, union {{{}}, {}}}
+\tstore {u8, union {{{}}, {}}} %1, %2
+\t%3 = alloca {u8, union {{{}}, {}}}
+\t%4 = fieldaddr {u8, union {{{}}, {}}} %3, 0
+\tstore u8 0u8, %4
+\t%5 = fieldaddr {u8, union {{{}}, {}}} %3, 1
+\t%6 = fieldaddr union {{{}}, {}} %5, 0
+\t%7 = fieldaddr {{}} %6, 0
+\t%8 = load {u8, union {{{}}, {}}}, %3
+\t%9 = alloca {u8, union {{{}}, {}}}
+\tstore {u8, union {{{}}, {}}} %8, %9
+\t%10 = load {u8, union {{{}}, {}}}, %2
+\t%12 = alloca {u8, union {{{}}, {}}}
+\tstore {u8, union {{{}}, {}}} %10, %12
+\t%15 = fieldaddr {u8, union {{{}}, {}}} %12, 0
+\t%16 = load u8, %15
+\tswitch u8 %16, @21 [0: @17]
+@17:
+\t%18 = fieldaddr {u8, union {{{}}, {}}} %12, 1
+\t%19 = fieldaddr union {{{}}, {}} %18, 0
+\t%20 = fieldaddr {{}} %19, 0
+\tjump @13
+@13:
+\tjump @11
+@21:
+\tjump @14
+@14:
+\tjump @11
+@11:
+\t%22 = phi i32 [1i32, @13], [0i32, @14]
+\treturn i32 %22
+}
+",
+        );
+        // The index of an element of type `()` is still checked
+        assert_ir(
+            "fn f(i: usize) { let mut a = [(), ()]; a[i]; a[0] = (); let b = [(); 3]; }",
+            "\
+module test
+fn void f (%1: u64) {
+@0:
+\t%2 = alloca u64
+\tstore u64 %1, %2
+\t%3 = alloca [2 x {}]
+\t%4 = load [2 x {}], %3
+\t%5 = alloca [2 x {}]
+\tstore [2 x {}] %4, %5
+\t%6 = load u64, %2
+\t%7 = cmp lt u64 %6, 2u64
+\tbranch bool %7, @8, @9
+@8:
+\t%10 = elementaddr {} %5, u64 %6
+\t%11 = elementaddr {} %5, u64 0u64
+\t%12 = alloca [3 x {}]
+\t%13 = load [3 x {}], %12
+\t%14 = alloca [3 x {}]
+\tstore [3 x {}] %13, %14
+\treturn void
+@9:
+\ttrap
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_match_enum() {
+        // The tag of the enum chooses the arm, which reads the fields from the payload
+        assert_ir(
+            "enum E { A(i32), B } fn f(e: E) -> i32 { match e { E::A(x) => x, E::B => 0 } }",
+            "\
+module test
+fn i32 f (%1: {u8, union {{i32}, {}}}) {
+@0:
+\t%2 = alloca {u8, union {{i32}, {}}}
+\tstore {u8, union {{i32}, {}}} %1, %2
+\t%3 = load {u8, union {{i32}, {}}}, %2
+\t%5 = alloca {u8, union {{i32}, {}}}
+\tstore {u8, union {{i32}, {}}} %3, %5
+\t%6 = alloca i32
+\t%9 = fieldaddr {u8, union {{i32}, {}}} %5, 0
+\t%10 = load u8, %9
+\tswitch u8 %10, @16 [0: @11]
+@11:
+\t%12 = fieldaddr {u8, union {{i32}, {}}} %5, 1
+\t%13 = fieldaddr union {{i32}, {}} %12, 0
+\t%14 = fieldaddr {i32} %13, 0
+\t%15 = load i32, %14
+\tstore i32 %15, %6
+\tjump @7
+@7:
+\t%17 = load i32, %6
+\tjump @4
+@16:
+\tjump @8
+@8:
+\tjump @4
+@4:
+\t%18 = phi i32 [%17, @7], [0i32, @8]
+\treturn i32 %18
+}
+",
+        );
+    }
+
+    #[test]
+    fn test_slice_index() {
+        // Lengths and indices are `usize`, which is as wide as a pointer
+        let generator = IrGenerator::new();
+        let slice = AstType::Slice {
+            ty: Box::new(AstType::Number(NumberType::I32)),
+            mutable: false,
+        };
+        let ty = generator.rxir_type(&slice, &(0..0)).unwrap();
+        assert_eq!(ty.to_string(), "{i32*, u64}");
+        let ty = generator
+            .rxir_type(&AstType::Number(NumberType::USIZE), &(0..0))
+            .unwrap();
+        assert_eq!(ty.to_string(), "u64");
+        assert_ir(
+            "fn f(s: &[i32]) -> usize { s.len() }",
+            "\
+module test
+fn u64 f (%1: {i32*, u64}) {
+@0:
+\t%2 = alloca {i32*, u64}
+\tstore {i32*, u64} %1, %2
+\t%3 = load {i32*, u64}, %2
+\t%4 = extract {i32*, u64} %3, 1
+\treturn u64 %4
+}
+",
+        );
+        // The index is checked against the length of the slice before it is used
+        assert_ir(
+            "fn f(s: &[i32], i: usize) -> i32 { s[i] }",
+            "\
+module test
+fn i32 f (%1: {i32*, u64}, %2: u64) {
+@0:
+\t%3 = alloca {i32*, u64}
+\tstore {i32*, u64} %1, %3
+\t%4 = alloca u64
+\tstore u64 %2, %4
+\t%5 = load {i32*, u64}, %3
+\t%6 = extract {i32*, u64} %5, 0
+\t%7 = extract {i32*, u64} %5, 1
+\t%8 = load u64, %4
+\t%9 = cmp lt u64 %8, %7
+\tbranch bool %9, @10, @11
+@10:
+\t%12 = elementaddr i32 %6, u64 %8
+\t%13 = load i32, %12
+\treturn i32 %13
+@11:
+\ttrap
+}
+",
+        );
+    }
+}
@@ -1,6 +1,7 @@
-use std::ops::Range;
 pub use logos::{Logos as LexerTrait, Span};
 use logos::Logos;
+use redox_ast::{Diagnostic, NumberKind, NumberLiteral, NumberType, NumberValue};
+use std::str::FromStr;
 
 #[derive(Default, Debug, Clone, PartialEq, thiserror::Error)]
 pub enum LexerError {
@@ -8,6 +9,15 @@ pub enum LexerError {
     NonAsciiCharacter,
     ParseIntError(std::num::ParseIntError),
     ParseFloatError(std::num::ParseFloatError),
+    /// A suffix that isn't a number type, or that can't be used with the literal, like `1.5i32`
+    InvalidSuffix(String),
+    /// A string literal without the closing quote
+    UnterminatedString,
+    /// An escape sequence in a string literal that isn't valid, like `\q` or `\u{110000}`, and
+    /// where it is in the source
+    InvalidEscape(String, Span),
+    /// A character in a byte string that isn't ASCII, which needs to be written as a `\x` escape
+    NonAsciiByte(char),
 }
 
 impl std::fmt::Display for LexerError {
@@ -16,6 +26,51 @@ impl std::fmt::Display for LexerError {
             Self::NonAsciiCharacter => write!(f, "Non-ascii character"),
             Self::ParseIntError(err) => write!(f, "Parse int error: {}", err),
             Self::ParseFloatError(err) => write!(f, "Parse float error: {}", err),
+            Self::InvalidSuffix(suffix) => write!(f, "Invalid suffix {suffix}"),
+            Self::UnterminatedString => write!(f, "Unterminated string literal"),
+            Self::InvalidEscape(escape, _) => write!(f, "Invalid escape {escape}"),
+            Self::NonAsciiByte(c) => write!(f, "Non-ascii character {c:?} in byte string"),
+        }
+    }
+}
+
+impl LexerError {
+    /// Unlike the errors of the later stages, this isn't `ToDiagnostic`: logos creates the error
+    /// for input that doesn't match any token with `Default`, so most errors can't hold their
+    /// span. The caller needs to provide it, which is the span of the token from `Lexer::span`.
+    /// An invalid escape is only part of its string literal, so it carries its own span instead
+    pub fn to_diagnostic(&self, span: Span) -> Diagnostic {
+        match self {
+            Self::NonAsciiCharacter => Diagnostic::error("unexpected character", span)
+                .with_label("this character is not valid in redox source code"),
+            Self::ParseIntError(err) => {
+                Diagnostic::error("invalid integer literal", span).with_label(err.to_string())
+            }
+            Self::ParseFloatError(err) => {
+                Diagnostic::error("invalid float literal", span).with_label(err.to_string())
+            }
+            Self::InvalidSuffix(suffix) => {
+                Diagnostic::error(format!("invalid suffix `{suffix}` for number literal"), span)
+                    .with_label("invalid suffix")
+                    .with_help("the suffix must be a number type like `u8` or `f32`, and floats can only have a float suffix")
+            }
+            Self::UnterminatedString => Diagnostic::error("unterminated string literal", span)
+                .with_label("the string starts here, but there is no closing `\"`"),
+            // Only the escape is underlined, not the whole literal
+            Self::InvalidEscape(escape, escape_span) => Diagnostic::error(
+                format!("invalid escape `{escape}` in string literal"),
+                escape_span.clone(),
+            )
+            .with_label("invalid escape")
+            .with_help(
+                "valid escapes are `\\n`, `\\r`, `\\t`, `\\\\`, `\\0`, `\\\"`, `\\'`, `\\x` with \
+                 two hex digits, and `\\u{..}` with a unicode code point",
+            ),
+            Self::NonAsciiByte(c) => {
+                Diagnostic::error(format!("non-ascii character {c:?} in byte string"), span)
+                    .with_label("byte strings can only contain ascii characters")
+                    .with_help("write the bytes of the character as `\\x` escapes")
+            }
         }
     }
 }
@@ -35,6 +90,38 @@ pub enum Token {
     KwFn,
     #[token("return")]
     KwReturn,
+    #[token("let")]
+    KwLet,
+    #[token("mut")]
+    KwMut,
+    #[token("true")]
+    KwTrue,
+    #[token("false")]
+    KwFalse,
+    #[token("if")]
+    KwIf,
+    #[token("else")]
+    KwElse,
+    #[token("while")]
+    KwWhile,
+    #[token("loop")]
+    KwLoop,
+    #[token("break")]
+    KwBreak,
+    #[token("continue")]
+    KwContinue,
+    #[token("as")]
+    KwAs,
+    #[token("struct")]
+    KwStruct,
+    #[token("enum")]
+    KwEnum,
+    #[token("match")]
+    KwMatch,
+    #[token("unsafe")]
+    KwUnsafe,
+    #[token("const")]
+    KwConst,
 
     #[token(";")]
     Semicolon,
@@ -51,37 +138,358 @@ pub enum Token {
     #[token("}")]
     RightBrace,
 
+    #[token("[")]
+    LeftBracket,
+
+    #[token("]")]
+    RightBracket,
+
     #[token("->")]
     Arrow,
+    #[token("=>")]
+    FatArrow,
 
     #[token(",")]
     Comma,
     #[token(":")]
     Colon,
+    #[token("::")]
+    PathSep,
+    #[token(".")]
+    Dot,
+    #[token("=")]
+    Assign,
+    #[token("+=")]
+    PlusAssign,
+    #[token("-=")]
+    MinusAssign,
+    #[token("*=")]
+    StarAssign,
+    #[token("/=")]
+    SlashAssign,
+    #[token("%=")]
+    PercentAssign,
+    #[token("&=")]
+    AmpAssign,
+    #[token("|=")]
+    PipeAssign,
+    #[token("^=")]
+    CaretAssign,
+    #[token("<<=")]
+    ShlAssign,
+    #[token(">>=")]
+    ShrAssign,
+
+    #[token("+")]
+    Plus,
+    #[token("-")]
+    Minus,
+    #[token("*")]
+    Star,
+    #[token("/")]
+    Slash,
+    #[token("%")]
+    Percent,
+    #[token("!")]
+    Bang,
+    #[token("&")]
+    Amp,
+    #[token("|")]
+    Pipe,
+    #[token("^")]
+    Caret,
+    #[token("<<")]
+    Shl,
+    #[token(">>")]
+    Shr,
+    #[token("&&")]
+    AndAnd,
+    #[token("||")]
+    OrOr,
+    #[token("==")]
+    EqEq,
+    #[token("!=")]
+    NotEq,
+    #[token("<")]
+    Lt,
+    #[token("<=")]
+    Le,
+    #[token(">")]
+    Gt,
+    #[token(">=")]
+    Ge,
 
     #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice().to_string())]
     Ident(String),
+    /// A loop label like `'outer`, without the quote
+    #[regex(r"'[a-zA-Z_][a-zA-Z0-9_]*", |lex| lex.slice()[1..].to_string())]
+    Label(String),
 
-    #[regex(r"[0-9]+", parse_num_literal)]
+    /// A number literal, prefixes for other bases like `0x` and suffixes like `u8` are matched as
+    /// part of the trailing letters, and validated when the literal is parsed
+    #[regex(
+        r"[0-9][0-9_]*(\.[0-9][0-9_]*)?([eE][+-]?[0-9][0-9_]*)?[a-zA-Z0-9_]*",
+        parse_num_literal
+    )]
     NumberLit(redox_ast::NumberLiteral),
+    /// A string literal `"..."` with its escapes replaced, or a raw string `r#"..."#` that doesn't
+    /// have escapes
+    #[regex(r#""([^"\\]|\\(.|\n))*""#, |lex| lex_string(lex, false).map(into_string))]
+    #[regex(r#""([^"\\]|\\(.|\n))*"#, |_| Err(LexerError::UnterminatedString))]
+    #[regex(r##"r#*""##, |lex| lex_raw_string(lex).map(into_string))]
+    StrLit(String),
+    /// A byte string `b"..."`, which can only contain ascii characters and escapes for bytes, or a
+    /// raw byte string `br#"..."#`
+    #[regex(r#"b"([^"\\]|\\(.|\n))*""#, |lex| lex_string(lex, true))]
+    #[regex(r#"b"([^"\\]|\\(.|\n))*"#, |_| Err(LexerError::UnterminatedString))]
+    #[regex(r##"br#*""##, lex_raw_string)]
+    ByteStrLit(Vec<u8>),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Comment => f.write_str("comment"),
+            Self::OpenComment => f.write_str("`/*`"),
+            Self::CloseComment => f.write_str("`*/`"),
+            Self::KwFn => f.write_str("`fn`"),
+            Self::KwReturn => f.write_str("`return`"),
+            Self::KwLet => f.write_str("`let`"),
+            Self::KwMut => f.write_str("`mut`"),
+            Self::KwTrue => f.write_str("`true`"),
+            Self::KwFalse => f.write_str("`false`"),
+            Self::KwIf => f.write_str("`if`"),
+            Self::KwElse => f.write_str("`else`"),
+            Self::KwWhile => f.write_str("`while`"),
+            Self::KwLoop => f.write_str("`loop`"),
+            Self::KwBreak => f.write_str("`break`"),
+            Self::KwContinue => f.write_str("`continue`"),
+            Self::KwAs => f.write_str("`as`"),
+            Self::KwStruct => f.write_str("`struct`"),
+            Self::KwEnum => f.write_str("`enum`"),
+            Self::KwMatch => f.write_str("`match`"),
+            Self::KwUnsafe => f.write_str("`unsafe`"),
+            Self::KwConst => f.write_str("`const`"),
+            Self::Semicolon => f.write_str("`;`"),
+            Self::LeftParen => f.write_str("`(`"),
+            Self::RightParen => f.write_str("`)`"),
+            Self::LeftBrace => f.write_str("`{`"),
+            Self::RightBrace => f.write_str("`}`"),
+            Self::LeftBracket => f.write_str("`[`"),
+            Self::RightBracket => f.write_str("`]`"),
+            Self::Arrow => f.write_str("`->`"),
+            Self::FatArrow => f.write_str("`=>`"),
+            Self::Comma => f.write_str("`,`"),
+            Self::Colon => f.write_str("`:`"),
+            Self::PathSep => f.write_str("`::`"),
+            Self::Dot => f.write_str("`.`"),
+            Self::Assign => f.write_str("`=`"),
+            Self::PlusAssign => f.write_str("`+=`"),
+            Self::MinusAssign => f.write_str("`-=`"),
+            Self::StarAssign => f.write_str("`*=`"),
+            Self::SlashAssign => f.write_str("`/=`"),
+            Self::PercentAssign => f.write_str("`%=`"),
+            Self::AmpAssign => f.write_str("`&=`"),
+            Self::PipeAssign => f.write_str("`|=`"),
+            Self::CaretAssign => f.write_str("`^=`"),
+            Self::ShlAssign => f.write_str("`<<=`"),
+            Self::ShrAssign => f.write_str("`>>=`"),
+            Self::Plus => f.write_str("`+`"),
+            Self::Minus => f.write_str("`-`"),
+            Self::Star => f.write_str("`*`"),
+            Self::Slash => f.write_str("`/`"),
+            Self::Percent => f.write_str("`%`"),
+            Self::Bang => f.write_str("`!`"),
+            Self::Amp => f.write_str("`&`"),
+            Self::Pipe => f.write_str("`|`"),
+            Self::Caret => f.write_str("`^`"),
+            Self::Shl => f.write_str("`<<`"),
+            Self::Shr => f.write_str("`>>`"),
+            Self::AndAnd => f.write_str("`&&`"),
+            Self::OrOr => f.write_str("`||`"),
+            Self::EqEq => f.write_str("`==`"),
+            Self::NotEq => f.write_str("`!=`"),
+            Self::Lt => f.write_str("`<`"),
+            Self::Le => f.write_str("`<=`"),
+            Self::Gt => f.write_str("`>`"),
+            Self::Ge => f.write_str("`>=`"),
+            Self::Ident(ident) => write!(f, "identifier `{ident}`"),
+            Self::Label(label) => write!(f, "label `'{label}`"),
+            Self::NumberLit(num) => write!(f, "number literal `{num}`"),
+            Self::StrLit(_) => f.write_str("string literal"),
+            Self::ByteStrLit(_) => f.write_str("byte string literal"),
+        }
+    }
+}
+
+fn parse_num_literal(lex: &mut Lexer) -> Result<NumberLiteral, LexerError> {
+    let slice = lex.slice();
+    let (radix, rest) = match slice.get(..2) {
+        Some("0x") => (16, &slice[2..]),
+        Some("0o") => (8, &slice[2..]),
+        Some("0b") => (2, &slice[2..]),
+        _ => (10, slice),
+    };
+    // Invalid digits for binary and octal are part of the digits, so they are reported as invalid
+    // digits instead of an invalid suffix
+    let is_digit =
+        |c: char| c == '_' || (c.is_ascii_hexdigit() && (radix == 16 || c.is_ascii_digit()));
+    let skip_digits = |start: usize| {
+        rest[start..]
+            .find(|c| !is_digit(c))
+            .map_or(rest.len(), |len| start + len)
+    };
+    let mut end = skip_digits(0);
+    let mut float = false;
+    // Only decimal literals can be floats, the regex only allows a fraction and an exponent if
+    // they are followed by a digit
+    if radix == 10 {
+        if rest[end..].starts_with('.') {
+            float = true;
+            end = skip_digits(end + 1);
+        }
+        if let Some(exponent) = rest[end..].strip_prefix(['e', 'E']) {
+            let sign = exponent.starts_with(['+', '-']) as usize;
+            if exponent[sign..].starts_with(|c: char| c.is_ascii_digit()) {
+                float = true;
+                end = skip_digits(end + 1 + sign);
+            }
+        }
+    }
+    let (digits, suffix) = rest.split_at(end);
+    let digits = digits.replace('_', "");
+
+    let invalid_suffix = || LexerError::InvalidSuffix(suffix.to_string());
+    let ty = match suffix {
+        "" => None,
+        _ => Some(NumberType::from_str(suffix).map_err(|_| invalid_suffix())?),
+    };
+    let value = match ty.as_ref().map(|ty| &ty.kind) {
+        Some(NumberKind::Float) if radix != 10 => return Err(invalid_suffix()),
+        Some(NumberKind::Signed | NumberKind::Unsigned) if float => return Err(invalid_suffix()),
+        Some(NumberKind::Float) => {
+            NumberValue::Float(digits.parse().map_err(LexerError::ParseFloatError)?)
+        }
+        None if float => NumberValue::Float(digits.parse().map_err(LexerError::ParseFloatError)?),
+        _ => NumberValue::Int(
+            u128::from_str_radix(&digits, radix).map_err(LexerError::ParseIntError)?,
+        ),
+    };
+    Ok(NumberLiteral::new(value, ty))
 }
 
-fn parse_num_literal(lex: &mut Lexer) -> Result<redox_ast::NumberLiteral, LexerError> {
-    let mut num = lex.slice().to_string();
-    let mut radix = 10;
-    if num.starts_with("0x") {
-        num = num[2..].to_string();
-        radix = 16;
+/// Replaces the escapes in a string or byte string literal with the bytes they stand for. Byte
+/// strings can only contain ascii characters, but `\x` can escape any byte
+fn lex_string(lex: &mut Lexer, bytes: bool) -> Result<Vec<u8>, LexerError> {
+    let slice = lex.slice();
+    // The regex makes sure that the literal ends with a quote, and that every backslash is
+    // followed by a character
+    let offset = slice.find('"').unwrap() + 1;
+    let contents = &slice[offset..slice.len() - 1];
+    let mut result = Vec::with_capacity(contents.len());
+    let mut chars = contents.char_indices();
+    while let Some((start, c)) = chars.next() {
+        if c != '\\' {
+            if bytes && !c.is_ascii() {
+                return Err(LexerError::NonAsciiByte(c));
+            }
+            result.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+        let invalid = |end: usize| {
+            let literal_start = lex.span().start + offset;
+            LexerError::InvalidEscape(
+                contents[start..end].to_string(),
+                literal_start + start..literal_start + end,
+            )
+        };
+        let (escape_end, escape) = chars.next().unwrap();
+        let end = escape_end + escape.len_utf8();
+        let c = match escape {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            '\\' | '"' | '\'' => escape,
+            'x' => {
+                let digits = contents
+                    .get(end..end + 2)
+                    .filter(|digits| digits.bytes().all(|c| c.is_ascii_hexdigit()))
+                    .ok_or_else(|| invalid(contents.len().min(end + 2)))?;
+                let value = u8::from_str_radix(digits, 16).unwrap();
+                // Strings are UTF-8, so only the bytes of ascii characters can be escaped
+                if !bytes && !value.is_ascii() {
+                    return Err(invalid(end + 2));
+                }
+                chars.nth(1);
+                result.push(value);
+                continue;
+            }
+            'u' if !bytes => {
+                // The code point is 1 to 6 hex digits in braces, like `\u{1F600}`
+                let close = contents[end..]
+                    .find('}')
+                    .map(|close| end + close + 1)
+                    .filter(|_| contents[end..].starts_with('{'))
+                    .ok_or_else(|| invalid(end))?;
+                let code = contents[end + 1..close - 1].replace('_', "");
+                let value = Some(&code)
+                    .filter(|code| (1..=6).contains(&code.len()))
+                    .filter(|code| code.bytes().all(|c| c.is_ascii_hexdigit()))
+                    .and_then(|code| char::from_u32(u32::from_str_radix(code, 16).unwrap()))
+                    .ok_or_else(|| invalid(close))?;
+                while chars.offset() < close {
+                    chars.next();
+                }
+                value
+            }
+            // A backslash at the end of a line skips the line break, `\n` or `\r\n`, and the
+            // indentation of the next line
+            '\n' | '\r' if escape == '\n' || contents[end..].starts_with('\n') => {
+                let rest = &contents[end..];
+                let skipped = rest.len() - rest.trim_start().len();
+                while chars.offset() < end + skipped {
+                    chars.next();
+                }
+                continue;
+            }
+            _ => return Err(invalid(end)),
+        };
+        result.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+    }
+    Ok(result)
+}
+
+/// Lexes the rest of a raw string after its opening quote, which ends at a quote followed by as
+/// many `#` as the start of the string
+fn lex_raw_string(lex: &mut Lexer) -> Result<Vec<u8>, LexerError> {
+    let hashes = lex.slice().matches('#').count();
+    let terminator = format!("\"{}", "#".repeat(hashes));
+    let Some(len) = lex.remainder().find(&terminator) else {
+        lex.bump(lex.remainder().len());
+        return Err(LexerError::UnterminatedString);
+    };
+    let contents = lex.remainder()[..len].to_string();
+    lex.bump(len + terminator.len());
+    if lex.slice().starts_with('b') {
+        if let Some(c) = contents.chars().find(|c| !c.is_ascii()) {
+            return Err(LexerError::NonAsciiByte(c));
+        }
     }
-    let num = u64::from_str_radix(&num, radix).map_err(|err| LexerError::ParseIntError(err))?;
-    Ok(redox_ast::NumberLiteral::int32(num))
+    Ok(contents.into_bytes())
+}
+
+/// The bytes of a string literal always come from valid UTF-8
+fn into_string(bytes: Vec<u8>) -> String {
+    String::from_utf8(bytes).expect("String literals are valid UTF-8")
 }
 
 #[cfg(test)]
 mod tests {
     use {
-        super::{LexerTrait, Token},
+        super::{LexerError, LexerTrait, Token},
         pretty_assertions::assert_eq,
+        redox_ast::{NumberKind, NumberLiteral, NumberType, NumberValue},
         rstest::rstest,
     };
 
@@ -91,14 +499,165 @@ mod tests {
     #[case(")", Token::RightParen)]
     #[case("{", Token::LeftBrace)]
     #[case("}", Token::RightBrace)]
+    #[case("[", Token::LeftBracket)]
+    #[case("]", Token::RightBracket)]
     #[case("foo", Token::Ident("foo".to_string()))]
     #[case("->", Token::Arrow)]
+    #[case("let", Token::KwLet)]
+    #[case("mut", Token::KwMut)]
+    #[case("true", Token::KwTrue)]
+    #[case("false", Token::KwFalse)]
+    #[case("trueish", Token::Ident("trueish".to_string()))]
+    #[case("if", Token::KwIf)]
+    #[case("else", Token::KwElse)]
+    #[case("while", Token::KwWhile)]
+    #[case("loop", Token::KwLoop)]
+    #[case("break", Token::KwBreak)]
+    #[case("continue", Token::KwContinue)]
+    #[case("as", Token::KwAs)]
+    #[case("struct", Token::KwStruct)]
+    #[case(".", Token::Dot)]
+    #[case("enum", Token::KwEnum)]
+    #[case("match", Token::KwMatch)]
+    #[case("unsafe", Token::KwUnsafe)]
+    #[case("const", Token::KwConst)]
+    #[case("constant", Token::Ident("constant".to_string()))]
+    #[case("=>", Token::FatArrow)]
+    #[case("::", Token::PathSep)]
+    #[case("'outer", Token::Label("outer".to_string()))]
+    #[case("=", Token::Assign)]
+    #[case("letter", Token::Ident("letter".to_string()))]
+    #[case("+=", Token::PlusAssign)]
+    #[case("%=", Token::PercentAssign)]
+    #[case("<<=", Token::ShlAssign)]
+    #[case("<<", Token::Shl)]
+    #[case("<=", Token::Le)]
+    #[case("&&", Token::AndAnd)]
+    #[case("!=", Token::NotEq)]
     fn test_lexing_tok(#[case] input: &str, #[case] expected: Token) {
         let mut lexer = Token::lexer(input);
         let tok = lexer.next();
         assert_eq!(tok, Some(Ok(expected)));
     }
 
+    #[rstest]
+    #[case("42", NumberLiteral::int(42))]
+    #[case("1_000_000", NumberLiteral::int(1_000_000))]
+    #[case("0x1F", NumberLiteral::int(0x1f))]
+    #[case("0xff_ff", NumberLiteral::int(0xffff))]
+    #[case("0o17", NumberLiteral::int(0o17))]
+    #[case("0b1010_1010", NumberLiteral::int(0b1010_1010))]
+    #[case("0x1f32", NumberLiteral::int(0x1f32))]
+    #[case(
+        "42u8",
+        NumberLiteral::new(NumberValue::Int(42), Some(NumberType::new(NumberKind::Unsigned, 8)))
+    )]
+    #[case(
+        "0xffi64",
+        NumberLiteral::new(NumberValue::Int(0xff), Some(NumberType::I64))
+    )]
+    #[case(
+        "1usize",
+        NumberLiteral::new(NumberValue::Int(1), Some(NumberType::USIZE))
+    )]
+    #[case(
+        "340282366920938463463374607431768211455u128",
+        NumberLiteral::new(
+            NumberValue::Int(u128::MAX),
+            Some(NumberType::new(NumberKind::Unsigned, 128))
+        )
+    )]
+    #[case("1.5", NumberLiteral::float(1.5))]
+    #[case(
+        "1.5f32",
+        NumberLiteral::new(NumberValue::Float(1.5), Some(NumberType::F32))
+    )]
+    #[case(
+        "7f64",
+        NumberLiteral::new(NumberValue::Float(7.0), Some(NumberType::F64))
+    )]
+    #[case("1e10", NumberLiteral::float(1e10))]
+    #[case("2.5E-3", NumberLiteral::float(2.5e-3))]
+    #[case("1_0.0_1e+1_0", NumberLiteral::float(10.01e10))]
+    fn test_lexing_number(#[case] input: &str, #[case] expected: NumberLiteral) {
+        let mut lexer = Token::lexer(input);
+        assert_eq!(lexer.next(), Some(Ok(Token::NumberLit(expected))));
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[rstest]
+    #[case("1.5i32", "i32")]
+    #[case("1x", "x")]
+    #[case("0b1f32", "f32")]
+    #[case("1e", "e")]
+    #[case("2u7", "u7")]
+    fn test_lexing_invalid_suffix(#[case] input: &str, #[case] suffix: &str) {
+        let mut lexer = Token::lexer(input);
+        assert_eq!(
+            lexer.next(),
+            Some(Err(LexerError::InvalidSuffix(suffix.to_string())))
+        );
+    }
+
+    #[rstest]
+    #[case("0b102")]
+    #[case("0x")]
+    #[case("340282366920938463463374607431768211456")]
+    fn test_lexing_invalid_int(#[case] input: &str) {
+        let mut lexer = Token::lexer(input);
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(LexerError::ParseIntError(_)))
+        ));
+    }
+
+    #[rstest]
+    #[case(r#""hello""#, "hello")]
+    #[case(r#""""#, "")]
+    #[case(r#""a\tb\nc\r\0""#, "a\tb\nc\r\0")]
+    #[case(r#""\"quoted\" \\ \'""#, "\"quoted\" \\ '")]
+    #[case(r#""\x41\x7f""#, "A\x7f")]
+    #[case(r#""\u{48}\u{1F600}\u{10_FFFF}""#, "H\u{1F600}\u{10FFFF}")]
+    #[case("\"héllo\nworld\"", "héllo\nworld")]
+    #[case("\"one \\\n    two\"", "one two")]
+    #[case("\"one \\\r\n    two\"", "one two")]
+    #[case("\"a\\\r\nb\"", "ab")]
+    #[case(r#"r"C:\path\n""#, "C:\\path\\n")]
+    #[case(r###"r##"a "# b"##"###, "a \"# b")]
+    fn test_lexing_string(#[case] input: &str, #[case] expected: &str) {
+        let mut lexer = Token::lexer(input);
+        assert_eq!(lexer.next(), Some(Ok(Token::StrLit(expected.to_string()))));
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[rstest]
+    #[case(r#"b"abc""#, b"abc".to_vec())]
+    #[case(r#"b"\xff\x00\n""#, vec![0xff, 0, b'\n'])]
+    #[case(r#"br"\x41""#, b"\\x41".to_vec())]
+    fn test_lexing_byte_string(#[case] input: &str, #[case] expected: Vec<u8>) {
+        let mut lexer = Token::lexer(input);
+        assert_eq!(lexer.next(), Some(Ok(Token::ByteStrLit(expected))));
+        assert_eq!(lexer.next(), None);
+    }
+
+    #[rstest]
+    #[case(r#""abc"#, LexerError::UnterminatedString)]
+    #[case(r##"r#"abc""##, LexerError::UnterminatedString)]
+    #[case(r#""a\qb""#, LexerError::InvalidEscape("\\q".to_string(), 2..4))]
+    #[case(r#""ab\qc""#, LexerError::InvalidEscape("\\q".to_string(), 3..5))]
+    #[case(r#""\x80""#, LexerError::InvalidEscape("\\x80".to_string(), 1..5))]
+    #[case(r#""\x4""#, LexerError::InvalidEscape("\\x4".to_string(), 1..4))]
+    #[case(r#""\u{110000}""#, LexerError::InvalidEscape("\\u{110000}".to_string(), 1..11))]
+    #[case(r#""\u{}""#, LexerError::InvalidEscape("\\u{}".to_string(), 1..5))]
+    #[case(r#""\u41""#, LexerError::InvalidEscape("\\u".to_string(), 1..3))]
+    #[case(r#"b"\u{41}""#, LexerError::InvalidEscape("\\u".to_string(), 2..4))]
+    #[case("\"a\\\rb\"", LexerError::InvalidEscape("\\\r".to_string(), 2..4))]
+    #[case(r#"b"é""#, LexerError::NonAsciiByte('é'))]
+    fn test_lexing_invalid_string(#[case] input: &str, #[case] expected: LexerError) {
+        let mut lexer = Token::lexer(input);
+        assert_eq!(lexer.next(), Some(Err(expected)));
+    }
+
     #[rstest]
     #[case("fn main() {}", vec![
         Token::KwFn, 
@@ -108,6 +667,36 @@ mod tests {
         Token::LeftBrace, 
         Token::RightBrace
     ])]
+    #[case("x -= 1; /* x *= 2 */", vec![
+        Token::Ident("x".to_string()),
+        Token::MinusAssign,
+        Token::NumberLit(redox_ast::NumberLiteral::int(1)),
+        Token::Semicolon,
+        Token::OpenComment,
+        Token::Ident("x".to_string()),
+        Token::StarAssign,
+        Token::NumberLit(redox_ast::NumberLiteral::int(2)),
+        Token::CloseComment,
+    ])]
+    #[case("-a*b>=c||!d", vec![
+        Token::Minus,
+        Token::Ident("a".to_string()),
+        Token::Star,
+        Token::Ident("b".to_string()),
+        Token::Ge,
+        Token::Ident("c".to_string()),
+        Token::OrOr,
+        Token::Bang,
+        Token::Ident("d".to_string()),
+    ])]
+    #[case(r#"f(b"a", r"b")"#, vec![
+        Token::Ident("f".to_string()),
+        Token::LeftParen,
+        Token::ByteStrLit(b"a".to_vec()),
+        Token::Comma,
+        Token::StrLit("b".to_string()),
+        Token::RightParen,
+    ])]
     fn test_lexing_seq(#[case] input: &str, #[case] expected: Vec<Token>) {
         let mut lexer = Token::lexer(input);
         let mut tokens = Vec::new();
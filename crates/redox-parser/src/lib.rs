@@ -1,5 +1,8 @@
 use redox_ast::{
-    Attributes, Block, Expr, ExprKind, FunctionDef, Literal, TopLevel, TopLevelKind, Type,
+    Argument, Attributes, BinaryOp, Block, Diagnostic, EnumDef, Expr, ExprKind, FieldInit,
+    FieldPattern, FunctionDef, LetBinding, Literal, MatchArm, NumberLiteral, NumberType,
+    NumberValue, Pattern, PatternKind, Spanned, StructDef, StructField, ToDiagnostic, TopLevel,
+    TopLevelKind, Type, UnaryOp, Variant, VariantKind, VariantPath,
 };
 use redox_lexer::{Lexer, LexerError, LexerTrait, Span, Token};
 use std::str::FromStr;
@@ -10,29 +13,112 @@ pub struct Parser<'ctx> {
 
     // State
     current_tok: Option<(Token, Span)>,
+    /// The end of the last token that was consumed, used to close the span of a node
+    prev_end: usize,
+    /// Errors that were recovered from, the parser continues after these
+    errors: Vec<ParseError>,
+    /// Whether `Name {` starts a struct literal. It doesn't in the condition of an `if` or
+    /// `while` or the scrutinee of a `match`, where the brace starts the body
+    struct_literals: bool,
+}
+
+/// The result of parsing a source file. If there are errors, the AST only contains what could be
+/// parsed, with the erroneous statements or items left out.
+#[derive(Debug, Clone)]
+pub struct ParseOutput {
+    pub ast: Vec<TopLevel>,
+    pub errors: Vec<ParseError>,
+}
+
+impl ParseOutput {
+    pub fn into_result(self) -> Result<Vec<TopLevel>, Vec<ParseError>> {
+        if self.errors.is_empty() {
+            Ok(self.ast)
+        } else {
+            Err(self.errors)
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error, Clone)]
 pub enum ParseError {
-    LexerError(LexerError),
-    UnexpectedEOF,
-    UnexpectedToken(Token),
-    UnclosedComment,
+    LexerError(LexerError, Span),
+    UnexpectedEOF {
+        expected: Option<String>,
+        span: Span,
+    },
+    UnexpectedToken {
+        found: Token,
+        expected: Option<String>,
+        span: Span,
+    },
+    UnclosedComment(Span),
+    InvalidType(String, Span),
+    /// Valid syntax for a feature that the compiler doesn't support yet
+    Unsupported(String, Span),
 }
 
-impl From<LexerError> for ParseError {
-    fn from(err: LexerError) -> Self {
-        Self::LexerError(err)
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::LexerError(_, span)
+            | Self::UnexpectedEOF { span, .. }
+            | Self::UnexpectedToken { span, .. }
+            | Self::UnclosedComment(span)
+            | Self::InvalidType(_, span)
+            | Self::Unsupported(_, span) => span.clone(),
+        }
     }
 }
 
 impl std::fmt::Display for ParseError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::LexerError(err) => err.fmt(f),
-            Self::UnexpectedEOF => write!(f, "Unexpected EOF"),
-            Self::UnexpectedToken(tok) => write!(f, "Unexpected token: {tok:?}"),
-            Self::UnclosedComment => write!(f, "Unclosed comment"),
+            Self::LexerError(err, _) => err.fmt(f),
+            Self::UnexpectedEOF { .. } => write!(f, "Unexpected EOF"),
+            Self::UnexpectedToken { found, .. } => write!(f, "Unexpected token: {found}"),
+            Self::UnclosedComment(_) => write!(f, "Unclosed comment"),
+            Self::InvalidType(ty, _) => write!(f, "Invalid type: {ty}"),
+            Self::Unsupported(construct, _) => write!(f, "Unsupported: {construct}"),
+        }
+    }
+}
+
+impl ToDiagnostic for ParseError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            Self::LexerError(err, span) => err.to_diagnostic(span.clone()),
+            Self::UnexpectedEOF { expected, span } => {
+                let diagnostic = Diagnostic::error("unexpected end of file", span.clone());
+                match expected {
+                    Some(expected) => diagnostic.with_label(format!("expected {expected}")),
+                    None => diagnostic,
+                }
+            }
+            Self::UnexpectedToken {
+                found,
+                expected,
+                span,
+            } => {
+                let diagnostic = Diagnostic::error(format!("unexpected {found}"), span.clone());
+                match expected {
+                    Some(expected) => diagnostic.with_label(format!("expected {expected}")),
+                    None => diagnostic.with_label("unexpected token"),
+                }
+            }
+            Self::UnclosedComment(span) => {
+                Diagnostic::error("unclosed block comment", span.clone())
+                    .with_label("this comment is never closed")
+                    .with_help("add a `*/` to close the comment")
+            }
+            Self::InvalidType(ty, span) => {
+                Diagnostic::error(format!("unknown type `{ty}`"), span.clone())
+                    .with_label("not a known type")
+            }
+            Self::Unsupported(construct, span) => {
+                Diagnostic::error(format!("{construct} are not supported yet"), span.clone())
+                    .with_label("not supported")
+            }
         }
     }
 }
@@ -43,6 +129,9 @@ impl<'ctx> Parser<'ctx> {
         Self {
             lexer,
             current_tok: None,
+            prev_end: 0,
+            errors: Vec::new(),
+            struct_literals: true,
         }
     }
 
@@ -51,49 +140,94 @@ impl<'ctx> Parser<'ctx> {
         Self::new(Token::lexer(source))
     }
 
+    /// Moves to the next token, and returns it. Lexer errors are recorded and the invalid tokens
+    /// are skipped, so the rest of the file can still be parsed.
     #[instrument(skip(self))]
-    fn advance(&mut self) -> Result<Option<Token>, ParseError> {
-        // FIXME: This is very bad coding here, a lot of repeated code
+    fn advance(&mut self) -> Option<Token> {
         tracing::trace!("Advance");
-        let tok = if let Some(tok) = self.lexer.next() {
-            let mut tok = tok?;
-            if tok == Token::OpenComment {
-                tracing::trace!("Open comment");
-                let mut broken = false;
-                while let Some(tok) = self.lexer.next() {
-                    tracing::trace!(?tok, "Comment token");
-                    if tok? == Token::CloseComment {
-                        tracing::trace!("Close comment");
-                        broken = true;
-                        break;
+        if let Some((_, span)) = &self.current_tok {
+            self.prev_end = span.end;
+        }
+        self.current_tok = None;
+        while let Some(tok) = self.lexer.next() {
+            match tok {
+                Ok(Token::OpenComment) => {
+                    tracing::trace!("Open comment");
+                    if !self.skip_comment() {
+                        return None;
                     }
                 }
-
-                if !broken {
-                    return Err(ParseError::UnclosedComment);
+                Ok(tok) => {
+                    self.current_tok = Some((tok.clone(), self.lexer.span()));
+                    return Some(tok);
                 }
+                Err(err) => {
+                    tracing::trace!(?err, "Lexer error");
+                    self.errors
+                        .push(ParseError::LexerError(err, self.lexer.span()));
+                }
+            }
+        }
+        None
+    }
 
-                if let Some(new_tok) = self.lexer.next() {
-                    tok = new_tok?;
-                } else {
-                    self.current_tok = None;
-                    return Ok(None);
-                };
+    /// Skips the tokens of a block comment, assuming the opening token was just lexed.
+    /// Returns false if the comment was never closed.
+    fn skip_comment(&mut self) -> bool {
+        let comment_span = self.lexer.span();
+        for tok in self.lexer.by_ref() {
+            tracing::trace!(?tok, "Comment token");
+            // Anything is allowed in a comment, so lexer errors are ignored
+            if tok == Ok(Token::CloseComment) {
+                tracing::trace!("Close comment");
+                return true;
             }
-            tok
-        } else {
-            self.current_tok = None;
-            return Ok(None);
-        };
+        }
+        self.errors.push(ParseError::UnclosedComment(comment_span));
+        false
+    }
+
+    /// Skips tokens until the end of the current statement, which is a `;` (which is consumed),
+    /// or a `}` or the start of an item that isn't nested in braces (which are not consumed)
+    fn synchronize_statement(&mut self) {
+        tracing::trace!("Synchronizing to the next statement");
+        let mut depth = 0usize;
+        while let Some((tok, _)) = &self.current_tok {
+            match tok {
+                Token::LeftBrace => depth += 1,
+                Token::RightBrace if depth == 0 => return,
+                Token::RightBrace => depth -= 1,
+                Token::KwFn | Token::KwStruct | Token::KwEnum if depth == 0 => return,
+                Token::Semicolon if depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                _ => (),
+            }
+            self.advance();
+        }
+    }
 
-        self.current_tok = Some((tok.clone(), self.lexer.span()));
-        Ok(Some(tok))
+    /// Skips tokens until the next `fn`, `struct` or `enum` that isn't nested in braces
+    fn synchronize_item(&mut self) {
+        tracing::trace!("Synchronizing to the next item");
+        let mut depth = 0usize;
+        while let Some((tok, _)) = &self.current_tok {
+            match tok {
+                Token::LeftBrace => depth += 1,
+                Token::RightBrace => depth = depth.saturating_sub(1),
+                Token::KwFn | Token::KwStruct | Token::KwEnum if depth == 0 => return,
+                _ => (),
+            }
+            self.advance();
+        }
     }
 
     #[instrument(skip(self))]
     fn advance_no_eof(&mut self) -> Result<Token, ParseError> {
         tracing::trace!("Advance ensuring no EOF");
-        self.advance()?.ok_or(ParseError::UnexpectedEOF)
+        self.advance();
+        self.current()
     }
 
     #[instrument(skip(self))]
@@ -101,66 +235,116 @@ impl<'ctx> Parser<'ctx> {
         tracing::trace!("Current token");
         self.current_tok
             .as_ref()
-            .ok_or(ParseError::UnexpectedEOF)
+            .ok_or_else(|| self.unexpected(None))
             .map(|t| t.0.clone())
     }
 
+    /// The span of the current token, or an empty span at the end of the source if there are no
+    /// more tokens
+    fn span(&self) -> Span {
+        self.current_tok
+            .as_ref()
+            .map(|t| t.1.clone())
+            .unwrap_or(self.prev_end..self.prev_end)
+    }
+
+    /// Creates an error for the current token, which can be the end of the file
+    fn unexpected(&self, expected: Option<&str>) -> ParseError {
+        let expected = expected.map(|s| s.to_string());
+        match &self.current_tok {
+            Some((found, span)) => ParseError::UnexpectedToken {
+                found: found.clone(),
+                expected,
+                span: span.clone(),
+            },
+            None => ParseError::UnexpectedEOF {
+                expected,
+                span: self.span(),
+            },
+        }
+    }
+
     #[instrument(skip(self))]
     fn expect_advance(&mut self, expected: Token) -> Result<Token, ParseError> {
         tracing::trace!(?expected, "Expecting advance");
-        let tok = self.advance()?.ok_or(ParseError::UnexpectedEOF)?;
-        if tok != expected {
-            return Err(ParseError::UnexpectedToken(tok));
-        }
-        Ok(tok)
+        self.advance();
+        self.expect(expected.clone())?;
+        Ok(expected)
     }
 
     #[instrument(skip(self))]
     fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
         tracing::trace!(?expected, "Expecting token");
-        let tok = self.current()?;
-        if tok != expected {
-            return Err(ParseError::UnexpectedToken(tok));
+        if self.current_tok.as_ref().map(|t| &t.0) != Some(&expected) {
+            return Err(self.unexpected(Some(&expected.to_string())));
         }
         Ok(())
     }
 
+    /// Parses the whole source, recovering from syntax errors at statement and item boundaries so
+    /// that every error in the file is reported
     #[instrument(skip(self))]
-    pub fn parse(&mut self) -> Result<Vec<TopLevel>, ParseError> {
+    pub fn parse(&mut self) -> ParseOutput {
         tracing::trace!("Started parsing");
         let mut top_levels = Vec::new();
 
-        while let Some(tok) = self.advance()? {
-            match tok {
-                Token::KwFn => top_levels.push(TopLevel::expr(self.parse_function_def()?)),
-                _ => todo!(),
+        self.advance();
+        while let Some((tok, _)) = self.current_tok.clone() {
+            let result = match tok {
+                Token::KwFn => self.parse_function_def().map(TopLevel::expr),
+                Token::KwStruct => self.parse_struct_def(),
+                Token::KwEnum => self.parse_enum_def(),
+                _ => Err(self.unexpected(Some("an item"))),
+            };
+            match result {
+                Ok(top_level) => top_levels.push(top_level),
+                Err(err) => {
+                    tracing::trace!(?err, "Recovering from error");
+                    self.errors.push(err);
+                    // The current token could be the keyword of the item that failed
+                    if let Some(Token::KwFn | Token::KwStruct | Token::KwEnum) =
+                        self.current_tok.as_ref().map(|t| &t.0)
+                    {
+                        self.advance();
+                    }
+                    self.synchronize_item();
+                }
             }
         }
-        Ok(top_levels)
+        ParseOutput {
+            ast: top_levels,
+            errors: std::mem::take(&mut self.errors),
+        }
     }
 
+    /// Parses a function definition, assuming the current token is the `fn` keyword
     #[instrument(skip(self))]
     fn parse_function_def(&mut self) -> Result<Expr, ParseError> {
         tracing::trace!("Parsing function definition");
+        let start = self.span().start;
         let attributes: Attributes = Vec::new();
         let name = match self.advance_no_eof()? {
             Token::Ident(ident) => ident,
-            _ => todo!(),
+            _ => return Err(self.unexpected(Some("a function name"))),
         };
 
         self.expect_advance(Token::LeftParen)?;
+        self.advance_no_eof()?;
         let arguments = self.parse_typed_argument_list()?;
         tracing::trace!(?arguments, "Parsed arguments");
         self.expect(Token::RightParen)?;
 
         let return_ty = if let Token::Arrow = self.advance_no_eof()? {
             self.advance_no_eof()?;
-            Some(self.parse_type()?)
+            let ty_start = self.span().start;
+            let ty = self.parse_type()?;
+            Some(Spanned::new(ty, ty_start..self.prev_end))
         } else {
             None
         };
+        let signature_span = start..self.prev_end;
         self.expect(Token::LeftBrace)?;
-        let body = self.parse_block()?;
+        let body = self.parse_block();
         // Parse block already consumes the right brace, and we dont' need to check for it here
 
         Ok(Expr::new(
@@ -170,119 +354,1209 @@ impl<'ctx> Parser<'ctx> {
                 return_ty,
                 attributes,
                 body,
+                span: signature_span,
+            }),
+            start..self.prev_end,
+        ))
+    }
+
+    /// Parses a struct declaration, assuming the current token is the `struct` keyword
+    #[instrument(skip(self))]
+    fn parse_struct_def(&mut self) -> Result<TopLevel, ParseError> {
+        tracing::trace!("Parsing struct declaration");
+        let start = self.span().start;
+        let name = match self.advance_no_eof()? {
+            Token::Ident(ident) => ident,
+            _ => return Err(self.unexpected(Some("a struct name"))),
+        };
+        let span = self.span();
+        self.expect_advance(Token::LeftBrace)?;
+        let fields = self.parse_struct_fields()?;
+        Ok(TopLevel::new(
+            TopLevelKind::Struct(StructDef { name, fields, span }),
+            start..self.prev_end,
+        ))
+    }
+
+    /// Parses the fields of a struct or struct variant declaration, assuming the current token is
+    /// the left brace
+    fn parse_struct_fields(&mut self) -> Result<Vec<StructField>, ParseError> {
+        self.advance_no_eof()?;
+        let mut fields = Vec::new();
+        while self.current()? != Token::RightBrace {
+            let field_start = self.span().start;
+            let Token::Ident(field) = self.current()? else {
+                return Err(self.unexpected(Some("a field name")));
+            };
+            self.expect_advance(Token::Colon)?;
+            self.advance_no_eof()?;
+            let ty_start = self.span().start;
+            let ty = self.parse_type()?;
+            tracing::trace!(?field, ?ty, "Parsed field");
+            fields.push(StructField {
+                name: field,
+                ty: Spanned::new(ty, ty_start..self.prev_end),
+                span: field_start..self.prev_end,
+            });
+            match self.current()? {
+                Token::Comma => {
+                    self.advance();
+                }
+                Token::RightBrace => break,
+                _ => return Err(self.unexpected(Some("`,` or `}`"))),
+            }
+        }
+        // Consume the right brace
+        self.advance();
+        Ok(fields)
+    }
+
+    /// Parses an enum declaration, assuming the current token is the `enum` keyword
+    #[instrument(skip(self))]
+    fn parse_enum_def(&mut self) -> Result<TopLevel, ParseError> {
+        tracing::trace!("Parsing enum declaration");
+        let start = self.span().start;
+        let name = match self.advance_no_eof()? {
+            Token::Ident(ident) => ident,
+            _ => return Err(self.unexpected(Some("an enum name"))),
+        };
+        let span = self.span();
+        self.expect_advance(Token::LeftBrace)?;
+        self.advance_no_eof()?;
+        let mut variants = Vec::new();
+        while self.current()? != Token::RightBrace {
+            let variant_start = self.span().start;
+            let Token::Ident(variant) = self.current()? else {
+                return Err(self.unexpected(Some("a variant name")));
+            };
+            let (kind, fields) = match self.advance_no_eof()? {
+                Token::LeftParen => {
+                    let parse_field = |parser: &mut Self| {
+                        let ty_start = parser.span().start;
+                        let ty = parser.parse_type()?;
+                        Ok(Spanned::new(ty, ty_start..parser.prev_end))
+                    };
+                    let (types, _) = self.parse_parenthesized(parse_field)?;
+                    let fields = types
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, ty)| StructField {
+                            name: index.to_string(),
+                            span: ty.span.clone(),
+                            ty,
+                        })
+                        .collect();
+                    (VariantKind::Tuple, fields)
+                }
+                Token::LeftBrace => (VariantKind::Struct, self.parse_struct_fields()?),
+                _ => (VariantKind::Unit, Vec::new()),
+            };
+            tracing::trace!(?variant, ?kind, "Parsed variant");
+            variants.push(Variant {
+                name: variant,
+                kind,
+                fields,
+                span: variant_start..self.prev_end,
+            });
+            match self.current()? {
+                Token::Comma => {
+                    self.advance();
+                }
+                Token::RightBrace => break,
+                _ => return Err(self.unexpected(Some("`,` or `}`"))),
+            }
+        }
+        // Consume the right brace
+        self.advance();
+        Ok(TopLevel::new(
+            TopLevelKind::Enum(EnumDef {
+                name,
+                variants,
+                span,
             }),
-            std::ops::Range::default(),
+            start..self.prev_end,
         ))
     }
 
-    /// Parses a block, assuming the current token is the left brace
+    /// Parses a block, assuming the current token is the left brace.
+    /// Errors in statements are recorded, and the block continues at the next statement.
     #[instrument(skip(self))]
-    fn parse_block(&mut self) -> Result<Block, ParseError> {
+    fn parse_block(&mut self) -> Block {
         tracing::trace!("Parsing block");
+        let start = self.span().start;
         let mut statements = Vec::new();
+        let mut tail = None;
 
-        while let Some(tok) = self.advance()? {
-            match tok {
-                Token::RightBrace => break,
-                Token::LeftBrace => unimplemented!("Nested blocks are not yet supported"),
-                _ => {
-                    let statement = self.parse_statement()?;
-                    statements.push(statement);
+        self.advance();
+        loop {
+            match self.current_tok.as_ref().map(|t| &t.0) {
+                Some(Token::RightBrace) => {
+                    self.advance();
+                    break;
+                }
+                // Items can't be nested, so the block is most likely missing its closing brace
+                Some(Token::KwFn | Token::KwStruct | Token::KwEnum) | None => {
+                    self.errors.push(self.unexpected(Some("`}`")));
+                    break;
                 }
+                Some(_) => match self.parse_statement() {
+                    Ok((expr, true)) => tail = Some(Box::new(expr)),
+                    Ok((statement, false)) => statements.push(statement),
+                    Err(err) => {
+                        tracing::trace!(?err, "Recovering from error");
+                        self.errors.push(err);
+                        self.synchronize_statement();
+                    }
+                },
             }
         }
 
-        Ok(Block {
+        Block {
             statements,
+            tail,
             attributes: Vec::new(),
-        })
+            span: start..self.prev_end,
+        }
     }
 
+    /// Parses a statement, and returns whether it is the value of the block, which is the case for
+    /// an expression that is directly followed by the closing brace
     #[instrument(skip(self))]
-    fn parse_statement(&mut self) -> Result<Expr, ParseError> {
+    fn parse_statement(&mut self) -> Result<(Expr, bool), ParseError> {
         tracing::trace!("Parsing statement");
-        // TODO: We need to respect semiclons
         let res = match self.current()? {
-            Token::KwReturn => {
-                self.advance()?;
+            Token::KwLet => self.parse_let()?,
+            // A statement that starts with a block ends after it, so `if a {} *p = 1;` is two
+            // statements
+            Token::KwIf
+            | Token::KwWhile
+            | Token::KwLoop
+            | Token::KwMatch
+            | Token::KwUnsafe
+            | Token::LeftBrace
+            | Token::Label(_) => {
+                let expr = self.parse_primary()?;
+                match self.current_tok.as_ref().map(|t| &t.0) {
+                    Some(Token::RightBrace) => return Ok((expr, true)),
+                    Some(Token::Semicolon) => expr,
+                    _ => return Ok((expr, false)),
+                }
+            }
+            _ => {
                 let expr = self.parse_expr()?;
-                Expr::new(
-                    ExprKind::Return(Some(Box::new(expr))),
-                    std::ops::Range::default(),
-                )
+                match self.current_tok.as_ref().map(|t| &t.0) {
+                    Some(Token::RightBrace) => return Ok((expr, true)),
+                    // Expressions ending with a block don't need a semicolon
+                    Some(tok) if *tok != Token::Semicolon && expr.kind.ends_with_block() => {
+                        return Ok((expr, false));
+                    }
+                    _ => expr,
+                }
             }
-            _ => self.parse_expr()?,
         };
 
         self.expect(Token::Semicolon)?;
         // Consume the semicolon
-        self.advance()?;
-        Ok(res)
+        self.advance();
+        Ok((res, false))
+    }
+
+    /// Parses a return expression, assuming the current token is the `return` keyword
+    #[instrument(skip(self))]
+    fn parse_return(&mut self) -> Result<Expr, ParseError> {
+        tracing::trace!("Parsing return");
+        let start = self.span().start;
+        let value = match self.advance_no_eof()? {
+            Token::Semicolon | Token::RightBrace => None,
+            _ => Some(Box::new(self.parse_expr()?)),
+        };
+        Ok(Expr::new(ExprKind::Return(value), start..self.prev_end))
+    }
+
+    /// Parses a `while` loop, assuming the current token is the `while` keyword. A labeled loop
+    /// passes the label and the start of it
+    #[instrument(skip(self))]
+    fn parse_while(&mut self, label: Option<(String, usize)>) -> Result<Expr, ParseError> {
+        tracing::trace!("Parsing while loop");
+        let start = label
+            .as_ref()
+            .map_or(self.span().start, |(_, start)| *start);
+        self.advance();
+        let condition = self.with_struct_literals(false, Self::parse_expr)?;
+        self.expect(Token::LeftBrace)?;
+        let body = self.parse_block();
+        Ok(Expr::new(
+            ExprKind::While {
+                label: label.map(|(label, _)| label),
+                condition: Box::new(condition),
+                body,
+            },
+            start..self.prev_end,
+        ))
+    }
+
+    /// Parses a `loop`, assuming the current token is the `loop` keyword. A labeled loop passes
+    /// the label and the start of it
+    #[instrument(skip(self))]
+    fn parse_loop(&mut self, label: Option<(String, usize)>) -> Result<Expr, ParseError> {
+        tracing::trace!("Parsing loop");
+        let start = label
+            .as_ref()
+            .map_or(self.span().start, |(_, start)| *start);
+        self.expect_advance(Token::LeftBrace)?;
+        let body = self.parse_block();
+        Ok(Expr::new(
+            ExprKind::Loop {
+                label: label.map(|(label, _)| label),
+                body,
+            },
+            start..self.prev_end,
+        ))
+    }
+
+    /// Runs `parse` with struct literals allowed or not, and restores the previous setting after
+    fn with_struct_literals<T>(&mut self, allowed: bool, parse: impl FnOnce(&mut Self) -> T) -> T {
+        let previous = std::mem::replace(&mut self.struct_literals, allowed);
+        let result = parse(self);
+        self.struct_literals = previous;
+        result
+    }
+
+    /// Parses the label after `break` or `continue`, if there is one
+    fn parse_label(&mut self) -> Option<String> {
+        match self.current_tok.as_ref().map(|t| &t.0) {
+            Some(Token::Label(label)) => {
+                let label = label.clone();
+                self.advance();
+                Some(label)
+            }
+            _ => None,
+        }
+    }
+
+    /// Parses an `if` expression, assuming the current token is the `if` keyword
+    #[instrument(skip(self))]
+    fn parse_if(&mut self) -> Result<Expr, ParseError> {
+        tracing::trace!("Parsing if");
+        let start = self.span().start;
+        self.advance();
+        let condition = self.with_struct_literals(false, Self::parse_expr)?;
+        self.expect(Token::LeftBrace)?;
+        let then_block = self.parse_block();
+        let else_block = match self.current_tok.as_ref().map(|t| &t.0) {
+            Some(Token::KwElse) => match self.advance_no_eof()? {
+                Token::KwIf => {
+                    let nested = self.parse_if()?;
+                    let mut block = Block::empty(nested.span.clone());
+                    block.tail = Some(Box::new(nested));
+                    Some(block)
+                }
+                _ => {
+                    self.expect(Token::LeftBrace)?;
+                    Some(self.parse_block())
+                }
+            },
+            _ => None,
+        };
+        Ok(Expr::new(
+            ExprKind::If {
+                condition: Box::new(condition),
+                then_block,
+                else_block,
+            },
+            start..self.prev_end,
+        ))
+    }
+
+    /// Parses a let binding, assuming the current token is the `let` keyword.
+    /// The semicolon is not consumed.
+    #[instrument(skip(self))]
+    fn parse_let(&mut self) -> Result<Expr, ParseError> {
+        tracing::trace!("Parsing let binding");
+        let start = self.span().start;
+        self.advance_no_eof()?;
+        let pattern = self.parse_pattern()?;
+
+        let ty = if self.current()? == Token::Colon {
+            self.advance();
+            let ty_start = self.span().start;
+            let ty = self.parse_type()?;
+            Some(Spanned::new(ty, ty_start..self.prev_end))
+        } else {
+            None
+        };
+
+        self.expect(Token::Assign)?;
+        self.advance();
+        let value = self.parse_expr()?;
+
+        Ok(Expr::new(
+            ExprKind::Let(LetBinding {
+                pattern,
+                ty,
+                value: Box::new(value),
+            }),
+            start..self.prev_end,
+        ))
+    }
+
+    /// Parses a comma separated list in parentheses, assuming the current token is the left
+    /// parenthesis. Also returns whether the list is a tuple, which is the case unless it is a
+    /// single element without a trailing comma, like `(a)`
+    fn parse_parenthesized<T>(
+        &mut self,
+        parse: impl Fn(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<(Vec<T>, bool), ParseError> {
+        self.advance_no_eof()?;
+        let mut elements = Vec::new();
+        let mut trailing_comma = false;
+        while self.current()? != Token::RightParen {
+            elements.push(parse(self)?);
+            match self.current()? {
+                Token::Comma => {
+                    trailing_comma = true;
+                    self.advance();
+                }
+                Token::RightParen => {
+                    trailing_comma = false;
+                    break;
+                }
+                _ => return Err(self.unexpected(Some("`,` or `)`"))),
+            }
+        }
+        // Consume the right parenthesis
+        self.advance();
+        let tuple = elements.len() != 1 || trailing_comma;
+        Ok((elements, tuple))
+    }
+
+    /// Parses a pattern, assuming the current token is the first token of it
+    #[instrument(skip(self))]
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        tracing::trace!("Parsing pattern");
+        let span = self.span();
+        let kind = match self.current()? {
+            Token::KwMut => {
+                let Token::Ident(name) = self.advance_no_eof()? else {
+                    return Err(self.unexpected(Some("a variable name")));
+                };
+                self.advance();
+                PatternKind::Binding {
+                    name,
+                    mutable: true,
+                }
+            }
+            Token::Ident(name) => {
+                if self.advance() == Some(Token::PathSep) {
+                    return self.parse_variant_pattern(Spanned::new(name, span));
+                }
+                if name == "_" {
+                    PatternKind::Wildcard
+                } else {
+                    PatternKind::Binding {
+                        name,
+                        mutable: false,
+                    }
+                }
+            }
+            Token::NumberLit(number) => {
+                self.advance();
+                PatternKind::Literal {
+                    value: Literal::Number(number),
+                    negated: false,
+                }
+            }
+            Token::Minus => {
+                let Token::NumberLit(number) = self.advance_no_eof()? else {
+                    return Err(self.unexpected(Some("a number literal")));
+                };
+                self.advance();
+                PatternKind::Literal {
+                    value: Literal::Number(number),
+                    negated: true,
+                }
+            }
+            Token::KwTrue | Token::KwFalse => {
+                let value = matches!(self.current()?, Token::KwTrue);
+                self.advance();
+                PatternKind::Literal {
+                    value: Literal::Bool(value),
+                    negated: false,
+                }
+            }
+            Token::LeftParen => {
+                let (mut elements, tuple) = self.parse_parenthesized(Self::parse_pattern)?;
+                if !tuple {
+                    let mut pattern = elements.pop().unwrap();
+                    pattern.span = span.start..self.prev_end;
+                    return Ok(pattern);
+                }
+                PatternKind::Tuple(elements)
+            }
+            _ => return Err(self.unexpected(Some("a pattern"))),
+        };
+        Ok(Pattern::new(kind, span.start..self.prev_end))
+    }
+
+    /// Parses a pattern for an enum variant with patterns for its fields, assuming the current
+    /// token is the `::` after the name of the enum
+    fn parse_variant_pattern(&mut self, enum_name: Spanned<String>) -> Result<Pattern, ParseError> {
+        let start = enum_name.span.start;
+        let path = self.parse_variant_path(enum_name)?;
+        let (kind, fields) = match self.current_tok.as_ref().map(|t| &t.0) {
+            Some(Token::LeftParen) => {
+                let (patterns, _) = self.parse_parenthesized(Self::parse_pattern)?;
+                let fields = patterns
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, pattern)| FieldPattern {
+                        name: index.to_string(),
+                        span: pattern.span.clone(),
+                        pattern,
+                    })
+                    .collect();
+                (VariantKind::Tuple, fields)
+            }
+            Some(Token::LeftBrace) => (VariantKind::Struct, self.parse_field_patterns()?),
+            _ => (VariantKind::Unit, Vec::new()),
+        };
+        Ok(Pattern::new(
+            PatternKind::Variant { path, kind, fields },
+            start..self.prev_end,
+        ))
+    }
+
+    /// Parses the field patterns in braces of a struct variant pattern, assuming the current token
+    /// is the left brace
+    fn parse_field_patterns(&mut self) -> Result<Vec<FieldPattern>, ParseError> {
+        let mut fields = Vec::new();
+        self.advance_no_eof()?;
+        while self.current()? != Token::RightBrace {
+            let span = self.span();
+            let Token::Ident(field) = self.current()? else {
+                return Err(self.unexpected(Some("a field name")));
+            };
+            let pattern = if self.advance_no_eof()? == Token::Colon {
+                self.advance();
+                self.parse_pattern()?
+            } else {
+                let binding = PatternKind::Binding {
+                    name: field.clone(),
+                    mutable: false,
+                };
+                Pattern::new(binding, span.clone())
+            };
+            fields.push(FieldPattern {
+                name: field,
+                pattern,
+                span: span.start..self.prev_end,
+            });
+            match self.current()? {
+                Token::Comma => {
+                    self.advance();
+                }
+                Token::RightBrace => break,
+                _ => return Err(self.unexpected(Some("`,` or `}`"))),
+            }
+        }
+        // Consume the right brace
+        self.advance();
+        Ok(fields)
     }
 
     #[instrument(skip(self))]
     fn parse_expr(&mut self) -> Result<Expr, ParseError> {
         tracing::trace!("Parsing expression");
+        let start = self.span().start;
+        let target = self.parse_binary(0)?;
+        let op = match self.current_tok.as_ref().map(|t| &t.0) {
+            Some(Token::Assign) => None,
+            Some(Token::PlusAssign) => Some(BinaryOp::Add),
+            Some(Token::MinusAssign) => Some(BinaryOp::Sub),
+            Some(Token::StarAssign) => Some(BinaryOp::Mul),
+            Some(Token::SlashAssign) => Some(BinaryOp::Div),
+            Some(Token::PercentAssign) => Some(BinaryOp::Rem),
+            Some(Token::AmpAssign) => Some(BinaryOp::BitAnd),
+            Some(Token::PipeAssign) => Some(BinaryOp::BitOr),
+            Some(Token::CaretAssign) => Some(BinaryOp::BitXor),
+            Some(Token::ShlAssign) => Some(BinaryOp::Shl),
+            Some(Token::ShrAssign) => Some(BinaryOp::Shr),
+            _ => return Ok(target),
+        };
+        self.advance();
+        // Assignments are right associative, so `a = b = c` is `a = (b = c)`
+        let value = self.parse_expr()?;
+        Ok(Expr::new(
+            ExprKind::Assign {
+                op,
+                target: Box::new(target),
+                value: Box::new(value),
+            },
+            start..self.prev_end,
+        ))
+    }
+
+    /// Parses binary operators using precedence climbing. Only operators with a higher precedence
+    /// than `min_precedence` are parsed, the rest are left for the caller.
+    #[instrument(skip(self))]
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expr, ParseError> {
+        tracing::trace!("Parsing binary expression");
+        let start = self.span().start;
+        let mut lhs = self.parse_unary()?;
+        while let Some(tok) = self.current_tok.as_ref().map(|t| &t.0) {
+            // `as` binds tighter than any binary operator, but looser than the unary operators, so
+            // `-x as u8` is `(-x) as u8`
+            if *tok == Token::KwAs {
+                self.advance();
+                let ty_start = self.span().start;
+                let ty = self.parse_type()?;
+                lhs = Expr::new(
+                    ExprKind::Cast {
+                        expr: Box::new(lhs),
+                        ty: Spanned::new(ty, ty_start..self.prev_end),
+                    },
+                    start..self.prev_end,
+                );
+                continue;
+            }
+            let Some((op, precedence)) = binary_op(tok) else {
+                break;
+            };
+            if precedence <= min_precedence {
+                break;
+            }
+            self.advance();
+            // All binary operators are left associative, so the right hand side only takes
+            // operators that bind tighter than this one
+            let rhs = self.parse_binary(precedence)?;
+            lhs = Expr::new(
+                ExprKind::Binary {
+                    op,
+                    lhs: Box::new(lhs),
+                    rhs: Box::new(rhs),
+                },
+                start..self.prev_end,
+            );
+        }
+        Ok(lhs)
+    }
+
+    #[instrument(skip(self))]
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        tracing::trace!("Parsing unary expression");
+        let start = self.span().start;
+        let op = match self.current()? {
+            Token::Minus => UnaryOp::Neg,
+            Token::Bang => UnaryOp::Not,
+            Token::Amp => {
+                let mutable = self.advance_no_eof()? == Token::KwMut;
+                if mutable {
+                    self.advance();
+                }
+                let expr = self.parse_unary()?;
+                return Ok(Expr::new(
+                    ExprKind::Ref {
+                        mutable,
+                        expr: Box::new(expr),
+                    },
+                    start..self.prev_end,
+                ));
+            }
+            // `&&x` is lexed as a single token, but borrows twice
+            Token::AndAnd => {
+                self.advance();
+                let expr = self.parse_unary()?;
+                let inner = Expr::new(
+                    ExprKind::Ref {
+                        mutable: false,
+                        expr: Box::new(expr),
+                    },
+                    start + 1..self.prev_end,
+                );
+                return Ok(Expr::new(
+                    ExprKind::Ref {
+                        mutable: false,
+                        expr: Box::new(inner),
+                    },
+                    start..self.prev_end,
+                ));
+            }
+            Token::Star => {
+                self.advance();
+                let expr = self.parse_unary()?;
+                return Ok(Expr::new(
+                    ExprKind::Deref(Box::new(expr)),
+                    start..self.prev_end,
+                ));
+            }
+            _ => return self.parse_postfix(),
+        };
+        self.advance();
+        let expr = self.parse_unary()?;
+        Ok(Expr::new(
+            ExprKind::Unary {
+                op,
+                expr: Box::new(expr),
+            },
+            start..self.prev_end,
+        ))
+    }
+
+    /// Parses a primary expression followed by any number of calls, field accesses, method calls
+    /// and indexing
+    #[instrument(skip(self))]
+    fn parse_postfix(&mut self) -> Result<Expr, ParseError> {
+        tracing::trace!("Parsing postfix expression");
+        let start = self.span().start;
+        let mut expr = self.parse_primary()?;
+        loop {
+            let kind = match self.current_tok.as_ref().map(|t| &t.0) {
+                Some(Token::LeftParen) => {
+                    self.advance();
+                    let arguments = self.with_struct_literals(true, Self::parse_argument_list)?;
+                    // Consume the right parenthesis
+                    self.advance();
+                    ExprKind::Call {
+                        callee: Box::new(expr),
+                        arguments,
+                    }
+                }
+                Some(Token::LeftBracket) => {
+                    self.advance();
+                    let index = self.with_struct_literals(true, Self::parse_expr)?;
+                    self.expect(Token::RightBracket)?;
+                    self.advance();
+                    ExprKind::Index {
+                        expr: Box::new(expr),
+                        index: Box::new(index),
+                    }
+                }
+                Some(Token::Dot) => {
+                    let fields = match self.advance_no_eof()? {
+                        Token::Ident(field) => {
+                            let field = Spanned::new(field, self.span());
+                            if self.advance() == Some(Token::LeftParen) {
+                                self.advance();
+                                let arguments =
+                                    self.with_struct_literals(true, Self::parse_argument_list)?;
+                                // Consume the right parenthesis
+                                self.advance();
+                                expr = Expr::new(
+                                    ExprKind::MethodCall {
+                                        receiver: Box::new(expr),
+                                        method: field,
+                                        arguments,
+                                    },
+                                    start..self.prev_end,
+                                );
+                                continue;
+                            }
+                            vec![field]
+                        }
+                        Token::NumberLit(_) => {
+                            let indices = self.parse_tuple_indices()?;
+                            self.advance();
+                            indices
+                        }
+                        _ => return Err(self.unexpected(Some("a field name"))),
+                    };
+                    // `t.0.1` is lexed as a float, which is split into two accesses
+                    for field in fields {
+                        let end = field.span.end;
+                        expr = Expr::new(
+                            ExprKind::Field {
+                                expr: Box::new(expr),
+                                field,
+                            },
+                            start..end,
+                        );
+                    }
+                    continue;
+                }
+                _ => break,
+            };
+            expr = Expr::new(kind, start..self.prev_end);
+        }
+        Ok(expr)
+    }
+
+    /// Gets the tuple indices from the number literal after a `.`, which is a single index like
+    /// `0`, or two indices like `0.1`. The literal is not consumed
+    fn parse_tuple_indices(&mut self) -> Result<Vec<Spanned<String>>, ParseError> {
+        let span = self.span();
+        let text = &self.lexer.source()[span.clone()];
+        let mut indices = Vec::new();
+        let mut offset = span.start;
+        for index in text.split('.') {
+            // Only plain decimal indices are allowed, without suffixes, separators or leading
+            // zeros
+            let valid = index
+                .parse::<usize>()
+                .is_ok_and(|value| value.to_string() == index);
+            if !valid {
+                return Err(ParseError::UnexpectedToken {
+                    found: self.current()?,
+                    expected: Some("a tuple index".to_string()),
+                    span,
+                });
+            }
+            indices.push(Spanned::new(
+                index.to_string(),
+                offset..offset + index.len(),
+            ));
+            offset += index.len() + 1;
+        }
+        Ok(indices)
+    }
+
+    /// Parses the fields of a struct literal, assuming the current token is the left brace after
+    /// the name of the struct
+    #[instrument(skip(self))]
+    fn parse_struct_literal(&mut self, name: Spanned<String>) -> Result<Expr, ParseError> {
+        tracing::trace!("Parsing struct literal");
+        let start = name.span.start;
+        let fields = self.parse_field_inits()?;
+        Ok(Expr::new(
+            ExprKind::StructLiteral { name, fields },
+            start..self.prev_end,
+        ))
+    }
+
+    /// Parses the fields in braces of a struct literal or struct variant, assuming the current
+    /// token is the left brace
+    fn parse_field_inits(&mut self) -> Result<Vec<FieldInit>, ParseError> {
+        let mut fields = Vec::new();
+        self.advance_no_eof()?;
+        while self.current()? != Token::RightBrace {
+            let span = self.span();
+            let Token::Ident(field) = self.current()? else {
+                return Err(self.unexpected(Some("a field name")));
+            };
+            let value = if self.advance_no_eof()? == Token::Colon {
+                self.advance();
+                self.parse_expr()?
+            } else {
+                Expr::new(ExprKind::Variable(field.clone()), span.clone())
+            };
+            fields.push(FieldInit {
+                name: field,
+                value,
+                span: span.start..self.prev_end,
+            });
+            match self.current()? {
+                Token::Comma => {
+                    self.advance();
+                }
+                Token::RightBrace => break,
+                _ => return Err(self.unexpected(Some("`,` or `}`"))),
+            }
+        }
+        // Consume the right brace
+        self.advance();
+        Ok(fields)
+    }
+
+    /// Parses the path of an enum variant, assuming the current token is the `::` after the name
+    /// of the enum
+    fn parse_variant_path(
+        &mut self,
+        enum_name: Spanned<String>,
+    ) -> Result<VariantPath, ParseError> {
+        let Token::Ident(variant) = self.advance_no_eof()? else {
+            return Err(self.unexpected(Some("a variant name")));
+        };
+        let variant = Spanned::new(variant, self.span());
+        self.advance();
+        Ok(VariantPath { enum_name, variant })
+    }
+
+    /// Parses a value of an enum variant with its fields, assuming the current token is the `::`
+    /// after the name of the enum
+    #[instrument(skip(self))]
+    fn parse_variant(&mut self, enum_name: Spanned<String>) -> Result<Expr, ParseError> {
+        tracing::trace!("Parsing enum variant");
+        let start = enum_name.span.start;
+        let path = self.parse_variant_path(enum_name)?;
+        let (kind, fields) = match self.current_tok.as_ref().map(|t| &t.0) {
+            Some(Token::LeftParen) => {
+                let (values, _) = self.with_struct_literals(true, |parser| {
+                    parser.parse_parenthesized(Self::parse_expr)
+                })?;
+                let fields = values
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, value)| FieldInit {
+                        name: index.to_string(),
+                        span: value.span.clone(),
+                        value,
+                    })
+                    .collect();
+                (VariantKind::Tuple, fields)
+            }
+            Some(Token::LeftBrace) if self.struct_literals => {
+                (VariantKind::Struct, self.parse_field_inits()?)
+            }
+            _ => (VariantKind::Unit, Vec::new()),
+        };
+        Ok(Expr::new(
+            ExprKind::Variant { path, kind, fields },
+            start..self.prev_end,
+        ))
+    }
+
+    /// Parses a `match` expression, assuming the current token is the `match` keyword. Arms are
+    /// separated by commas, which are optional after an arm whose body ends with a block
+    #[instrument(skip(self))]
+    fn parse_match(&mut self) -> Result<Expr, ParseError> {
+        tracing::trace!("Parsing match");
+        let start = self.span().start;
+        self.advance();
+        let scrutinee = self.with_struct_literals(false, Self::parse_expr)?;
+        self.expect(Token::LeftBrace)?;
+        self.advance_no_eof()?;
+        let mut arms = Vec::new();
+        while self.current()? != Token::RightBrace {
+            let arm_start = self.span().start;
+            let pattern = self.parse_pattern()?;
+            self.expect(Token::FatArrow)?;
+            self.advance();
+            let body = match self.current()? {
+                // A block body ends the arm, so `{ a } -1 => b` starts another arm instead of
+                // subtracting from the block
+                Token::LeftBrace => {
+                    let block_start = self.span().start;
+                    let block = self.with_struct_literals(true, Self::parse_block);
+                    Expr::new(ExprKind::Block(block), block_start..self.prev_end)
+                }
+                _ => self.with_struct_literals(true, Self::parse_expr)?,
+            };
+            let ends_with_block = body.kind.ends_with_block();
+            arms.push(MatchArm {
+                pattern,
+                body,
+                span: arm_start..self.prev_end,
+            });
+            match self.current()? {
+                Token::Comma => {
+                    self.advance();
+                }
+                Token::RightBrace => break,
+                _ if ends_with_block => (),
+                _ => return Err(self.unexpected(Some("`,` or `}`"))),
+            }
+        }
+        // Consume the right brace
+        self.advance();
+        Ok(Expr::new(
+            ExprKind::Match {
+                scrutinee: Box::new(scrutinee),
+                arms,
+            },
+            start..self.prev_end,
+        ))
+    }
+
+    /// Parses the comma separated arguments of a call, assuming the current token is the first
+    /// token after the left parenthesis. The right parenthesis is not consumed.
+    #[instrument(skip(self))]
+    fn parse_argument_list(&mut self) -> Result<Vec<Expr>, ParseError> {
+        tracing::trace!("Parsing argument list");
+        let mut arguments = Vec::new();
+        while self.current()? != Token::RightParen {
+            arguments.push(self.parse_expr()?);
+            match self.current()? {
+                Token::Comma => {
+                    self.advance();
+                }
+                Token::RightParen => break,
+                _ => return Err(self.unexpected(Some("`,` or `)`"))),
+            }
+        }
+        Ok(arguments)
+    }
+
+    /// Parses an expression that isn't made up of other expressions joined by operators
+    #[instrument(skip(self))]
+    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
+        tracing::trace!("Parsing primary expression");
+        let span = self.span();
         match self.current()? {
             Token::NumberLit(num) => {
-                self.advance()?;
+                self.advance();
+                Ok(Expr::new(ExprKind::Literal(Literal::Number(num)), span))
+            }
+            Token::KwTrue | Token::KwFalse => {
+                let value = matches!(self.current()?, Token::KwTrue);
+                self.advance();
+                Ok(Expr::new(ExprKind::Literal(Literal::Bool(value)), span))
+            }
+            Token::StrLit(value) => {
+                self.advance();
+                Ok(Expr::new(ExprKind::Literal(Literal::Str(value)), span))
+            }
+            Token::ByteStrLit(value) => {
+                self.advance();
+                Ok(Expr::new(ExprKind::Literal(Literal::ByteStr(value)), span))
+            }
+            Token::KwReturn => self.parse_return(),
+            Token::LeftBrace => {
+                let block = self.with_struct_literals(true, Self::parse_block);
+                Ok(Expr::new(ExprKind::Block(block), span.start..self.prev_end))
+            }
+            Token::KwIf => self.parse_if(),
+            Token::KwMatch => self.parse_match(),
+            Token::KwUnsafe => {
+                self.expect_advance(Token::LeftBrace)?;
+                let block = self.with_struct_literals(true, Self::parse_block);
                 Ok(Expr::new(
-                    ExprKind::Literal(Literal::Number(num)),
-                    std::ops::Range::default(),
+                    ExprKind::Unsafe(block),
+                    span.start..self.prev_end,
                 ))
             }
-            Token::KwReturn => self.parse_statement(),
-            Token::Ident(name) => {
-                self.advance()?;
+            Token::KwWhile => self.parse_while(None),
+            Token::KwLoop => self.parse_loop(None),
+            Token::Label(label) => {
+                self.expect_advance(Token::Colon)?;
+                match self.advance_no_eof()? {
+                    Token::KwWhile => self.parse_while(Some((label, span.start))),
+                    Token::KwLoop => self.parse_loop(Some((label, span.start))),
+                    _ => Err(self.unexpected(Some("a loop"))),
+                }
+            }
+            Token::KwBreak => {
+                self.advance();
+                let label = self.parse_label();
+                let value = match self.current_tok.as_ref().map(|t| &t.0) {
+                    None | Some(Token::Semicolon) | Some(Token::RightBrace) => None,
+                    Some(_) => Some(Box::new(self.parse_expr()?)),
+                };
+                Ok(Expr::new(
+                    ExprKind::Break { label, value },
+                    span.start..self.prev_end,
+                ))
+            }
+            Token::KwContinue => {
+                self.advance();
+                let label = self.parse_label();
                 Ok(Expr::new(
-                    ExprKind::Variable(name),
-                    std::ops::Range::default(),
+                    ExprKind::Continue { label },
+                    span.start..self.prev_end,
                 ))
             }
-            tok => todo!("Unexpected token (unimplemented): {:?}", tok),
+            Token::Ident(name) => match self.advance() {
+                Some(Token::LeftBrace) if self.struct_literals => {
+                    self.parse_struct_literal(Spanned::new(name, span))
+                }
+                Some(Token::PathSep) => self.parse_variant(Spanned::new(name, span)),
+                _ => Ok(Expr::new(ExprKind::Variable(name), span)),
+            },
+            Token::LeftBracket => self.with_struct_literals(true, Self::parse_array),
+            Token::LeftParen => {
+                let (mut elements, tuple) = self.with_struct_literals(true, |parser| {
+                    parser.parse_parenthesized(Self::parse_expr)
+                })?;
+                if tuple {
+                    return Ok(Expr::new(
+                        ExprKind::Tuple(elements),
+                        span.start..self.prev_end,
+                    ));
+                }
+                let mut expr = elements.pop().unwrap();
+                // The parentheses are included in the span, so diagnostics underline them as well
+                expr.span = span.start..self.prev_end;
+                Ok(expr)
+            }
+            _ => Err(self.unexpected(Some("an expression"))),
         }
     }
 
-    /// Parses a type, assuming the first token is consumed
+    /// Parses an array literal `[a, b]` or `[value; count]`, assuming the current token is the
+    /// left bracket
+    #[instrument(skip(self))]
+    fn parse_array(&mut self) -> Result<Expr, ParseError> {
+        tracing::trace!("Parsing array literal");
+        let start = self.span().start;
+        self.advance_no_eof()?;
+        let mut elements = Vec::new();
+        while self.current()? != Token::RightBracket {
+            elements.push(self.parse_expr()?);
+            match self.current()? {
+                Token::Comma => {
+                    self.advance();
+                }
+                Token::Semicolon if elements.len() == 1 => {
+                    self.advance();
+                    let count = self.parse_array_len()?;
+                    self.expect(Token::RightBracket)?;
+                    self.advance();
+                    return Ok(Expr::new(
+                        ExprKind::ArrayRepeat {
+                            value: Box::new(elements.pop().unwrap()),
+                            count,
+                        },
+                        start..self.prev_end,
+                    ));
+                }
+                Token::RightBracket => break,
+                _ => return Err(self.unexpected(Some("`,` or `]`"))),
+            }
+        }
+        // Consume the right bracket
+        self.advance();
+        Ok(Expr::new(ExprKind::Array(elements), start..self.prev_end))
+    }
+
+    /// Parses the length of an array type or repeat literal, which is an integer literal without
+    /// a suffix other than `usize`
+    fn parse_array_len(&mut self) -> Result<u64, ParseError> {
+        let len = match self.current()? {
+            Token::NumberLit(NumberLiteral {
+                value: NumberValue::Int(len),
+                suffix: None | Some(NumberType::USIZE),
+            }) => u64::try_from(len).ok(),
+            _ => None,
+        };
+        let Some(len) = len else {
+            return Err(self.unexpected(Some("an array length")));
+        };
+        self.advance();
+        Ok(len)
+    }
+
+    /// Parses a type, assuming the current token is the first token of the type
     #[instrument(skip(self))]
     fn parse_type(&mut self) -> Result<Type, ParseError> {
         tracing::trace!("Parsing type");
+        let span = self.span();
         match self.current()? {
-            Token::LeftParen => match self.advance()?.ok_or(ParseError::UnexpectedEOF)? {
-                Token::RightParen => {
-                    self.advance()?;
-                    Ok(Type::empty())
+            Token::LeftParen => {
+                let (mut types, tuple) = self.parse_parenthesized(Self::parse_type)?;
+                if tuple {
+                    Ok(Type::Tuple(types))
+                } else {
+                    Ok(types.pop().unwrap())
                 }
-                _ => unimplemented!("Proper type parsing is not yet implemented!"),
-            },
+            }
+            // A string is only ever used behind a reference, as `&str`
+            Token::Ident(ty) if ty == "str" => Err(ParseError::Unsupported(
+                "`str` without a reference".to_string(),
+                span,
+            )),
             Token::Ident(ty) => {
-                self.advance()?;
-                // TODO: Support custom error messages
-                Ok(Type::from_str(&ty)
-                    .map_err(|_err| ParseError::UnexpectedToken(Token::Ident(ty)))?)
+                self.advance();
+                Type::from_str(&ty).map_err(|_err| ParseError::InvalidType(ty, span))
             }
-            tok => Err(ParseError::UnexpectedToken(tok)),
+            Token::LeftBracket => {
+                self.advance_no_eof()?;
+                let ty = self.parse_type()?;
+                self.expect(Token::Semicolon)?;
+                self.advance();
+                let len = self.parse_array_len()?;
+                self.expect(Token::RightBracket)?;
+                self.advance();
+                Ok(Type::Array(Box::new(ty), len))
+            }
+            Token::Amp => {
+                self.advance_no_eof()?;
+                self.parse_reference_type(span.start)
+            }
+            // `&&T` is lexed as a single token, but is a reference to a reference
+            Token::AndAnd => {
+                self.advance_no_eof()?;
+                let ty = self.parse_reference_type(span.start + 1)?;
+                Ok(Type::Ref {
+                    ty: Box::new(ty),
+                    mutable: false,
+                })
+            }
+            Token::Star => {
+                let mutable = match self.advance_no_eof()? {
+                    Token::KwConst => false,
+                    Token::KwMut => true,
+                    _ => return Err(self.unexpected(Some("`const` or `mut`"))),
+                };
+                self.advance_no_eof()?;
+                let ty = self.parse_type()?;
+                Ok(Type::Pointer {
+                    ty: Box::new(ty),
+                    mutable,
+                })
+            }
+            _ => Err(self.unexpected(Some("a type"))),
         }
     }
 
+    /// Parses a reference type `&T`, `&mut T`, a slice type `&[T]` or `&str`, assuming the current
+    /// token is the first token after the `&`, which starts at `start`
+    fn parse_reference_type(&mut self, start: usize) -> Result<Type, ParseError> {
+        let mutable = self.current()? == Token::KwMut;
+        if mutable {
+            self.advance_no_eof()?;
+        }
+        if self.current()? == Token::Ident("str".to_string()) {
+            // Strings are constants in read-only memory, so they can't be written to
+            if mutable {
+                return Err(ParseError::Unsupported(
+                    "mutable strings".to_string(),
+                    start..self.span().end,
+                ));
+            }
+            self.advance();
+            return Ok(Type::Str);
+        }
+        if self.current()? != Token::LeftBracket {
+            let ty = self.parse_type()?;
+            return Ok(Type::Ref {
+                ty: Box::new(ty),
+                mutable,
+            });
+        }
+        self.advance_no_eof()?;
+        let ty = self.parse_type()?;
+        // Borrowing an array makes a slice, so there are no references to arrays
+        if self.current()? == Token::Semicolon {
+            return Err(ParseError::Unsupported(
+                "references to arrays".to_string(),
+                start..self.span().end,
+            ));
+        }
+        self.expect(Token::RightBracket)?;
+        self.advance();
+        Ok(Type::Slice {
+            ty: Box::new(ty),
+            mutable,
+        })
+    }
+
+    /// Parses the arguments of a function definition, assuming the current token is the first
+    /// token after the left parenthesis. The right parenthesis is not consumed.
     #[instrument(skip(self))]
-    fn parse_typed_argument_list(&mut self) -> Result<Vec<(String, Type)>, ParseError> {
+    fn parse_typed_argument_list(&mut self) -> Result<Vec<Argument>, ParseError> {
         tracing::trace!("Parsing typed argument list");
         let mut args = Vec::new();
-        while let Some(tok) = self.advance()? {
-            match tok {
+        loop {
+            let start = self.span().start;
+            match self.current()? {
+                Token::RightParen => break,
                 Token::Ident(name) => {
-                    self.advance()?;
+                    self.advance();
                     self.expect(Token::Colon)?;
-                    self.advance()?;
+                    self.advance();
+                    let ty_start = self.span().start;
                     let ty = self.parse_type()?;
                     tracing::trace!(?name, ?ty, "Parsed argument");
-                    args.push((name, ty));
+                    args.push(Argument {
+                        name,
+                        ty: Spanned::new(ty, ty_start..self.prev_end),
+                        span: start..self.prev_end,
+                    });
                 }
-                tok => return Err(ParseError::UnexpectedToken(tok)),
+                _ => return Err(self.unexpected(Some("an argument name"))),
             }
             match self.current()? {
-                Token::Comma => (),
+                Token::Comma => {
+                    self.advance();
+                }
                 _ => break,
             }
         }
@@ -290,15 +1564,42 @@ impl<'ctx> Parser<'ctx> {
     }
 }
 
+/// Gets the operator and its precedence for tokens that are binary operators, a higher precedence
+/// binds tighter
+fn binary_op(tok: &Token) -> Option<(BinaryOp, u8)> {
+    let op = match tok {
+        Token::Star => (BinaryOp::Mul, 10),
+        Token::Slash => (BinaryOp::Div, 10),
+        Token::Percent => (BinaryOp::Rem, 10),
+        Token::Plus => (BinaryOp::Add, 9),
+        Token::Minus => (BinaryOp::Sub, 9),
+        Token::Shl => (BinaryOp::Shl, 8),
+        Token::Shr => (BinaryOp::Shr, 8),
+        Token::Amp => (BinaryOp::BitAnd, 7),
+        Token::Caret => (BinaryOp::BitXor, 6),
+        Token::Pipe => (BinaryOp::BitOr, 5),
+        Token::EqEq => (BinaryOp::Eq, 4),
+        Token::NotEq => (BinaryOp::Ne, 4),
+        Token::Lt => (BinaryOp::Lt, 4),
+        Token::Le => (BinaryOp::Le, 4),
+        Token::Gt => (BinaryOp::Gt, 4),
+        Token::Ge => (BinaryOp::Ge, 4),
+        Token::AndAnd => (BinaryOp::And, 3),
+        Token::OrOr => (BinaryOp::Or, 2),
+        _ => return None,
+    };
+    Some(op)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use redox_ast::Block;
+    use redox_ast::{Block, TopLevelKind};
 
     #[test]
     fn test_parse_function_def() {
         let mut parser = Parser::with_source("fn foo() {}");
-        let top_levels = parser.parse().unwrap();
+        let top_levels = parser.parse().into_result().unwrap();
         assert_eq!(top_levels.len(), 1);
         assert_eq!(
             top_levels[0],
@@ -308,10 +1609,740 @@ mod tests {
                     arguments: Vec::new(),
                     return_ty: None,
                     attributes: Vec::new(),
-                    body: Block::empty(),
+                    body: Block::empty(9..11),
+                    span: 0..8,
                 }),
-                std::ops::Range::default()
+                0..11
             ))
         );
     }
+
+    #[test]
+    fn test_parse_spans() {
+        let source = "fn main(argc: i32) -> i32 {\n    return argc;\n}";
+        let mut parser = Parser::with_source(source);
+        let top_levels = parser.parse().into_result().unwrap();
+        assert_eq!(top_levels[0].span, 0..source.len());
+        let TopLevelKind::Expr(expr) = &top_levels[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        assert_eq!(function.span, 0..25);
+        assert_eq!(&source[function.arguments[0].span.clone()], "argc: i32");
+        assert_eq!(&source[function.arguments[0].ty.span.clone()], "i32");
+        assert_eq!(
+            &source[function.return_ty.as_ref().unwrap().span.clone()],
+            "i32"
+        );
+        assert_eq!(function.body.span, 26..source.len());
+
+        let statement = &function.body.statements[0];
+        assert_eq!(&source[statement.span.clone()], "return argc");
+        let ExprKind::Return(Some(value)) = &statement.kind else {
+            panic!("Expected a return statement");
+        };
+        assert_eq!(&source[value.span.clone()], "argc");
+    }
+
+    #[test]
+    fn test_error_diagnostic() {
+        let source = "fn main() -> i32 {\n    let x = 1\n}";
+        let errors = Parser::with_source(source).parse().errors;
+        assert_eq!(errors.len(), 1);
+        let err = &errors[0];
+        assert_eq!(err.span(), 33..34);
+        let file = redox_ast::SourceFile::new("main.rx", source);
+        assert_eq!(
+            err.to_diagnostic().render(&file),
+            "error: unexpected `}`\n \
+             --> main.rx:3:1\n  \
+             |\n\
+             3 | }\n  \
+             | ^ expected `;`\n"
+        );
+    }
+
+    #[test]
+    fn test_error_recovery() {
+        let source =
+            "fn foo() -> i32 {\n    return 1\n    return 2;\n    return );\n    return 3;\n}\n\
+                      ;\n\
+                      fn bar() -> i32 { return 3; }\n\
+                      fn baz( { return 4; }\n\
+                      fn qux() -> i32 { return 5;";
+        let output = Parser::with_source(source).parse();
+        let spans = output
+            .errors
+            .iter()
+            .map(|err| &source[err.span()])
+            .collect::<Vec<_>>();
+        // The missing semicolon, the invalid return value, the stray semicolon, the invalid
+        // argument, and the unclosed block at the end of the file
+        assert_eq!(spans, vec!["return", ")", ";", "{", ""]);
+
+        let names = output
+            .ast
+            .iter()
+            .map(|top_level| {
+                let TopLevelKind::Expr(expr) = &top_level.kind else {
+                    panic!("Expected an expression");
+                };
+                match &expr.kind {
+                    ExprKind::FunctionDef(function) => {
+                        (function.name.as_str(), function.body.statements.len())
+                    }
+                    _ => panic!("Expected a function definition"),
+                }
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![("foo", 1), ("bar", 1), ("qux", 1)]);
+    }
+
+    #[rstest::rstest]
+    #[case("i8")]
+    #[case("i16")]
+    #[case("i128")]
+    #[case("isize")]
+    #[case("u8")]
+    #[case("u64")]
+    #[case("usize")]
+    #[case("f32")]
+    #[case("f64")]
+    #[case("bool")]
+    #[case("Point")]
+    #[case("()")]
+    #[case("(i32,)")]
+    #[case("(i32, (bool, f64))")]
+    #[case("[i32; 4]")]
+    #[case("[[u8; 2]; 3]")]
+    #[case("&[(i32, bool)]")]
+    #[case("&mut [u8]")]
+    #[case("&i32")]
+    #[case("&mut (i32, bool)")]
+    #[case("&&mut Point")]
+    #[case("&str")]
+    #[case("&[&str]")]
+    #[case("*const u8")]
+    #[case("*mut *const [i32; 2]")]
+    fn test_parse_type(#[case] input: &str) {
+        let source = format!("fn foo(a: {input}) {{}}");
+        let top_levels = Parser::with_source(&source).parse().into_result().unwrap();
+        let TopLevelKind::Expr(expr) = &top_levels[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        assert_eq!(function.arguments[0].ty.value.to_string(), input);
+    }
+
+    #[rstest::rstest]
+    #[case("i7")]
+    #[case("f16")]
+    #[case("fsize")]
+    #[case("u")]
+    fn test_parse_invalid_type(#[case] input: &str) {
+        let source = format!("fn foo(a: {input}) {{}}");
+        let errors = Parser::with_source(&source)
+            .parse()
+            .into_result()
+            .unwrap_err();
+        let [ParseError::InvalidType(ty, span)] = errors.as_slice() else {
+            panic!("Expected an invalid type error, found {errors:?}");
+        };
+        assert_eq!(ty, input);
+        assert_eq!(&source[span.clone()], input);
+    }
+
+    #[rstest::rstest]
+    #[case("fn foo(a: [i32; n]) {}")]
+    #[case("fn foo(a: [i32; 2u8]) {}")]
+    #[case("fn foo() { [0; -1]; }")]
+    #[case("fn foo() { [0; 1.5]; }")]
+    fn test_parse_invalid_array_len(#[case] source: &str) {
+        let errors = Parser::with_source(source)
+            .parse()
+            .into_result()
+            .unwrap_err();
+        let [ParseError::UnexpectedToken { expected, .. }] = errors.as_slice() else {
+            panic!("Expected an unexpected token error, found {errors:?}");
+        };
+        assert_eq!(expected.as_deref(), Some("an array length"));
+    }
+
+    #[test]
+    fn test_parse_let() {
+        let source = "fn foo() -> i32 { let x: i32 = 1; let mut y = x; return y; }";
+        let top_levels = Parser::with_source(source).parse().into_result().unwrap();
+        let TopLevelKind::Expr(expr) = &top_levels[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        let statements = &function.body.statements;
+        assert_eq!(
+            statements[0],
+            Expr::new(
+                ExprKind::Let(LetBinding {
+                    pattern: Pattern::new(
+                        PatternKind::Binding {
+                            name: "x".to_string(),
+                            mutable: false
+                        },
+                        22..23
+                    ),
+                    ty: Some(Spanned::new(
+                        Type::Number(redox_ast::NumberType::I32),
+                        25..28
+                    )),
+                    value: Box::new(Expr::new(
+                        ExprKind::Literal(Literal::Number(redox_ast::NumberLiteral::int(1))),
+                        31..32
+                    )),
+                }),
+                18..32
+            )
+        );
+        assert_eq!(
+            statements[1].kind,
+            ExprKind::Let(LetBinding {
+                pattern: Pattern::new(
+                    PatternKind::Binding {
+                        name: "y".to_string(),
+                        mutable: true
+                    },
+                    38..43
+                ),
+                ty: None,
+                value: Box::new(Expr::new(ExprKind::Variable("x".to_string()), 46..47)),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_let_pattern() {
+        let source = "fn foo() { let (a, (mut b, _), (c)) = t; }";
+        let top_levels = Parser::with_source(source).parse().into_result().unwrap();
+        let TopLevelKind::Expr(expr) = &top_levels[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        let ExprKind::Let(binding) = &function.body.statements[0].kind else {
+            panic!("Expected a let binding");
+        };
+        let PatternKind::Tuple(elements) = &binding.pattern.kind else {
+            panic!("Expected a tuple pattern");
+        };
+        assert_eq!(elements.len(), 3);
+        assert_eq!(&source[elements[1].span.clone()], "(mut b, _)");
+        let PatternKind::Tuple(nested) = &elements[1].kind else {
+            panic!("Expected a tuple pattern");
+        };
+        assert_eq!(nested[1].kind, PatternKind::Wildcard);
+        // Parentheses around a single pattern don't make a tuple
+        assert_eq!(
+            elements[2].kind,
+            PatternKind::Binding {
+                name: "c".to_string(),
+                mutable: false
+            }
+        );
+    }
+
+    #[rstest::rstest]
+    #[case("t.01")]
+    #[case("t.0u8")]
+    #[case("t.1e1")]
+    fn test_parse_invalid_tuple_index(#[case] input: &str) {
+        let source = format!("fn foo() {{ {input}; }}");
+        let errors = Parser::with_source(&source)
+            .parse()
+            .into_result()
+            .unwrap_err();
+        let [ParseError::UnexpectedToken { span, .. }] = errors.as_slice() else {
+            panic!("Expected an unexpected token error, found {errors:?}");
+        };
+        assert_eq!(&source[span.clone()], &input[2..]);
+    }
+
+    #[test]
+    fn test_parse_assign() {
+        let source = "fn foo() { x = y += 1; }";
+        let top_levels = Parser::with_source(source).parse().into_result().unwrap();
+        let TopLevelKind::Expr(expr) = &top_levels[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        let variable = |name: &str, span| Expr::new(ExprKind::Variable(name.to_string()), span);
+        assert_eq!(
+            function.body.statements[0],
+            Expr::new(
+                ExprKind::Assign {
+                    op: None,
+                    target: Box::new(variable("x", 11..12)),
+                    value: Box::new(Expr::new(
+                        ExprKind::Assign {
+                            op: Some(BinaryOp::Add),
+                            target: Box::new(variable("y", 15..16)),
+                            value: Box::new(Expr::new(
+                                ExprKind::Literal(Literal::Number(redox_ast::NumberLiteral::int(
+                                    1
+                                ))),
+                                20..21
+                            )),
+                        },
+                        15..21
+                    )),
+                },
+                11..21
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_if() {
+        let source = "fn foo() { if a { 1 } else if b { 2 } else { return; } if c {} d }";
+        let top_levels = Parser::with_source(source).parse().into_result().unwrap();
+        let TopLevelKind::Expr(expr) = &top_levels[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        let body = &function.body;
+        // Statements ending with a block don't need a semicolon
+        assert_eq!(body.statements.len(), 2);
+        assert_eq!(
+            body.tail.as_deref().map(|tail| &tail.kind),
+            Some(&ExprKind::Variable("d".to_string()))
+        );
+        let ExprKind::If {
+            condition,
+            then_block,
+            else_block: Some(else_block),
+        } = &body.statements[0].kind
+        else {
+            panic!("Expected an if expression");
+        };
+        assert_eq!(&source[condition.span.clone()], "a");
+        assert_eq!(&source[then_block.span.clone()], "{ 1 }");
+        assert!(then_block.statements.is_empty());
+        assert_eq!(&source[then_block.tail.as_ref().unwrap().span.clone()], "1");
+        // `else if` is an else block that only contains the nested `if`
+        assert!(else_block.statements.is_empty());
+        let nested = else_block.tail.as_ref().unwrap();
+        assert_eq!(&source[nested.span.clone()], "if b { 2 } else { return; }");
+        let ExprKind::If {
+            else_block: Some(else_block),
+            ..
+        } = &nested.kind
+        else {
+            panic!("Expected an if expression");
+        };
+        assert_eq!(else_block.statements[0].kind, ExprKind::Return(None));
+    }
+
+    #[test]
+    fn test_parse_block_expr() {
+        let source = "fn foo() -> i32 { { let x = 1; } let y = { x }; { y } }";
+        let top_levels = Parser::with_source(source).parse().into_result().unwrap();
+        let TopLevelKind::Expr(expr) = &top_levels[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        let body = &function.body;
+        assert_eq!(body.statements.len(), 2);
+        assert_eq!(&source[body.statements[0].span.clone()], "{ let x = 1; }");
+        let ExprKind::Let(binding) = &body.statements[1].kind else {
+            panic!("Expected a let statement");
+        };
+        let ExprKind::Block(block) = &binding.value.kind else {
+            panic!("Expected a block");
+        };
+        assert_eq!(&source[block.tail.as_ref().unwrap().span.clone()], "x");
+        let tail = body.tail.as_ref().unwrap();
+        assert_eq!(&source[tail.span.clone()], "{ y }");
+        assert!(matches!(tail.kind, ExprKind::Block(_)));
+    }
+
+    #[test]
+    fn test_parse_struct() {
+        let source =
+            "struct Point { x: i32, y: f64, } fn foo() { let p = Point { y: 1.0, x }; p.x }";
+        let top_levels = Parser::with_source(source).parse().into_result().unwrap();
+        let TopLevelKind::Struct(def) = &top_levels[0].kind else {
+            panic!("Expected a struct declaration");
+        };
+        assert_eq!(def.name, "Point");
+        assert_eq!(&source[def.span.clone()], "Point");
+        let fields: Vec<_> = def
+            .fields
+            .iter()
+            .map(|field| (field.name.as_str(), field.ty.value.to_string()))
+            .collect();
+        assert_eq!(fields, [("x", "i32".to_string()), ("y", "f64".to_string())]);
+        assert_eq!(&source[def.fields[1].span.clone()], "y: f64");
+
+        let TopLevelKind::Expr(expr) = &top_levels[1].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        let ExprKind::Let(binding) = &function.body.statements[0].kind else {
+            panic!("Expected a let binding");
+        };
+        let ExprKind::StructLiteral { name, fields } = &binding.value.kind else {
+            panic!("Expected a struct literal");
+        };
+        assert_eq!(name.value, "Point");
+        assert_eq!(&source[binding.value.span.clone()], "Point { y: 1.0, x }");
+        assert_eq!(fields[0].name, "y");
+        // A field without a value uses the variable with the same name
+        assert_eq!(fields[1].value.kind, ExprKind::Variable("x".to_string()));
+        let ExprKind::Field { field, .. } = &function.body.tail.as_ref().unwrap().kind else {
+            panic!("Expected a field access");
+        };
+        assert_eq!(field.value, "x");
+    }
+
+    #[test]
+    fn test_parse_struct_condition() {
+        // The brace after a name in a condition starts the body, unless it is in parentheses
+        let source = "fn foo() { if a == B { c } while (D {}).e {} }";
+        let top_levels = Parser::with_source(source).parse().into_result().unwrap();
+        let TopLevelKind::Expr(expr) = &top_levels[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        let ExprKind::If { condition, .. } = &function.body.statements[0].kind else {
+            panic!("Expected an if expression");
+        };
+        assert_eq!(&source[condition.span.clone()], "a == B");
+        let ExprKind::While { condition, .. } = &function.body.tail.as_ref().unwrap().kind else {
+            panic!("Expected a while loop");
+        };
+        assert_eq!(&source[condition.span.clone()], "(D {}).e");
+    }
+
+    #[test]
+    fn test_parse_enum() {
+        let source = "enum Shape { Circle(f64), Rect { w: f64, h: f64 }, Empty, } \
+                      fn foo() { Shape::Rect { w: 1.0, h }; Shape::Circle(r, ) }";
+        let top_levels = Parser::with_source(source).parse().into_result().unwrap();
+        let TopLevelKind::Enum(def) = &top_levels[0].kind else {
+            panic!("Expected an enum declaration");
+        };
+        assert_eq!(&source[def.span.clone()], "Shape");
+        let variants: Vec<_> = def
+            .variants
+            .iter()
+            .map(|variant| (variant.name.as_str(), variant.kind, variant.fields.len()))
+            .collect();
+        assert_eq!(
+            variants,
+            [
+                ("Circle", VariantKind::Tuple, 1),
+                ("Rect", VariantKind::Struct, 2),
+                ("Empty", VariantKind::Unit, 0)
+            ]
+        );
+        // The fields of a tuple variant are named by their index
+        assert_eq!(def.variants[0].fields[0].name, "0");
+        assert_eq!(
+            &source[def.variants[1].span.clone()],
+            "Rect { w: f64, h: f64 }"
+        );
+
+        let TopLevelKind::Expr(expr) = &top_levels[1].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        let statement = &function.body.statements[0];
+        let ExprKind::Variant { path, kind, fields } = &statement.kind else {
+            panic!("Expected a variant");
+        };
+        assert_eq!(path.to_string(), "Shape::Rect");
+        assert_eq!(*kind, VariantKind::Struct);
+        assert_eq!(&source[statement.span.clone()], "Shape::Rect { w: 1.0, h }");
+        assert_eq!(fields[1].value.kind, ExprKind::Variable("h".to_string()));
+        let tail = function.body.tail.as_ref().unwrap();
+        let ExprKind::Variant { kind, fields, .. } = &tail.kind else {
+            panic!("Expected a variant");
+        };
+        assert_eq!(*kind, VariantKind::Tuple);
+        assert_eq!(fields[0].name, "0");
+        assert_eq!(&source[tail.span.clone()], "Shape::Circle(r, )");
+    }
+
+    #[test]
+    fn test_parse_match() {
+        let source =
+            "fn foo() { match s { Shape::Circle(r) => r, Shape::Rect { w, h: _ } => { w } \
+                      -1 => 0, _ => 1, } }";
+        let top_levels = Parser::with_source(source).parse().into_result().unwrap();
+        let TopLevelKind::Expr(expr) = &top_levels[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        let ExprKind::Match { scrutinee, arms } = &function.body.tail.as_ref().unwrap().kind else {
+            panic!("Expected a match expression");
+        };
+        assert_eq!(scrutinee.kind, ExprKind::Variable("s".to_string()));
+        // The comma after an arm is optional when its body is a block
+        assert_eq!(arms.len(), 4);
+        assert_eq!(
+            &source[arms[1].span.clone()],
+            "Shape::Rect { w, h: _ } => { w }"
+        );
+        let PatternKind::Variant { kind, fields, .. } = &arms[1].pattern.kind else {
+            panic!("Expected a variant pattern");
+        };
+        assert_eq!(*kind, VariantKind::Struct);
+        // A field without a pattern binds a variable with the same name
+        assert_eq!(
+            fields[0].pattern.kind,
+            PatternKind::Binding {
+                name: "w".to_string(),
+                mutable: false
+            }
+        );
+        assert_eq!(fields[1].pattern.kind, PatternKind::Wildcard);
+        let PatternKind::Literal { negated, .. } = &arms[2].pattern.kind else {
+            panic!("Expected a literal pattern");
+        };
+        assert!(*negated);
+        assert_eq!(&source[arms[2].pattern.span.clone()], "-1");
+    }
+
+    #[test]
+    fn test_parse_unsafe() {
+        let source = "fn foo(p: *mut i32) -> i32 { while true {} *p = 1; unsafe { *p } }";
+        let top_levels = Parser::with_source(source).parse().into_result().unwrap();
+        let TopLevelKind::Expr(expr) = &top_levels[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        // A statement that starts with a block ends after it, instead of continuing as `{} * p`
+        let statements = &function.body.statements;
+        assert!(matches!(statements[0].kind, ExprKind::While { .. }));
+        let ExprKind::Assign { target, .. } = &statements[1].kind else {
+            panic!("Expected an assignment");
+        };
+        assert!(matches!(target.kind, ExprKind::Deref(_)));
+        let tail = function.body.tail.as_ref().unwrap();
+        let ExprKind::Unsafe(block) = &tail.kind else {
+            panic!("Expected an unsafe block");
+        };
+        assert_eq!(&source[tail.span.clone()], "unsafe { *p }");
+        assert!(matches!(
+            block.tail.as_ref().unwrap().kind,
+            ExprKind::Deref(_)
+        ));
+
+        let source = "fn foo(a: &[i32; 2]) {}";
+        let errors = Parser::with_source(source)
+            .parse()
+            .into_result()
+            .unwrap_err();
+        let [ParseError::Unsupported(_, span)] = errors.as_slice() else {
+            panic!("Expected an unsupported error, found {errors:?}");
+        };
+        assert_eq!(&source[span.clone()], "&[i32;");
+    }
+
+    #[test]
+    fn test_parse_strings() {
+        let source = r#"fn foo() { let s: &str = "a\tb"; let b = b"\x00"; }"#;
+        let top_levels = Parser::with_source(source).parse().into_result().unwrap();
+        let TopLevelKind::Expr(expr) = &top_levels[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        let statements = &function.body.statements;
+        let ExprKind::Let(binding) = &statements[0].kind else {
+            panic!("Expected a let binding");
+        };
+        assert_eq!(binding.ty.as_ref().unwrap().value, Type::Str);
+        let value = &binding.value;
+        assert_eq!(
+            value.kind,
+            ExprKind::Literal(Literal::Str("a\tb".to_string()))
+        );
+        assert_eq!(&source[value.span.clone()], r#""a\tb""#);
+        let ExprKind::Let(binding) = &statements[1].kind else {
+            panic!("Expected a let binding");
+        };
+        assert_eq!(
+            binding.value.kind,
+            ExprKind::Literal(Literal::ByteStr(vec![0]))
+        );
+
+        for (source, unsupported) in [
+            ("fn foo(a: &mut str) {}", "&mut str"),
+            ("fn foo(a: str) {}", "str"),
+        ] {
+            let errors = Parser::with_source(source)
+                .parse()
+                .into_result()
+                .unwrap_err();
+            let [ParseError::Unsupported(_, span)] = errors.as_slice() else {
+                panic!("Expected an unsupported error, found {errors:?}");
+            };
+            assert_eq!(&source[span.clone()], unsupported);
+        }
+    }
+
+    #[test]
+    fn test_parse_loops() {
+        let source = "fn foo() { 'outer: while a { loop { break 'outer; } continue } x = loop { break 1; }; }";
+        let top_levels = Parser::with_source(source).parse().into_result().unwrap();
+        let TopLevelKind::Expr(expr) = &top_levels[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        let statements = &function.body.statements;
+        assert_eq!(statements.len(), 2);
+        assert_eq!(
+            &source[statements[0].span.clone()],
+            "'outer: while a { loop { break 'outer; } continue }"
+        );
+        let ExprKind::While { label, body, .. } = &statements[0].kind else {
+            panic!("Expected a while loop");
+        };
+        assert_eq!(label.as_deref(), Some("outer"));
+        assert_eq!(
+            body.tail.as_deref().map(|tail| &tail.kind),
+            Some(&ExprKind::Continue { label: None })
+        );
+        let ExprKind::Loop { label: None, body } = &body.statements[0].kind else {
+            panic!("Expected a loop");
+        };
+        assert_eq!(
+            body.statements[0].kind,
+            ExprKind::Break {
+                label: Some("outer".to_string()),
+                value: None
+            }
+        );
+        let ExprKind::Assign { value, .. } = &statements[1].kind else {
+            panic!("Expected an assignment");
+        };
+        let ExprKind::Loop { body, .. } = &value.kind else {
+            panic!("Expected a loop");
+        };
+        assert_eq!(&source[body.statements[0].span.clone()], "break 1");
+    }
+
+    /// Prints an expression with every operator in parentheses, to check the precedence
+    fn parenthesize(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Variable(name) => name.clone(),
+            ExprKind::Literal(Literal::Number(num)) => num.value.to_string(),
+            ExprKind::Literal(Literal::Bool(value)) => value.to_string(),
+            ExprKind::Binary { op, lhs, rhs } => {
+                format!("({} {op} {})", parenthesize(lhs), parenthesize(rhs))
+            }
+            ExprKind::Unary { op, expr } => format!("({op}{})", parenthesize(expr)),
+            ExprKind::Cast { expr, ty } => format!("({} as {})", parenthesize(expr), ty.value),
+            ExprKind::Field { expr, field } => format!("{}.{}", parenthesize(expr), field.value),
+            ExprKind::Tuple(elements) => {
+                let elements: Vec<_> = elements.iter().map(parenthesize).collect();
+                format!("({},)", elements.join(", "))
+            }
+            ExprKind::Assign { op, target, value } => {
+                let op = op.map(|op| op.to_string()).unwrap_or_default();
+                format!("({} {op}= {})", parenthesize(target), parenthesize(value))
+            }
+            ExprKind::Call { callee, arguments } => {
+                let arguments: Vec<_> = arguments.iter().map(parenthesize).collect();
+                format!("{}({})", parenthesize(callee), arguments.join(", "))
+            }
+            ExprKind::Array(elements) => {
+                let elements: Vec<_> = elements.iter().map(parenthesize).collect();
+                format!("[{}]", elements.join(", "))
+            }
+            ExprKind::ArrayRepeat { value, count } => format!("[{}; {count}]", parenthesize(value)),
+            ExprKind::Index { expr, index } => {
+                format!("{}[{}]", parenthesize(expr), parenthesize(index))
+            }
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                arguments,
+            } => {
+                let arguments: Vec<_> = arguments.iter().map(parenthesize).collect();
+                let receiver = parenthesize(receiver);
+                format!("{receiver}.{}({})", method.value, arguments.join(", "))
+            }
+            ExprKind::Ref { mutable, expr } => {
+                let mutable = if *mutable { "mut " } else { "" };
+                format!("(&{mutable}{})", parenthesize(expr))
+            }
+            ExprKind::Deref(expr) => format!("(*{})", parenthesize(expr)),
+            kind => panic!("Unexpected expression {kind:?}"),
+        }
+    }
+
+    #[rstest::rstest]
+    #[case("a + b * c", "(a + (b * c))")]
+    #[case("a * b + c", "((a * b) + c)")]
+    #[case("a - b - c", "((a - b) - c)")]
+    #[case("(a - b) * c", "((a - b) * c)")]
+    #[case("-a * -b", "((-a) * (-b))")]
+    #[case("!a == b", "((!a) == b)")]
+    #[case("a & b << 1 | c ^ d", "((a & (b << 1)) | (c ^ d))")]
+    #[case("a < b && c >= d || e", "(((a < b) && (c >= d)) || e)")]
+    #[case("!a == false || true", "(((!a) == false) || true)")]
+    #[case("x = a + b", "(x = (a + b))")]
+    #[case("x <<= a || b", "(x <<= (a || b))")]
+    #[case("-f(a, b + 1) * g()", "((-f(a, (b + 1))) * g())")]
+    #[case("f(a,)(b)", "f(a)(b)")]
+    #[case("-a as u8 * b", "(((-a) as u8) * b)")]
+    #[case("a + b as i64 as f32", "(a + ((b as i64) as f32))")]
+    #[case("f(a) as bool == c", "((f(a) as bool) == c)")]
+    #[case("-a.b.c * f(d).e", "((-a.b.c) * f(d).e)")]
+    #[case("(a, (b), (c,), ())", "(a, b, (c,), (,),)")]
+    #[case("t.0.1 + t.2", "(t.0.1 + t.2)")]
+    #[case("-a[i + 1][0]", "(-a[(i + 1)][0])")]
+    #[case("[a, [b; 2], []][0]", "[a, [b; 2], []][0]")]
+    #[case("&a.b[1].len() + 1", "((&a.b[1].len()) + 1)")]
+    #[case("&mut s.f.len()", "(&mut s.f.len())")]
+    #[case("*p * **q", "((*p) * (*(*q)))")]
+    #[case("&&-a", "(&(&(-a)))")]
+    #[case("*p.f = &mut *q", "((*p.f) = (&mut (*q)))")]
+    fn test_parse_precedence(#[case] input: &str, #[case] expected: &str) {
+        let source = format!("fn foo() {{ {input}; }}");
+        let top_levels = Parser::with_source(&source).parse().into_result().unwrap();
+        let TopLevelKind::Expr(expr) = &top_levels[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        let statement = &function.body.statements[0];
+        assert_eq!(parenthesize(statement), expected);
+        assert_eq!(&source[statement.span.clone()], input);
+    }
 }
@@ -0,0 +1,687 @@
+//! Checks that references don't outlive the local variables they borrow, and that a variable isn't
+//! used in a way that conflicts with a borrow of it while the borrow is still in use.
+//!
+//! A borrow is held by the variables and temporaries that its reference flows into, and it lasts
+//! until the last use of a variable that holds it. Uses are ordered by their position in the
+//! source, and a variable that is used in a loop it was declared outside of stays live until the
+//! end of the loop, since the next iteration can use it again. This is coarser than the borrow
+//! checker in Rust, but it only needs the AST and doesn't look into other functions: the reference
+//! returned by a call is assumed to borrow from every argument.
+
+use crate::{TypeCheckError, TypeChecker};
+use redox_ast::{Block, Expr, ExprKind, FunctionDef, Pattern, PatternKind, Span, Type};
+use std::collections::{HashMap, HashSet};
+use tracing::instrument;
+
+/// Local variables are identified by where they are declared, since names can be shadowed
+type BindingId = usize;
+
+/// A local variable with its name
+type Binding = (BindingId, String);
+
+/// How a variable is used, in the order of the restrictions a borrow puts on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Reading the value, which is only prevented by a mutable borrow
+    Read,
+    /// Assigning to the variable or a part of it, which is prevented by any borrow
+    Write,
+    /// Borrowing the variable or a part of it
+    Borrow { mutable: bool },
+}
+
+/// A borrow of a local variable
+#[derive(Debug, Clone, PartialEq)]
+struct Loan {
+    binding: BindingId,
+    name: String,
+    mutable: bool,
+    /// The `&` expression that made the borrow
+    span: Span,
+}
+
+/// The last use of a variable
+#[derive(Debug, Clone)]
+struct LastUse {
+    span: Span,
+    /// The end of the range where the variable is live, which is after the end of the use if it
+    /// is in a loop
+    live_until: usize,
+}
+
+/// Finds the binding that every use of a variable refers to, and where each binding is last used
+#[derive(Default)]
+struct Liveness {
+    /// The binding of each use of a variable, by the start of the use
+    bindings: HashMap<usize, BindingId>,
+    last_uses: HashMap<BindingId, LastUse>,
+    /// The bindings that are in scope, the innermost scope is last
+    scopes: Vec<HashMap<String, BindingId>>,
+    /// The loops that enclose the current expression, the innermost loop is last
+    loops: Vec<Span>,
+}
+
+impl Liveness {
+    fn declare(&mut self, pattern: &Pattern) {
+        let mut bindings = Vec::new();
+        pattern_bindings(pattern, &mut bindings);
+        let scope = self.scopes.last_mut().expect("There is always a scope");
+        for (id, name) in bindings {
+            scope.insert(name, id);
+        }
+    }
+
+    /// Finds the binding that a variable refers to
+    fn resolve(&mut self, name: &str, span: &Span) -> Option<BindingId> {
+        // Functions are called by name, but aren't local variables
+        let id = *self.scopes.iter().rev().find_map(|scope| scope.get(name))?;
+        self.bindings.insert(span.start, id);
+        Some(id)
+    }
+
+    fn use_variable(&mut self, name: &str, span: &Span) {
+        let Some(id) = self.resolve(name, span) else {
+            return;
+        };
+        // The outermost loop that the variable is declared outside of runs the use again
+        let live_until = self
+            .loops
+            .iter()
+            .find(|body| body.start > id)
+            .map_or(span.end, |body| body.end);
+        let last_use = self.last_uses.entry(id).or_insert(LastUse {
+            span: span.clone(),
+            live_until,
+        });
+        if span.start >= last_use.span.start {
+            last_use.span = span.clone();
+        }
+        last_use.live_until = last_use.live_until.max(live_until);
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        self.scopes.push(HashMap::new());
+        for expr in block.expressions() {
+            self.visit(expr);
+        }
+        self.scopes.pop();
+    }
+
+    /// Visits the target of an assignment, which doesn't use the variable it assigns to
+    fn visit_place(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Variable(name) => {
+                self.resolve(name, &expr.span);
+            }
+            ExprKind::Field { expr, .. } => self.visit_place(expr),
+            ExprKind::Index { expr, index } => {
+                self.visit_place(expr);
+                self.visit(index);
+            }
+            _ => self.visit(expr),
+        }
+    }
+
+    fn visit(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Literal(_) | ExprKind::Continue { .. } | ExprKind::FunctionDef(_) => (),
+            ExprKind::Variable(name) => self.use_variable(name, &expr.span),
+            ExprKind::Let(binding) => {
+                self.visit(&binding.value);
+                self.declare(&binding.pattern);
+            }
+            ExprKind::Assign { op, target, value } => {
+                self.visit(value);
+                // A compound assignment reads the target before writing to it
+                match op {
+                    Some(_) => self.visit(target),
+                    None => self.visit_place(target),
+                }
+            }
+            ExprKind::Block(block) | ExprKind::Unsafe(block) => self.visit_block(block),
+            ExprKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.visit(condition);
+                self.visit_block(then_block);
+                if let Some(else_block) = else_block {
+                    self.visit_block(else_block);
+                }
+            }
+            ExprKind::While {
+                condition, body, ..
+            } => {
+                self.loops.push(expr.span.clone());
+                self.visit(condition);
+                self.visit_block(body);
+                self.loops.pop();
+            }
+            ExprKind::Loop { body, .. } => {
+                self.loops.push(expr.span.clone());
+                self.visit_block(body);
+                self.loops.pop();
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                self.visit(scrutinee);
+                for arm in arms {
+                    self.scopes.push(HashMap::new());
+                    self.declare(&arm.pattern);
+                    self.visit(&arm.body);
+                    self.scopes.pop();
+                }
+            }
+            ExprKind::Return(value) | ExprKind::Break { value, .. } => {
+                if let Some(value) = value {
+                    self.visit(value);
+                }
+            }
+            ExprKind::Call { arguments, .. } => arguments.iter().for_each(|arg| self.visit(arg)),
+            ExprKind::MethodCall {
+                receiver,
+                arguments,
+                ..
+            } => {
+                self.visit(receiver);
+                arguments.iter().for_each(|arg| self.visit(arg));
+            }
+            ExprKind::StructLiteral { fields, .. } | ExprKind::Variant { fields, .. } => {
+                fields.iter().for_each(|field| self.visit(&field.value));
+            }
+            ExprKind::Tuple(values) | ExprKind::Array(values) => {
+                values.iter().for_each(|value| self.visit(value));
+            }
+            ExprKind::Binary { lhs, rhs, .. } => {
+                self.visit(lhs);
+                self.visit(rhs);
+            }
+            ExprKind::Index { expr, index } => {
+                self.visit(expr);
+                self.visit(index);
+            }
+            ExprKind::Unary { expr, .. }
+            | ExprKind::Cast { expr, .. }
+            | ExprKind::Field { expr, .. }
+            | ExprKind::ArrayRepeat { value: expr, .. }
+            | ExprKind::Ref { expr, .. }
+            | ExprKind::Deref(expr) => self.visit(expr),
+        }
+    }
+}
+
+/// Collects the variables that a pattern declares
+fn pattern_bindings(pattern: &Pattern, bindings: &mut Vec<Binding>) {
+    match &pattern.kind {
+        PatternKind::Binding { name, .. } => bindings.push((pattern.span.start, name.clone())),
+        PatternKind::Tuple(patterns) => {
+            for pattern in patterns {
+                pattern_bindings(pattern, bindings);
+            }
+        }
+        PatternKind::Variant { fields, .. } => {
+            for field in fields {
+                pattern_bindings(&field.pattern, bindings);
+            }
+        }
+        PatternKind::Wildcard | PatternKind::Literal { .. } => (),
+    }
+}
+
+/// Adds loans to a set of loans, without duplicates
+fn merge(loans: &mut Vec<Loan>, other: impl IntoIterator<Item = Loan>) {
+    for loan in other {
+        if !loans.contains(&loan) {
+            loans.push(loan);
+        }
+    }
+}
+
+/// A loop that `break` can carry borrows out of
+struct LoopLoans {
+    label: Option<String>,
+    /// The borrows held by the values the loop is exited with
+    breaks: Vec<Loan>,
+}
+
+struct BorrowChecker<'a> {
+    checker: &'a TypeChecker,
+    liveness: Liveness,
+    /// The borrows that each variable in scope might hold
+    held: HashMap<BindingId, Vec<Loan>>,
+    /// The borrows held by values that are evaluated but not used yet, like the earlier arguments
+    /// of a call, with the expression that uses them
+    temporaries: Vec<(Loan, Span)>,
+    loops: Vec<LoopLoans>,
+}
+
+impl BorrowChecker<'_> {
+    fn is_live(&self, id: BindingId, position: usize) -> bool {
+        self.liveness
+            .last_uses
+            .get(&id)
+            .is_some_and(|last_use| last_use.live_until > position)
+    }
+
+    /// Checks that a use of a variable doesn't conflict with a borrow that is still in use
+    fn check_access(
+        &self,
+        id: BindingId,
+        name: &str,
+        access: Access,
+        span: &Span,
+    ) -> Result<(), TypeCheckError> {
+        let held = self
+            .held
+            .iter()
+            .filter(|(holder, _)| self.is_live(**holder, span.start))
+            .flat_map(|(holder, loans)| {
+                let later = &self.liveness.last_uses[holder].span;
+                loans.iter().map(move |loan| (loan, later))
+            });
+        let temporaries = self.temporaries.iter().map(|(loan, later)| (loan, later));
+        for (loan, later) in held.chain(temporaries) {
+            let conflicts = match access {
+                Access::Read => loan.mutable,
+                Access::Write => true,
+                Access::Borrow { mutable } => mutable || loan.mutable,
+            };
+            if loan.binding == id && conflicts {
+                return Err(TypeCheckError::BorrowConflict {
+                    name: name.to_string(),
+                    access,
+                    span: span.clone(),
+                    borrow: loan.span.clone(),
+                    borrow_mutable: loan.mutable,
+                    later: later.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the scope of some variables, and checks that nothing that outlives them still borrows
+    /// them. `value` is the borrows held by the value of the scope, and `end` is where it ends
+    fn drop_bindings(
+        &mut self,
+        bindings: &[Binding],
+        value: &[Loan],
+        end: Span,
+    ) -> Result<(), TypeCheckError> {
+        for (id, _) in bindings {
+            self.held.remove(id);
+        }
+        let held = self
+            .held
+            .iter()
+            .filter(|(holder, _)| self.is_live(**holder, end.start))
+            .flat_map(|(holder, loans)| {
+                let later = &self.liveness.last_uses[holder].span;
+                loans.iter().map(move |loan| (loan, Some(later.clone())))
+            });
+        let escaping = value
+            .iter()
+            .chain(self.loops.iter().flat_map(|loop_loans| &loop_loans.breaks))
+            .map(|loan| (loan, None));
+        for (loan, later) in held.chain(escaping) {
+            if bindings.iter().any(|(id, _)| *id == loan.binding) {
+                return Err(TypeCheckError::BorrowOutlivesValue {
+                    name: loan.name.clone(),
+                    borrow: loan.span.clone(),
+                    dropped: end,
+                    later,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn check_return(&self, value: &[Loan], span: &Span) -> Result<(), TypeCheckError> {
+        match value.first() {
+            // Every borrow is of a local variable, which doesn't exist after the function returns
+            Some(loan) => Err(TypeCheckError::ReturnLocalReference {
+                name: loan.name.clone(),
+                borrow: loan.span.clone(),
+                span: span.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the statements of a block, and returns the variables it declares and the borrows
+    /// held by its value, without ending the scope of the variables
+    fn block_contents(
+        &mut self,
+        block: &Block,
+    ) -> Result<(Vec<Binding>, Vec<Loan>), TypeCheckError> {
+        let mut bindings = Vec::new();
+        for statement in &block.statements {
+            self.expr(statement)?;
+            if let ExprKind::Let(binding) = &statement.kind {
+                pattern_bindings(&binding.pattern, &mut bindings);
+            }
+        }
+        let value = match &block.tail {
+            Some(tail) => self.expr(tail)?,
+            None => Vec::new(),
+        };
+        Ok((bindings, value))
+    }
+
+    fn block(&mut self, block: &Block) -> Result<Vec<Loan>, TypeCheckError> {
+        let (bindings, value) = self.block_contents(block)?;
+        let end = block.span.end;
+        self.drop_bindings(&bindings, &value, end - 1..end)?;
+        Ok(value)
+    }
+
+    /// Evaluates values that are all used by the expression `user`, the borrows of the earlier
+    /// values are held while the later ones are evaluated
+    fn values<'e>(
+        &mut self,
+        values: impl IntoIterator<Item = &'e Expr>,
+        user: &Span,
+    ) -> Result<Vec<Loan>, TypeCheckError> {
+        let len = self.temporaries.len();
+        let mut loans = Vec::new();
+        for value in values {
+            let value = self.expr(value)?;
+            self.temporaries
+                .extend(value.iter().map(|loan| (loan.clone(), user.clone())));
+            merge(&mut loans, value);
+        }
+        self.temporaries.truncate(len);
+        Ok(loans)
+    }
+
+    /// Checks the body of a loop twice, the second time with the borrows that the end of the
+    /// first iteration holds, since they are still held at the start of the next one
+    fn loop_body(
+        &mut self,
+        label: &Option<String>,
+        condition: Option<&Expr>,
+        body: &Block,
+    ) -> Result<Vec<Loan>, TypeCheckError> {
+        self.loops.push(LoopLoans {
+            label: label.clone(),
+            breaks: Vec::new(),
+        });
+        for _ in 0..2 {
+            let start = self.held.clone();
+            if let Some(condition) = condition {
+                self.expr(condition)?;
+            }
+            self.block(body)?;
+            self.merge_held(start);
+        }
+        Ok(self.loops.pop().unwrap().breaks)
+    }
+
+    /// Joins the borrows held after another branch of control flow with the current ones
+    fn merge_held(&mut self, other: HashMap<BindingId, Vec<Loan>>) {
+        for (id, loans) in other {
+            merge(self.held.entry(id).or_default(), loans);
+        }
+    }
+
+    fn declare(&mut self, pattern: &Pattern, loans: &[Loan]) {
+        let mut bindings = Vec::new();
+        pattern_bindings(pattern, &mut bindings);
+        for (id, _) in bindings {
+            self.held.insert(id, loans.to_vec());
+        }
+    }
+
+    /// Checks a place expression that is accessed, and returns the variable it is a part of if it
+    /// isn't behind a reference, and the borrows held by the value in the place
+    fn place(
+        &mut self,
+        expr: &Expr,
+        access: Access,
+    ) -> Result<(Option<Binding>, Vec<Loan>), TypeCheckError> {
+        match &expr.kind {
+            ExprKind::Variable(name) => {
+                let Some(&id) = self.liveness.bindings.get(&expr.span.start) else {
+                    return Ok((None, Vec::new()));
+                };
+                self.check_access(id, name, access, &expr.span)?;
+                let loans = self.held.get(&id).cloned().unwrap_or_default();
+                Ok((Some((id, name.clone())), loans))
+            }
+            ExprKind::Field { expr, .. } => self.place(expr, access),
+            ExprKind::Index { expr: base, index } => {
+                let place = match base.ty.as_ref().unwrap() {
+                    Type::Slice { .. } => (None, self.expr(base)?),
+                    _ => self.place(base, access)?,
+                };
+                self.expr(index)?;
+                Ok(place)
+            }
+            // The value behind a reference belongs to what the reference borrows
+            ExprKind::Deref(expr) => Ok((None, self.expr(expr)?)),
+            _ => Ok((None, self.expr(expr)?)),
+        }
+    }
+
+    /// Checks an expression, and returns the borrows that its value might hold
+    fn expr(&mut self, expr: &Expr) -> Result<Vec<Loan>, TypeCheckError> {
+        let loans = self.expr_loans(expr)?;
+        // Values that can't contain references don't hold on to the borrows they were made from
+        match &expr.ty {
+            Some(ty) if self.checker.holds_references(ty, &mut HashSet::new()) => Ok(loans),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn expr_loans(&mut self, expr: &Expr) -> Result<Vec<Loan>, TypeCheckError> {
+        match &expr.kind {
+            ExprKind::Literal(_) | ExprKind::Continue { .. } | ExprKind::FunctionDef(_) => {
+                Ok(Vec::new())
+            }
+            ExprKind::Variable(_)
+            | ExprKind::Field { .. }
+            | ExprKind::Index { .. }
+            | ExprKind::Deref(_) => Ok(self.place(expr, Access::Read)?.1),
+            ExprKind::Ref {
+                mutable,
+                expr: operand,
+            } => {
+                let access = Access::Borrow { mutable: *mutable };
+                // A borrow that conflicts points at the whole `&` expression, not just the variable
+                let (root, mut loans) = self.place(operand, access).map_err(|err| match err {
+                    TypeCheckError::BorrowConflict {
+                        name,
+                        access: conflict,
+                        borrow,
+                        borrow_mutable,
+                        later,
+                        ..
+                    } if conflict == access => TypeCheckError::BorrowConflict {
+                        name,
+                        access,
+                        span: expr.span.clone(),
+                        borrow,
+                        borrow_mutable,
+                        later,
+                    },
+                    err => err,
+                })?;
+                // Only borrows of local variables are checked, the value behind a reference
+                // keeps the borrows that the reference holds
+                if let Some((binding, name)) = root {
+                    let loan = Loan {
+                        binding,
+                        name,
+                        mutable: *mutable,
+                        span: expr.span.clone(),
+                    };
+                    merge(&mut loans, [loan]);
+                }
+                Ok(loans)
+            }
+            ExprKind::Return(value) => {
+                if let Some(value) = value {
+                    let loans = self.expr(value)?;
+                    self.check_return(&loans, &value.span)?;
+                }
+                Ok(Vec::new())
+            }
+            ExprKind::Let(binding) => {
+                let loans = self.expr(&binding.value)?;
+                self.declare(&binding.pattern, &loans);
+                Ok(Vec::new())
+            }
+            ExprKind::Assign { op, target, value } => {
+                let loans = self.expr(value)?;
+                // A compound assignment also reads the target, which conflicts with fewer borrows
+                // than writing to it
+                let (root, _) = self.place(target, Access::Write)?;
+                if let Some((id, _)) = root {
+                    // Assigning to a part of a variable keeps the borrows held by the other parts
+                    if op.is_none() && matches!(target.kind, ExprKind::Variable(_)) {
+                        self.held.insert(id, loans);
+                    } else {
+                        merge(self.held.entry(id).or_default(), loans);
+                    }
+                }
+                Ok(Vec::new())
+            }
+            ExprKind::Block(block) | ExprKind::Unsafe(block) => self.block(block),
+            ExprKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.expr(condition)?;
+                let start = self.held.clone();
+                let mut loans = self.block(then_block)?;
+                if let Some(else_block) = else_block {
+                    let then_held = std::mem::replace(&mut self.held, start);
+                    merge(&mut loans, self.block(else_block)?);
+                    self.merge_held(then_held);
+                } else {
+                    self.merge_held(start);
+                }
+                Ok(loans)
+            }
+            ExprKind::Match { scrutinee, arms } => {
+                let scrutinee = self.expr(scrutinee)?;
+                let start = self.held.clone();
+                let mut held = HashMap::new();
+                let mut loans = Vec::new();
+                for arm in arms {
+                    self.held = start.clone();
+                    self.declare(&arm.pattern, &scrutinee);
+                    let value = self.expr(&arm.body)?;
+                    let mut bindings = Vec::new();
+                    pattern_bindings(&arm.pattern, &mut bindings);
+                    let end = arm.span.end;
+                    self.drop_bindings(&bindings, &value, end - 1..end)?;
+                    merge(&mut loans, value);
+                    for (id, arm_loans) in std::mem::take(&mut self.held) {
+                        merge(held.entry(id).or_insert_with(Vec::new), arm_loans);
+                    }
+                }
+                self.held = start;
+                self.merge_held(held);
+                Ok(loans)
+            }
+            ExprKind::While {
+                label,
+                condition,
+                body,
+            } => {
+                self.loop_body(label, Some(condition), body)?;
+                Ok(Vec::new())
+            }
+            ExprKind::Loop { label, body } => self.loop_body(label, None, body),
+            ExprKind::Break { label, value } => {
+                if let Some(value) = value {
+                    let loans = self.expr(value)?;
+                    let loop_loans = match label {
+                        Some(label) => self
+                            .loops
+                            .iter_mut()
+                            .rfind(|loop_loans| loop_loans.label.as_ref() == Some(label)),
+                        None => self.loops.last_mut(),
+                    };
+                    merge(&mut loop_loans.unwrap().breaks, loans);
+                }
+                Ok(Vec::new())
+            }
+            // The reference returned by a function can only borrow from the arguments
+            ExprKind::Call { arguments, .. } => self.values(arguments, &expr.span),
+            ExprKind::MethodCall {
+                receiver,
+                arguments,
+                ..
+            } => {
+                self.place(receiver, Access::Read)?;
+                self.values(arguments, &expr.span)?;
+                Ok(Vec::new())
+            }
+            ExprKind::StructLiteral { fields, .. } | ExprKind::Variant { fields, .. } => {
+                self.values(fields.iter().map(|field| &field.value), &expr.span)
+            }
+            ExprKind::Tuple(values) | ExprKind::Array(values) => self.values(values, &expr.span),
+            ExprKind::Binary { lhs, rhs, .. } => {
+                self.values([&**lhs, &**rhs], &expr.span)?;
+                Ok(Vec::new())
+            }
+            ExprKind::Unary { expr, .. }
+            | ExprKind::Cast { expr, .. }
+            | ExprKind::ArrayRepeat { value: expr, .. } => self.expr(expr),
+        }
+    }
+}
+
+impl TypeChecker {
+    /// Checks the borrows in the body of a function, after the types of every expression are
+    /// resolved
+    #[instrument(skip(self, function))]
+    pub(super) fn check_borrows(&self, function: &FunctionDef) -> Result<(), TypeCheckError> {
+        tracing::trace!("Checking borrows in {}", function.name);
+        let mut liveness = Liveness::default();
+        let arguments = function
+            .arguments
+            .iter()
+            .map(|arg| (arg.name.clone(), arg.span.start))
+            .collect();
+        liveness.scopes.push(arguments);
+        liveness.visit_block(&function.body);
+        let mut checker = BorrowChecker {
+            checker: self,
+            liveness,
+            held: HashMap::new(),
+            temporaries: Vec::new(),
+            loops: Vec::new(),
+        };
+        let (_, value) = checker.block_contents(&function.body)?;
+        checker.check_return(&value, &Self::value_span(&function.body))
+    }
+
+    /// Checks if a value of a type can contain references, which keep the borrows they were made
+    /// from. Raw pointers aren't checked
+    fn holds_references(&self, ty: &Type, visited: &mut HashSet<String>) -> bool {
+        match ty {
+            Type::Ref { .. } | Type::Slice { .. } => true,
+            Type::Named(name) if visited.insert(name.clone()) => {
+                let fields: Vec<&Type> = match (self.structs.get(name), self.enums.get(name)) {
+                    (Some(struct_ty), _) => struct_ty.fields.iter().map(|(_, ty)| ty).collect(),
+                    (_, Some(enum_ty)) => enum_ty
+                        .variants
+                        .iter()
+                        .flat_map(|variant| variant.fields.iter().map(|(_, ty)| ty))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                fields
+                    .into_iter()
+                    .any(|ty| self.holds_references(ty, visited))
+            }
+            Type::Tuple(types) => types.iter().any(|ty| self.holds_references(ty, visited)),
+            Type::Array(ty, _) => self.holds_references(ty, visited),
+            _ => false,
+        }
+    }
+}
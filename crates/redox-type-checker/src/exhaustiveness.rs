@@ -0,0 +1,261 @@
+//! Checks that the arms of a `match` cover every value of the scrutinee, and that every arm can be
+//! reached. This uses the usefulness algorithm from "Warnings for pattern matching" (Maranget,
+//! 2007): a pattern is useful after some rows of patterns if there is a value that it matches and
+//! none of the rows do. An arm is unreachable if its pattern isn't useful after the earlier arms,
+//! and a `match` is exhaustive if `_` isn't useful after all of its arms.
+
+use crate::{TypeCheckError, TypeCheckWarning, TypeChecker};
+use redox_ast::{Literal, MatchArm, NumberValue, Pattern, PatternKind, Span, Type, VariantKind};
+use tracing::instrument;
+
+/// A pattern reduced to what matters for matching: which constructor a value is built with, and
+/// the patterns for its fields
+#[derive(Debug, Clone)]
+enum Pat {
+    /// Matches any value, like a binding or `_`
+    Wild,
+    Ctor(Ctor, Vec<Pat>),
+}
+
+/// The ways to build a value of a type
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Ctor {
+    /// The only constructor of a tuple, its fields are the elements
+    Tuple,
+    Bool(bool),
+    /// A number, with negative numbers stored as their two's complement. Numbers have too many
+    /// constructors to list, so only `_` covers them
+    Int(u128),
+    /// The variant of an enum with this index
+    Variant(usize),
+}
+
+impl TypeChecker {
+    /// Checks that the arms of a `match` on a value of type `ty` match every value, and warns about
+    /// the arms that can't be reached. `span` is the scrutinee
+    #[instrument(skip(self, arms))]
+    pub(super) fn check_match(
+        &mut self,
+        ty: &Type,
+        arms: &[MatchArm],
+        span: &Span,
+    ) -> Result<(), TypeCheckError> {
+        // The scrutinee never produces a value, so no arm runs
+        if *ty == Type::Never {
+            return Ok(());
+        }
+        let types = [ty.clone()];
+        let mut rows = Vec::new();
+        for arm in arms {
+            let row = vec![self.lower(&arm.pattern)];
+            if self.useful(&rows, &row, &types).is_none() {
+                let warning = TypeCheckWarning::UnreachablePattern(arm.pattern.span.clone());
+                self.warnings.push(warning);
+            }
+            rows.push(row);
+        }
+        match self.useful(&rows, &[Pat::Wild], &types) {
+            Some(witness) => Err(TypeCheckError::NonExhaustive {
+                missing: self.describe(&witness[0], ty),
+                span: span.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks that the pattern of a `let` matches every value of type `ty`
+    pub(super) fn check_irrefutable(
+        &self,
+        pattern: &Pattern,
+        ty: &Type,
+    ) -> Result<(), TypeCheckError> {
+        if *ty == Type::Never {
+            return Ok(());
+        }
+        let types = [ty.clone()];
+        match self.useful(&[vec![self.lower(pattern)]], &[Pat::Wild], &types) {
+            Some(witness) => Err(TypeCheckError::RefutablePattern {
+                missing: self.describe(&witness[0], ty),
+                span: pattern.span.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn lower(&self, pattern: &Pattern) -> Pat {
+        match &pattern.kind {
+            PatternKind::Binding { .. } | PatternKind::Wildcard => Pat::Wild,
+            PatternKind::Tuple(elements) => Pat::Ctor(
+                Ctor::Tuple,
+                elements.iter().map(|element| self.lower(element)).collect(),
+            ),
+            PatternKind::Literal { value, negated } => {
+                let ctor = match value {
+                    Literal::Bool(value) => Ctor::Bool(*value),
+                    Literal::Number(number) => match number.value {
+                        NumberValue::Int(value) if *negated => Ctor::Int(value.wrapping_neg()),
+                        NumberValue::Int(value) => Ctor::Int(value),
+                        // Float patterns are rejected when they are bound
+                        NumberValue::Float(_) => return Pat::Wild,
+                    },
+                    Literal::Str(_) | Literal::ByteStr(_) => {
+                        unreachable!("string literal patterns aren't parsed")
+                    }
+                };
+                Pat::Ctor(ctor, Vec::new())
+            }
+            PatternKind::Variant { path, fields, .. } => {
+                let (index, variant) = self.find_variant(path).unwrap();
+                // The fields are put in the order they are declared, and the fields that aren't
+                // written match anything
+                let fields = variant
+                    .fields
+                    .iter()
+                    .map(|(name, _)| {
+                        fields
+                            .iter()
+                            .find(|field| field.name == *name)
+                            .map_or(Pat::Wild, |field| self.lower(&field.pattern))
+                    })
+                    .collect();
+                Pat::Ctor(Ctor::Variant(index), fields)
+            }
+        }
+    }
+
+    /// Gets every constructor of a type, or `None` if there are too many to list
+    fn constructors(&self, ty: &Type) -> Option<Vec<Ctor>> {
+        match ty {
+            Type::Tuple(_) => Some(vec![Ctor::Tuple]),
+            Type::Bool => Some(vec![Ctor::Bool(false), Ctor::Bool(true)]),
+            Type::Named(name) => self
+                .enums
+                .get(name)
+                .map(|enum_ty| (0..enum_ty.variants.len()).map(Ctor::Variant).collect()),
+            _ => None,
+        }
+    }
+
+    /// Gets the types of the fields of a value built with a constructor
+    fn field_types(&self, ty: &Type, ctor: Ctor) -> Vec<Type> {
+        match (ty, ctor) {
+            (Type::Tuple(types), Ctor::Tuple) => types.clone(),
+            (Type::Named(name), Ctor::Variant(index)) => self.enums[name].variants[index]
+                .fields
+                .iter()
+                .map(|(_, ty)| ty.clone())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Finds values that `row` matches and none of the `rows` do, where every row has a pattern
+    /// for each of the `types`. Returns a pattern for each column that describes those values, or
+    /// `None` if `row` isn't useful.
+    fn useful(&self, rows: &[Vec<Pat>], row: &[Pat], types: &[Type]) -> Option<Vec<Pat>> {
+        let Some((first, rest)) = row.split_first() else {
+            // Every column was matched, so the row is only useful if there is no other row
+            return rows.is_empty().then(Vec::new);
+        };
+        let ctors = match first {
+            Pat::Ctor(ctor, _) => vec![*ctor],
+            Pat::Wild => {
+                let used: Vec<Ctor> = rows
+                    .iter()
+                    .filter_map(|row| match &row[0] {
+                        Pat::Ctor(ctor, _) => Some(*ctor),
+                        Pat::Wild => None,
+                    })
+                    .collect();
+                match self.constructors(&types[0]) {
+                    Some(all) if all.iter().all(|ctor| used.contains(ctor)) => all,
+                    // Some values of the first column aren't matched by any constructor in the
+                    // rows, so only the rows that match anything there can match them
+                    all => {
+                        let default: Vec<Vec<Pat>> = rows
+                            .iter()
+                            .filter(|row| matches!(row[0], Pat::Wild))
+                            .map(|row| row[1..].to_vec())
+                            .collect();
+                        let mut witness = self.useful(&default, rest, &types[1..])?;
+                        let missing = all
+                            .and_then(|all| all.into_iter().find(|ctor| !used.contains(ctor)))
+                            .map_or(Pat::Wild, |ctor| {
+                                let arity = self.field_types(&types[0], ctor).len();
+                                Pat::Ctor(ctor, vec![Pat::Wild; arity])
+                            });
+                        witness.insert(0, missing);
+                        return Some(witness);
+                    }
+                }
+            }
+        };
+        for ctor in ctors {
+            let fields = self.field_types(&types[0], ctor);
+            let specialized: Vec<Vec<Pat>> = rows
+                .iter()
+                .filter_map(|row| specialize(row, ctor, fields.len()))
+                .collect();
+            let row = specialize(row, ctor, fields.len()).unwrap();
+            let types: Vec<Type> = fields.iter().chain(&types[1..]).cloned().collect();
+            if let Some(mut witness) = self.useful(&specialized, &row, &types) {
+                let rest = witness.split_off(fields.len());
+                let mut result = vec![Pat::Ctor(ctor, witness)];
+                result.extend(rest);
+                return Some(result);
+            }
+        }
+        None
+    }
+
+    /// Writes a pattern for a value of type `ty` the way it would appear in the source
+    fn describe(&self, pat: &Pat, ty: &Type) -> String {
+        let (ctor, fields) = match pat {
+            Pat::Wild => return "_".to_string(),
+            Pat::Ctor(ctor, fields) => (ctor, fields),
+        };
+        let field_types = self.field_types(ty, *ctor);
+        let described: Vec<String> = fields
+            .iter()
+            .zip(&field_types)
+            .map(|(field, ty)| self.describe(field, ty))
+            .collect();
+        match (ctor, ty) {
+            (Ctor::Tuple, _) if described.len() == 1 => format!("({},)", described[0]),
+            (Ctor::Tuple, _) => format!("({})", described.join(", ")),
+            (Ctor::Bool(value), _) => value.to_string(),
+            (Ctor::Int(value), _) => value.to_string(),
+            (Ctor::Variant(index), Type::Named(name)) => {
+                let variant = &self.enums[name].variants[*index];
+                match variant.kind {
+                    VariantKind::Unit => format!("{name}::{}", variant.name),
+                    VariantKind::Tuple => {
+                        format!("{name}::{}({})", variant.name, described.join(", "))
+                    }
+                    VariantKind::Struct => {
+                        let fields: Vec<String> = variant
+                            .fields
+                            .iter()
+                            .zip(&described)
+                            .map(|((field, _), described)| format!("{field}: {described}"))
+                            .collect();
+                        format!("{name}::{} {{ {} }}", variant.name, fields.join(", "))
+                    }
+                }
+            }
+            (Ctor::Variant(_), _) => unreachable!("variant of a type that isn't an enum"),
+        }
+    }
+}
+
+/// Keeps a row if its first pattern can match a value built with `ctor`, and replaces that pattern
+/// with the patterns for the `arity` fields of the value
+fn specialize(row: &[Pat], ctor: Ctor, arity: usize) -> Option<Vec<Pat>> {
+    let mut specialized = match &row[0] {
+        Pat::Ctor(other, fields) if *other == ctor => fields.clone(),
+        Pat::Ctor(..) => return None,
+        Pat::Wild => vec![Pat::Wild; arity],
+    };
+    specialized.extend_from_slice(&row[1..]);
+    Some(specialized)
+}
@@ -1,37 +1,971 @@
-use redox_ast::{Block, Expr, ExprKind, TopLevel, TopLevelKind, Type};
-use std::collections::HashMap;
+use redox_ast::{
+    BinaryOp, Block, Diagnostic, EnumDef, Expr, ExprKind, FieldInit, InferKind, InferVar, Literal,
+    NumberKind, NumberLiteral, NumberType, NumberValue, Pattern, PatternKind, Span, Spanned,
+    StructDef, StructField, ToDiagnostic, TopLevel, TopLevelKind, Type, UnaryOp, VariantKind,
+    VariantPath,
+};
+use std::collections::{HashMap, HashSet};
 use tracing::instrument;
 
+mod borrowck;
+mod exhaustiveness;
+
+pub use borrowck::Access;
+
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum TypeCheckError {
-    UnableToInferType,
-    IncompatibleTypes { expected: Type, found: Type },
-    UnknownVariable(String),
+    UnableToInferType(Span),
+    IncompatibleTypes {
+        expected: Type,
+        found: Type,
+        span: Span,
+        /// The annotation that requires the expected type, like the return type of a function
+        declared: Option<Span>,
+    },
+    UnknownVariable(String, Span),
+    /// Assigning to a variable that wasn't declared as mutable
+    AssignToImmutable {
+        name: String,
+        span: Span,
+        /// Where the variable was declared
+        declared: Span,
+        argument: bool,
+    },
+    InvalidAssignmentTarget(Span),
+    /// An operator applied to a type that doesn't support it
+    InvalidOperand {
+        op: String,
+        ty: Type,
+        span: Span,
+    },
+    /// A `break` or `continue` that isn't inside of a loop
+    OutsideOfLoop {
+        keyword: String,
+        span: Span,
+    },
+    UnknownLabel(String, Span),
+    /// A `break` with a value inside of a `while` loop, which can't produce a value
+    BreakWithValue(Span),
+    UnknownFunction(String, Span),
+    /// A call with the wrong number of arguments
+    ArgumentCount {
+        expected: usize,
+        found: usize,
+        span: Span,
+        /// The signature of the function that is called, built-in methods don't have one
+        declared: Option<Span>,
+    },
+    /// A function that returns a value, but can reach the end of its body without one
+    MissingReturn {
+        expected: Type,
+        /// The closing brace of the body
+        span: Span,
+        /// The return type of the function
+        declared: Span,
+    },
+    /// Two functions with the same name
+    DuplicateFunction {
+        name: String,
+        span: Span,
+        first: Span,
+    },
+    /// A number literal that doesn't fit in its type
+    LiteralOutOfRange {
+        ty: Type,
+        span: Span,
+    },
+    UnknownType(String, Span),
+    /// Two structs with the same name
+    DuplicateType {
+        name: String,
+        span: Span,
+        first: Span,
+    },
+    /// A field that is declared or initialized twice
+    DuplicateField {
+        name: String,
+        span: Span,
+        first: Span,
+    },
+    UnknownField {
+        ty: Type,
+        field: String,
+        span: Span,
+    },
+    /// A struct literal that doesn't initialize every field
+    MissingFields {
+        name: String,
+        fields: Vec<String>,
+        span: Span,
+    },
+    /// A pattern that can't match a value of the expected type, `found` describes the pattern
+    MismatchedPattern {
+        expected: Type,
+        found: String,
+        span: Span,
+    },
+    /// A variable that is bound twice in the same pattern
+    DuplicateBinding {
+        name: String,
+        span: Span,
+        first: Span,
+    },
+    /// A path to a variant that the enum doesn't have
+    UnknownVariant {
+        enum_name: String,
+        variant: String,
+        span: Span,
+    },
+    /// Two variants with the same name in an enum
+    DuplicateVariant {
+        name: String,
+        span: Span,
+        first: Span,
+    },
+    /// A variant that is written with the fields of another kind of variant, like
+    /// `Shape::Circle { r }` for a tuple variant. `kind` is the kind of the declared variant
+    VariantKindMismatch {
+        name: String,
+        kind: VariantKind,
+        span: Span,
+    },
+    /// A tuple variant, or a pattern for one, with the wrong number of fields
+    FieldCount {
+        name: String,
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    /// A `match` that doesn't cover every value of the scrutinee, `missing` is a pattern for the
+    /// values that aren't covered
+    NonExhaustive {
+        missing: String,
+        span: Span,
+    },
+    /// A `let` with a pattern that doesn't match every value
+    RefutablePattern {
+        missing: String,
+        span: Span,
+    },
+    /// A type that contains itself, which would need an infinite amount of memory
+    RecursiveType {
+        name: String,
+        span: Span,
+    },
+    /// Indexing a value that isn't an array or a slice
+    NotIndexable {
+        ty: Type,
+        span: Span,
+    },
+    UnknownMethod {
+        ty: Type,
+        method: String,
+        span: Span,
+    },
+    /// Borrowing a variable mutably that wasn't declared as mutable
+    BorrowImmutable {
+        name: String,
+        span: Span,
+        /// Where the variable was declared
+        declared: Span,
+    },
+    /// Writing to memory behind a shared reference, slice or `*const` pointer, `ty` is the type
+    /// of the reference
+    WriteThroughImmutable {
+        ty: Type,
+        span: Span,
+    },
+    /// Dereferencing a raw pointer outside of an `unsafe` block
+    UnsafeDeref(Span),
+    /// Using a variable in a way that conflicts with a borrow of it that is used later
+    BorrowConflict {
+        name: String,
+        access: Access,
+        span: Span,
+        /// The `&` expression that borrows the variable
+        borrow: Span,
+        borrow_mutable: bool,
+        /// Where the borrow is used after the conflicting use
+        later: Span,
+    },
+    /// A variable going out of scope while it is still borrowed
+    BorrowOutlivesValue {
+        name: String,
+        borrow: Span,
+        /// The end of the scope of the variable
+        dropped: Span,
+        /// Where the borrow is used after the variable is dropped, if it isn't the value of the
+        /// scope
+        later: Option<Span>,
+    },
+    /// Returning a reference to a local variable or an argument of the function
+    ReturnLocalReference {
+        name: String,
+        borrow: Span,
+        span: Span,
+    },
+    /// An `as` cast between types that can't be converted
+    InvalidCast {
+        from: Type,
+        to: Type,
+        span: Span,
+    },
+    /// A construct that the parser accepts, but the type checker doesn't support yet
+    Unsupported(String, Span),
 }
 
 impl std::fmt::Display for TypeCheckError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::UnableToInferType => write!(f, "Unable to infer type"),
-            Self::IncompatibleTypes { expected, found } => {
+            Self::UnableToInferType(_) => write!(f, "Unable to infer type"),
+            Self::IncompatibleTypes {
+                expected, found, ..
+            } => {
                 write!(f, "Expected type {expected}, found type {found}")
             }
-            Self::UnknownVariable(name) => write!(f, "Unknown variable {name}"),
+            Self::UnknownVariable(name, _) => write!(f, "Unknown variable {name}"),
+            Self::AssignToImmutable { name, .. } => {
+                write!(f, "Cannot assign to immutable variable {name}")
+            }
+            Self::InvalidAssignmentTarget(_) => write!(f, "Invalid assignment target"),
+            Self::InvalidOperand { op, ty, .. } => {
+                write!(f, "Cannot apply operator {op} to type {ty}")
+            }
+            Self::OutsideOfLoop { keyword, .. } => write!(f, "{keyword} outside of a loop"),
+            Self::UnknownLabel(label, _) => write!(f, "Unknown label '{label}"),
+            Self::BreakWithValue(_) => write!(f, "Break with a value from a while loop"),
+            Self::UnknownFunction(name, _) => write!(f, "Unknown function {name}"),
+            Self::ArgumentCount {
+                expected, found, ..
+            } => write!(f, "Expected {expected} arguments, found {found}"),
+            Self::MissingReturn { expected, .. } => {
+                write!(f, "Function can reach its end without returning {expected}")
+            }
+            Self::DuplicateFunction { name, .. } => {
+                write!(f, "Function {name} is defined multiple times")
+            }
+            Self::LiteralOutOfRange { ty, .. } => write!(f, "Literal out of range for {ty}"),
+            Self::UnknownType(name, _) => write!(f, "Unknown type {name}"),
+            Self::DuplicateType { name, .. } => write!(f, "Type {name} is defined multiple times"),
+            Self::DuplicateField { name, .. } => {
+                write!(f, "Field {name} is specified multiple times")
+            }
+            Self::UnknownField { ty, field, .. } => write!(f, "No field {field} on type {ty}"),
+            Self::MissingFields { name, fields, .. } => {
+                write!(f, "Missing fields {} in {name}", fields.join(", "))
+            }
+            Self::UnknownVariant {
+                enum_name, variant, ..
+            } => write!(f, "No variant {variant} in enum {enum_name}"),
+            Self::DuplicateVariant { name, .. } => {
+                write!(f, "Variant {name} is defined multiple times")
+            }
+            Self::VariantKindMismatch { name, .. } => {
+                write!(f, "Variant {name} is written with the wrong kind of fields")
+            }
+            Self::FieldCount {
+                name,
+                expected,
+                found,
+                ..
+            } => write!(f, "Expected {expected} fields in {name}, found {found}"),
+            Self::NonExhaustive { missing, .. } => {
+                write!(f, "Non-exhaustive patterns: {missing} not covered")
+            }
+            Self::RefutablePattern { missing, .. } => {
+                write!(f, "Refutable pattern in let: {missing} not covered")
+            }
+            Self::RecursiveType { name, .. } => write!(f, "Recursive type {name}"),
+            Self::MismatchedPattern {
+                expected, found, ..
+            } => write!(f, "Expected type {expected}, found {found}"),
+            Self::DuplicateBinding { name, .. } => {
+                write!(f, "Variable {name} is bound multiple times in the pattern")
+            }
+            Self::NotIndexable { ty, .. } => write!(f, "Cannot index into type {ty}"),
+            Self::UnknownMethod { ty, method, .. } => {
+                write!(f, "No method {method} on type {ty}")
+            }
+            Self::BorrowImmutable { name, .. } => {
+                write!(f, "Cannot borrow immutable variable {name} as mutable")
+            }
+            Self::WriteThroughImmutable { ty, .. } => write!(f, "Cannot write through {ty}"),
+            Self::UnsafeDeref(_) => write!(f, "Dereference of raw pointer outside of unsafe"),
+            Self::BorrowConflict { name, access, .. } => match access {
+                Access::Read => write!(f, "Cannot use {name} while it is borrowed mutably"),
+                Access::Write => write!(f, "Cannot assign to {name} while it is borrowed"),
+                Access::Borrow { .. } => write!(f, "Conflicting borrows of {name}"),
+            },
+            Self::BorrowOutlivesValue { name, .. } => {
+                write!(f, "Borrow of {name} outlives the variable")
+            }
+            Self::ReturnLocalReference { name, .. } => {
+                write!(f, "Cannot return a reference to local variable {name}")
+            }
+            Self::InvalidCast { from, to, .. } => write!(f, "Cannot cast {from} as {to}"),
+            Self::Unsupported(construct, _) => write!(f, "Unsupported: {construct}"),
+        }
+    }
+}
+
+impl ToDiagnostic for TypeCheckError {
+    fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            Self::UnableToInferType(span) => {
+                Diagnostic::error("unable to infer type", span.clone())
+                    .with_label("type annotations needed")
+            }
+            Self::IncompatibleTypes {
+                expected,
+                found,
+                span,
+                declared,
+            } => {
+                let mut diagnostic = Diagnostic::error("mismatched types", span.clone())
+                    .with_label(format!("expected `{expected}`, found `{found}`"));
+                // Numbers are never converted implicitly
+                if let (Type::Number(_), Type::Number(_)) = (expected, found) {
+                    diagnostic = diagnostic.with_help(format!(
+                        "you can convert `{found}` to `{expected}` with `as {expected}`"
+                    ));
+                }
+                match declared {
+                    Some(declared) => diagnostic.with_secondary(
+                        declared.clone(),
+                        format!("expected `{expected}` because of this"),
+                    ),
+                    None => diagnostic,
+                }
+            }
+            Self::UnknownVariable(name, span) => Diagnostic::error(
+                format!("cannot find value `{name}` in this scope"),
+                span.clone(),
+            )
+            .with_label("not found in this scope"),
+            Self::AssignToImmutable {
+                name,
+                span,
+                declared,
+                argument: false,
+            } => Diagnostic::error(
+                format!("cannot assign twice to immutable variable `{name}`"),
+                span.clone(),
+            )
+            .with_label("cannot assign twice to immutable variable")
+            .with_secondary(declared.clone(), format!("first assignment to `{name}`"))
+            .with_help(format!(
+                "consider making this binding mutable: `mut {name}`"
+            )),
+            Self::AssignToImmutable {
+                name,
+                span,
+                declared,
+                argument: true,
+            } => Diagnostic::error(
+                format!("cannot assign to immutable argument `{name}`"),
+                span.clone(),
+            )
+            .with_label("cannot assign to immutable argument")
+            .with_secondary(declared.clone(), "argument declared here")
+            .with_help(format!(
+                "arguments can't be assigned to, use a mutable local instead: `let mut {name} = {name};`"
+            )),
+            Self::InvalidAssignmentTarget(span) => {
+                Diagnostic::error("invalid left-hand side of assignment", span.clone())
+                    .with_label("cannot assign to this expression")
+            }
+            Self::InvalidOperand { op, ty, span } => Diagnostic::error(
+                format!("cannot apply operator `{op}` to type `{ty}`"),
+                span.clone(),
+            )
+            .with_label(format!("`{op}` is not supported for `{ty}`")),
+            Self::OutsideOfLoop { keyword, span } => {
+                Diagnostic::error(format!("`{keyword}` outside of a loop"), span.clone())
+                    .with_label(format!("cannot `{keyword}` outside of a loop"))
+            }
+            Self::UnknownLabel(label, span) => Diagnostic::error(
+                format!("use of undeclared label `'{label}`"),
+                span.clone(),
+            )
+            .with_label(format!("undeclared label `'{label}`")),
+            Self::BreakWithValue(span) => {
+                Diagnostic::error("`break` with value from a `while` loop", span.clone())
+                    .with_label("can only break with a value inside `loop`")
+            }
+            Self::UnknownFunction(name, span) => Diagnostic::error(
+                format!("cannot find function `{name}` in this scope"),
+                span.clone(),
+            )
+            .with_label("not found in this scope"),
+            Self::ArgumentCount {
+                expected,
+                found,
+                span,
+                declared,
+            } => {
+                let plural = |count: usize| if count == 1 { "" } else { "s" };
+                let diagnostic = Diagnostic::error(
+                    format!(
+                        "this function takes {expected} argument{} but {found} argument{} {} supplied",
+                        plural(*expected),
+                        plural(*found),
+                        if *found == 1 { "was" } else { "were" }
+                    ),
+                    span.clone(),
+                )
+                .with_label(format!("expected {expected} argument{}", plural(*expected)));
+                match declared {
+                    Some(declared) => diagnostic.with_secondary(declared.clone(), "function defined here"),
+                    None => diagnostic,
+                }
+            }
+            Self::MissingReturn {
+                expected,
+                span,
+                declared,
+            } => Diagnostic::error(
+                format!("function can reach its end without returning a value of type `{expected}`"),
+                span.clone(),
+            )
+            .with_label("the function can reach its end here")
+            .with_secondary(
+                declared.clone(),
+                format!("expected `{expected}` because of this return type"),
+            ),
+            Self::DuplicateFunction { name, span, first }
+            | Self::DuplicateType { name, span, first }
+            | Self::DuplicateVariant { name, span, first } => Diagnostic::error(
+                format!("the name `{name}` is defined multiple times"),
+                span.clone(),
+            )
+            .with_label(format!("`{name}` redefined here"))
+            .with_secondary(first.clone(), format!("previous definition of `{name}` here")),
+            Self::LiteralOutOfRange { ty, span } => {
+                Diagnostic::error(format!("literal out of range for `{ty}`"), span.clone())
+                    .with_label(format!("this value doesn't fit in `{ty}`"))
+            }
+            Self::UnknownType(name, span) => Diagnostic::error(
+                format!("cannot find type `{name}` in this scope"),
+                span.clone(),
+            )
+            .with_label("not found in this scope"),
+            Self::DuplicateField { name, span, first } => Diagnostic::error(
+                format!("field `{name}` is specified more than once"),
+                span.clone(),
+            )
+            .with_label("used more than once")
+            .with_secondary(first.clone(), format!("first use of `{name}`")),
+            Self::UnknownField { ty, field, span } => Diagnostic::error(
+                format!("no field `{field}` on type `{ty}`"),
+                span.clone(),
+            )
+            .with_label("unknown field"),
+            Self::MissingFields { name, fields, span } => {
+                let plural = if fields.len() == 1 { "" } else { "s" };
+                let fields = list_fields(fields);
+                Diagnostic::error(
+                    format!("missing field{plural} {fields} in initializer of `{name}`"),
+                    span.clone(),
+                )
+                .with_label(format!("missing {fields}"))
+            }
+            Self::MismatchedPattern {
+                expected,
+                found,
+                span,
+            } => Diagnostic::error("mismatched types", span.clone())
+                .with_label(format!("expected `{expected}`, found {found}")),
+            Self::DuplicateBinding { name, span, first } => Diagnostic::error(
+                format!("identifier `{name}` is bound more than once in the same pattern"),
+                span.clone(),
+            )
+            .with_label("used in a pattern more than once")
+            .with_secondary(first.clone(), format!("first binding of `{name}`")),
+            Self::UnknownVariant {
+                enum_name,
+                variant,
+                span,
+            } => Diagnostic::error(
+                format!("no variant named `{variant}` found for enum `{enum_name}`"),
+                span.clone(),
+            )
+            .with_label(format!("variant not found in `{enum_name}`")),
+            Self::VariantKindMismatch { name, kind, span } => {
+                let (kind, help) = match kind {
+                    VariantKind::Unit => ("unit", format!("write it without fields: `{name}`")),
+                    VariantKind::Tuple => (
+                        "tuple",
+                        format!("write its fields in parentheses: `{name}(..)`"),
+                    ),
+                    VariantKind::Struct => (
+                        "struct",
+                        format!("write its fields in braces: `{name} {{ .. }}`"),
+                    ),
+                };
+                Diagnostic::error(format!("`{name}` is a {kind} variant"), span.clone())
+                    .with_label("wrong kind of variant")
+                    .with_help(help)
+            }
+            Self::FieldCount {
+                name,
+                expected,
+                found,
+                span,
+            } => {
+                let plural = if *expected == 1 { "" } else { "s" };
+                Diagnostic::error(
+                    format!(
+                        "`{name}` has {expected} field{plural}, but {found} {} given",
+                        if *found == 1 { "was" } else { "were" }
+                    ),
+                    span.clone(),
+                )
+                .with_label(format!("expected {expected} field{plural}"))
+            }
+            Self::NonExhaustive { missing, span } => Diagnostic::error(
+                format!("non-exhaustive patterns: `{missing}` not covered"),
+                span.clone(),
+            )
+            .with_label(format!("pattern `{missing}` not covered"))
+            .with_help("add an arm for the missing values, or a `_` arm that matches anything"),
+            Self::RefutablePattern { missing, span } => {
+                Diagnostic::error("refutable pattern in `let` binding", span.clone())
+                    .with_label(format!("pattern `{missing}` not covered"))
+                    .with_help("`let` needs a pattern that matches every value, use `match` to handle the other values")
+            }
+            Self::RecursiveType { name, span } => Diagnostic::error(
+                format!("recursive type `{name}` has infinite size"),
+                span.clone(),
+            )
+            .with_label("recursive without indirection"),
+            Self::NotIndexable { ty, span } => Diagnostic::error(
+                format!("cannot index into a value of type `{ty}`"),
+                span.clone(),
+            )
+            .with_label("only arrays and slices can be indexed"),
+            Self::UnknownMethod { ty, method, span } => Diagnostic::error(
+                format!("no method named `{method}` found for type `{ty}`"),
+                span.clone(),
+            )
+            .with_label("method not found"),
+            Self::BorrowImmutable {
+                name,
+                span,
+                declared,
+            } => Diagnostic::error(
+                format!("cannot borrow `{name}` as mutable, as it is not declared as mutable"),
+                span.clone(),
+            )
+            .with_label("cannot borrow as mutable")
+            .with_secondary(declared.clone(), format!("`{name}` declared here"))
+            .with_help(format!("consider making this binding mutable: `mut {name}`")),
+            Self::WriteThroughImmutable { ty, span } => {
+                let help = match ty {
+                    Type::Pointer { .. } => "use a `*mut` pointer to write to the memory",
+                    _ => "borrow the value with `&mut` to write to it",
+                };
+                Diagnostic::error(
+                    format!("cannot write to memory behind a `{ty}`"),
+                    span.clone(),
+                )
+                .with_label(format!("`{ty}` only allows reading"))
+                .with_help(help)
+            }
+            Self::UnsafeDeref(span) => Diagnostic::error(
+                "dereference of raw pointer is unsafe and requires an `unsafe` block",
+                span.clone(),
+            )
+            .with_label("dereference of raw pointer")
+            .with_help(
+                "the pointer might not point to a valid value, wrap the dereference in \
+                 `unsafe { .. }` if it does",
+            ),
+            Self::BorrowConflict {
+                name,
+                access,
+                span,
+                borrow,
+                borrow_mutable,
+                later,
+            } => {
+                let kind = if *borrow_mutable { "mutable" } else { "immutable" };
+                let (message, label) = match access {
+                    Access::Read => (
+                        format!("cannot use `{name}` because it is borrowed as mutable"),
+                        format!("use of `{name}` while it is borrowed"),
+                    ),
+                    Access::Write => (
+                        format!("cannot assign to `{name}` because it is borrowed"),
+                        format!("assignment to `{name}` while it is borrowed"),
+                    ),
+                    Access::Borrow { mutable: true } if *borrow_mutable => (
+                        format!("cannot borrow `{name}` as mutable more than once at a time"),
+                        "second mutable borrow".to_string(),
+                    ),
+                    Access::Borrow { mutable } => {
+                        let (this, other) = if *mutable {
+                            ("mutable", "immutable")
+                        } else {
+                            ("immutable", "mutable")
+                        };
+                        (
+                            format!(
+                                "cannot borrow `{name}` as {this} because it is also borrowed \
+                                 as {other}"
+                            ),
+                            format!("{this} borrow"),
+                        )
+                    }
+                };
+                Diagnostic::error(message, span.clone())
+                    .with_label(label)
+                    .with_secondary(borrow.clone(), format!("{kind} borrow of `{name}` here"))
+                    .with_secondary(later.clone(), "borrow later used here")
+            }
+            Self::BorrowOutlivesValue {
+                name,
+                borrow,
+                dropped,
+                later,
+            } => {
+                let diagnostic =
+                    Diagnostic::error(format!("`{name}` does not live long enough"), borrow.clone())
+                        .with_label("borrowed value does not live long enough")
+                        .with_secondary(
+                            dropped.clone(),
+                            format!("`{name}` dropped here while still borrowed"),
+                        );
+                match later {
+                    Some(later) => diagnostic.with_secondary(later.clone(), "borrow later used here"),
+                    None => diagnostic.with_help(format!(
+                        "declare `{name}` outside of the block so it outlives the reference"
+                    )),
+                }
+            }
+            Self::ReturnLocalReference { name, borrow, span } => Diagnostic::error(
+                "cannot return a reference to a local variable",
+                span.clone(),
+            )
+            .with_label("returns a reference to data owned by the current function")
+            .with_secondary(borrow.clone(), format!("`{name}` is borrowed here"))
+            .with_help("return the value itself instead of a reference to it"),
+            Self::InvalidCast { from, to, span } => {
+                let diagnostic =
+                    Diagnostic::error(format!("casting `{from}` as `{to}` is invalid"), span.clone())
+                        .with_label("invalid cast");
+                match (from, to) {
+                    (Type::Bool, Type::Number(_)) => {
+                        diagnostic.with_help(format!("cast through an integer first: `as u8 as {to}`"))
+                    }
+                    _ => diagnostic,
+                }
+            }
+            Self::Unsupported(construct, span) => {
+                Diagnostic::error(format!("{construct} are not supported yet"), span.clone())
+                    .with_label("not supported")
+            }
+        }
+    }
+}
+
+/// Code that type checks, but is likely to be a mistake
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeCheckWarning {
+    /// A `match` arm that can't be reached, because the earlier arms match every value it matches
+    UnreachablePattern(Span),
+}
+
+impl ToDiagnostic for TypeCheckWarning {
+    fn to_diagnostic(&self) -> Diagnostic {
+        match self {
+            Self::UnreachablePattern(span) => {
+                Diagnostic::warning("unreachable pattern", span.clone())
+                    .with_label("every value this matches is matched by an earlier arm")
+            }
         }
     }
 }
 
+/// Formats field names as a list like "`x`, `y` and `z`"
+fn list_fields(fields: &[String]) -> String {
+    let fields: Vec<_> = fields.iter().map(|field| format!("`{field}`")).collect();
+    match fields.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} and {last}", rest.join(", ")),
+        _ => fields.join(""),
+    }
+}
+
 pub struct TypeChecker {
     // We don't take ownership of the AST
+    /// The signatures of every function in the module, so calls can be checked before the called
+    /// function is
+    functions: HashMap<String, FunctionSignature>,
+    /// The structs declared in the module, which named types refer to
+    structs: HashMap<String, StructType>,
+    /// The enums declared in the module, which share the names of types with structs
+    enums: HashMap<String, EnumType>,
+    /// The warnings found so far, which don't stop the module from type checking
+    warnings: Vec<TypeCheckWarning>,
+}
+
+#[derive(Debug, Clone)]
+struct FunctionSignature {
+    arguments: Vec<Type>,
+    return_ty: Type,
+    /// The span of the signature in the function definition
+    span: Span,
+}
+
+/// The fields of a struct, in the order they are declared
+#[derive(Debug, Clone)]
+struct StructType {
+    fields: Vec<(String, Type)>,
+    /// The span of the name in the declaration
+    span: Span,
+}
+
+impl StructType {
+    fn field(&self, name: &str) -> Option<&Type> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, ty)| ty)
+    }
+}
+
+/// The variants of an enum, in the order they are declared
+#[derive(Debug, Clone)]
+struct EnumType {
+    variants: Vec<VariantType>,
+    /// The span of the name in the declaration
+    span: Span,
+}
+
+#[derive(Debug, Clone)]
+struct VariantType {
+    name: String,
+    kind: VariantKind,
+    /// The fields in the order they are declared, the fields of a tuple variant are named by their
+    /// index
+    fields: Vec<(String, Type)>,
+}
+
+/// Gets the names and types of declared fields
+fn field_types(fields: &[StructField]) -> Vec<(String, Type)> {
+    fields
+        .iter()
+        .map(|field| (field.name.clone(), field.ty.value.clone()))
+        .collect()
 }
 
 struct FunctionContext {
-    arguments: Vec<(String, Type)>,
-    return_ty: Option<Type>,
+    arguments: Vec<(String, Variable)>,
+    return_ty: Type,
+    /// The return type annotation, or the signature if the function doesn't have one
+    return_span: Span,
+    /// The loops that enclose the current expression, the innermost loop is last
+    loops: Vec<LoopContext>,
+    /// Whether the current expression is inside of an `unsafe` block
+    in_unsafe: bool,
+    /// The types that the inference variables were resolved to, indexed by their id
+    substitutions: Vec<Option<Type>>,
+}
+
+impl FunctionContext {
+    fn new_var(&mut self, kind: InferKind) -> Type {
+        self.substitutions.push(None);
+        Type::Infer(InferVar {
+            id: self.substitutions.len() - 1,
+            kind,
+        })
+    }
+
+    /// Replaces the inference variables that are already resolved with their types
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Infer(var) => match &self.substitutions[var.id] {
+                Some(ty) => self.resolve(ty),
+                None => ty.clone(),
+            },
+            Type::Tuple(types) => Type::Tuple(types.iter().map(|ty| self.resolve(ty)).collect()),
+            Type::Array(ty, len) => Type::Array(Box::new(self.resolve(ty)), *len),
+            Type::Slice { ty, mutable } => Type::Slice {
+                ty: Box::new(self.resolve(ty)),
+                mutable: *mutable,
+            },
+            Type::Ref { ty, mutable } => Type::Ref {
+                ty: Box::new(self.resolve(ty)),
+                mutable: *mutable,
+            },
+            Type::Pointer { ty, mutable } => Type::Pointer {
+                ty: Box::new(self.resolve(ty)),
+                mutable: *mutable,
+            },
+            ty => ty.clone(),
+        }
+    }
+
+    /// Resolves a type, and uses the default type for the variables that are still unknown
+    fn resolve_default(&self, ty: &Type) -> Type {
+        match self.resolve(ty) {
+            Type::Infer(var) => Type::Number(var.kind.default_type()),
+            Type::Tuple(types) => {
+                Type::Tuple(types.iter().map(|ty| self.resolve_default(ty)).collect())
+            }
+            Type::Array(ty, len) => Type::Array(Box::new(self.resolve_default(&ty)), len),
+            Type::Slice { ty, mutable } => Type::Slice {
+                ty: Box::new(self.resolve_default(&ty)),
+                mutable,
+            },
+            Type::Ref { ty, mutable } => Type::Ref {
+                ty: Box::new(self.resolve_default(&ty)),
+                mutable,
+            },
+            Type::Pointer { ty, mutable } => Type::Pointer {
+                ty: Box::new(self.resolve_default(&ty)),
+                mutable,
+            },
+            ty => ty,
+        }
+    }
+
+    /// Checks that a value of type `found` can be used where `expected` is required, and resolves
+    /// the inference variables on either side so they are the same type. A value that never
+    /// exists can be used as any type.
+    fn unify(&mut self, expected: &Type, found: &Type, span: &Span) -> Result<(), TypeCheckError> {
+        let expected = self.resolve(expected);
+        let found = self.resolve(found);
+        let unified = match (&expected, &found) {
+            _ if expected == found => true,
+            (Type::Never, _) | (_, Type::Never) => true,
+            (Type::Infer(var), Type::Infer(other)) if var.kind == other.kind => {
+                self.substitutions[var.id] = Some(found.clone());
+                true
+            }
+            (Type::Infer(var), Type::Number(number)) | (Type::Number(number), Type::Infer(var))
+                if var.kind.accepts(number) =>
+            {
+                self.substitutions[var.id] = Some(Type::Number(number.clone()));
+                true
+            }
+            (Type::Tuple(types), Type::Tuple(others)) if types.len() == others.len() => types
+                .iter()
+                .zip(others)
+                .all(|(ty, other)| self.unify(ty, other, span).is_ok()),
+            (Type::Array(ty, len), Type::Array(other, other_len)) if len == other_len => {
+                self.unify(ty, other, span).is_ok()
+            }
+            // A mutable reference can be used where a shared one is expected, but not the other
+            // way around
+            (
+                Type::Slice { ty, mutable },
+                Type::Slice {
+                    ty: other,
+                    mutable: other_mutable,
+                },
+            )
+            | (
+                Type::Ref { ty, mutable },
+                Type::Ref {
+                    ty: other,
+                    mutable: other_mutable,
+                },
+            )
+            | (
+                Type::Pointer { ty, mutable },
+                Type::Pointer {
+                    ty: other,
+                    mutable: other_mutable,
+                },
+            ) if *other_mutable || !mutable => self.unify(ty, other, span).is_ok(),
+            _ => false,
+        };
+        if !unified {
+            // The elements of tuples that matched before the mismatch have been resolved since
+            return Err(TypeCheckError::IncompatibleTypes {
+                expected: self.resolve(&expected),
+                found: self.resolve(&found),
+                span: span.clone(),
+                declared: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that `value` can be used where `expected` is required, like `unify`. The elements of
+    /// a tuple literal are checked one at a time, so a mismatch points at the element
+    fn unify_value(&mut self, expected: &Type, value: &Expr) -> Result<(), TypeCheckError> {
+        if let (ExprKind::Tuple(elements), Type::Tuple(types)) =
+            (&value.kind, self.resolve(expected))
+        {
+            if elements.len() == types.len() {
+                return elements
+                    .iter()
+                    .zip(&types)
+                    .try_for_each(|(element, ty)| self.unify_value(ty, element));
+            }
+        }
+        self.unify(expected, value.ty.as_ref().unwrap(), &value.span)
+    }
+
+    /// Checks that a value of type `found` can be returned from the function
+    fn check_return(&mut self, found: &Type, span: &Span) -> Result<(), TypeCheckError> {
+        let return_ty = self.return_ty.clone();
+        let result = self.unify(&return_ty, found, span);
+        self.return_mismatch(result)
+    }
+
+    /// Checks that `value` can be returned from the function
+    fn check_return_value(&mut self, value: &Expr) -> Result<(), TypeCheckError> {
+        let return_ty = self.return_ty.clone();
+        let result = self.unify_value(&return_ty, value);
+        self.return_mismatch(result)
+    }
+
+    /// Adds the declared return type to a mismatch with the whole of it, rather than with an
+    /// element of a tuple
+    fn return_mismatch(&self, result: Result<(), TypeCheckError>) -> Result<(), TypeCheckError> {
+        result.map_err(|err| match err {
+            TypeCheckError::IncompatibleTypes {
+                expected,
+                found,
+                span,
+                ..
+            } if expected == self.resolve(&self.return_ty) => TypeCheckError::IncompatibleTypes {
+                expected,
+                found,
+                span,
+                declared: Some(self.return_span.clone()),
+            },
+            err => err,
+        })
+    }
+}
+
+/// A loop that `break` and `continue` can refer to
+struct LoopContext {
+    label: Option<String>,
+    /// Only `loop` can be exited with a value, `while` always has the value `()`
+    has_value: bool,
+    /// The type of the value that the loop is exited with, if there is a `break` for it
+    break_ty: Option<Type>,
 }
 
+/// A variable that is in scope, either a function argument or a local
+#[derive(Debug, Clone)]
+struct Variable {
+    ty: Type,
+    mutable: bool,
+    argument: bool,
+    /// Where the variable was declared
+    span: Span,
+}
+
+/// The variables that are in scope in a block. Nested blocks start with a copy of their parent's
+/// variables, so bindings in the nested block go out of scope at the end of it.
+#[derive(Clone)]
 struct BlockContext {
-    variables: HashMap<String, Type>,
+    variables: HashMap<String, Variable>,
 }
 
 impl BlockContext {
@@ -44,58 +978,770 @@ impl BlockContext {
 
 impl TypeChecker {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            functions: HashMap::new(),
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn warnings(&self) -> &[TypeCheckWarning] {
+        &self.warnings
     }
 
     #[instrument(skip(self, ast))]
     pub fn type_check(&mut self, ast: &mut Vec<TopLevel>) -> Result<(), TypeCheckError> {
+        self.collect_signatures(ast)?;
         for node in &mut *ast {
             tracing::trace!("Type checking node");
             match &mut node.kind {
                 TopLevelKind::Expr(expr) => match &mut expr.kind {
                     ExprKind::FunctionDef(function) => {
                         let mut ctx = FunctionContext {
-                            arguments: function.arguments.clone(),
-                            return_ty: function.return_ty.clone(),
+                            arguments: function
+                                .arguments
+                                .iter()
+                                .map(|arg| {
+                                    let variable = Variable {
+                                        ty: arg.ty.value.clone(),
+                                        mutable: false,
+                                        argument: true,
+                                        span: arg.span.clone(),
+                                    };
+                                    (arg.name.clone(), variable)
+                                })
+                                .collect(),
+                            // Functions without a return type return `()`
+                            return_ty: function
+                                .return_ty
+                                .as_ref()
+                                .map_or(Type::empty(), |ty| ty.value.clone()),
+                            return_span: function
+                                .return_ty
+                                .as_ref()
+                                .map_or(function.span.clone(), |ty| ty.span.clone()),
+                            loops: Vec::new(),
+                            in_unsafe: false,
+                            substitutions: Vec::new(),
                         };
-                        if !self.evaluate_block(&mut function.body, &mut ctx)? {
-                            return Err(TypeCheckError::IncompatibleTypes {
-                                expected: ctx.return_ty.clone().unwrap(),
-                                found: Type::empty(),
-                            });
+                        let mut block_ctx = BlockContext::new();
+                        for (name, variable) in &ctx.arguments {
+                            block_ctx.variables.insert(name.clone(), variable.clone());
+                        }
+                        // If the end of the body is reachable, the value of the body is returned
+                        if let Some(ty) =
+                            self.evaluate_block(&mut function.body, &mut ctx, &block_ctx)?
+                        {
+                            let ty = ctx.resolve(&ty);
+                            if ty.is_empty() && !ctx.return_ty.is_empty() {
+                                let end = function.body.span.end;
+                                return Err(TypeCheckError::MissingReturn {
+                                    expected: ctx.return_ty,
+                                    span: end - 1..end,
+                                    declared: ctx.return_span,
+                                });
+                            }
+                            match &function.body.tail {
+                                Some(tail) => ctx.check_return_value(tail)?,
+                                None => ctx.check_return(&ty, &function.body.span)?,
+                            }
                         }
-                        if function.return_ty.is_none() {
-                            return Err(TypeCheckError::UnableToInferType);
+                        for expr in function.body.expressions_mut() {
+                            Self::resolve_expr(expr, &ctx, false)?;
                         }
+                        self.check_borrows(function)?;
                     }
-                    ExprKind::Literal(_) | ExprKind::Return(_) | ExprKind::Variable(_) => {
-                        unreachable!()
+                    _ => {
+                        return Err(TypeCheckError::Unsupported(
+                            "top level expressions".to_string(),
+                            expr.span.clone(),
+                        ));
                     }
                 },
+                // Structs and enums are checked with the signatures
+                TopLevelKind::Struct(_) | TopLevelKind::Enum(_) => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Collects the types and the signatures of every function before any body is checked, so
+    /// they can be used before they are declared
+    fn collect_signatures(&mut self, ast: &[TopLevel]) -> Result<(), TypeCheckError> {
+        // Types are collected first, since signatures and other types refer to them
+        for node in ast {
+            let (name, span) = match &node.kind {
+                TopLevelKind::Struct(def) => (&def.name, &def.span),
+                TopLevelKind::Enum(def) => (&def.name, &def.span),
+                TopLevelKind::Expr(_) => continue,
+            };
+            let first = self
+                .structs
+                .get(name)
+                .map(|struct_ty| &struct_ty.span)
+                .or_else(|| self.enums.get(name).map(|enum_ty| &enum_ty.span));
+            if let Some(first) = first {
+                return Err(TypeCheckError::DuplicateType {
+                    name: name.clone(),
+                    span: span.clone(),
+                    first: first.clone(),
+                });
+            }
+            match &node.kind {
+                TopLevelKind::Struct(def) => {
+                    let struct_ty = StructType {
+                        fields: field_types(&def.fields),
+                        span: def.span.clone(),
+                    };
+                    self.structs.insert(def.name.clone(), struct_ty);
+                }
+                TopLevelKind::Enum(def) => {
+                    let variants = def
+                        .variants
+                        .iter()
+                        .map(|variant| VariantType {
+                            name: variant.name.clone(),
+                            kind: variant.kind,
+                            fields: field_types(&variant.fields),
+                        })
+                        .collect();
+                    let enum_ty = EnumType {
+                        variants,
+                        span: def.span.clone(),
+                    };
+                    self.enums.insert(def.name.clone(), enum_ty);
+                }
+                TopLevelKind::Expr(_) => (),
+            }
+        }
+        for node in ast {
+            let function = match &node.kind {
+                TopLevelKind::Struct(def) => {
+                    self.check_struct(def)?;
+                    continue;
+                }
+                TopLevelKind::Enum(def) => {
+                    self.check_enum(def)?;
+                    continue;
+                }
+                TopLevelKind::Expr(expr) => match &expr.kind {
+                    ExprKind::FunctionDef(function) => function,
+                    _ => continue,
+                },
+            };
+            for arg in &function.arguments {
+                self.check_type(&arg.ty.value, &arg.ty.span)?;
+            }
+            if let Some(ty) = &function.return_ty {
+                self.check_type(&ty.value, &ty.span)?;
+            }
+            let signature = FunctionSignature {
+                arguments: function
+                    .arguments
+                    .iter()
+                    .map(|arg| arg.ty.value.clone())
+                    .collect(),
+                return_ty: function
+                    .return_ty
+                    .as_ref()
+                    .map_or(Type::empty(), |ty| ty.value.clone()),
+                span: function.span.clone(),
+            };
+            if let Some(first) = self.functions.insert(function.name.clone(), signature) {
+                return Err(TypeCheckError::DuplicateFunction {
+                    name: function.name.clone(),
+                    span: function.span.clone(),
+                    first: first.span,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the fields of a struct have known types and different names, and that the
+    /// struct doesn't contain itself
+    fn check_struct(&self, def: &StructDef) -> Result<(), TypeCheckError> {
+        self.check_fields(&def.name, &def.span, &def.fields)
+    }
+
+    /// Checks that the variants of an enum have different names, and that their fields are valid
+    /// like the fields of a struct
+    fn check_enum(&self, def: &EnumDef) -> Result<(), TypeCheckError> {
+        let mut declared: HashMap<&str, &Span> = HashMap::new();
+        for variant in &def.variants {
+            if let Some(first) = declared.insert(&variant.name, &variant.span) {
+                return Err(TypeCheckError::DuplicateVariant {
+                    name: variant.name.clone(),
+                    span: variant.span.clone(),
+                    first: first.clone(),
+                });
+            }
+            self.check_fields(&def.name, &def.span, &variant.fields)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the fields of the type `name` have known types and different names, and that
+    /// they don't contain the type itself. `span` is the name in the declaration
+    fn check_fields(
+        &self,
+        name: &str,
+        span: &Span,
+        fields: &[StructField],
+    ) -> Result<(), TypeCheckError> {
+        let mut declared: HashMap<&str, &Span> = HashMap::new();
+        for field in fields {
+            if let Some(first) = declared.insert(&field.name, &field.span) {
+                return Err(TypeCheckError::DuplicateField {
+                    name: field.name.clone(),
+                    span: field.span.clone(),
+                    first: first.clone(),
+                });
+            }
+            self.check_type(&field.ty.value, &field.ty.span)?;
+            if self.contains_type(&field.ty.value, name, &mut HashSet::new()) {
+                return Err(TypeCheckError::RecursiveType {
+                    name: name.to_string(),
+                    span: span.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a value of type `ty` contains the struct or enum `name`, directly or in the fields
+    /// of other types. `visited` are the types that were already searched
+    fn contains_type(&self, ty: &Type, name: &str, visited: &mut HashSet<String>) -> bool {
+        match ty {
+            Type::Named(other) if other == name => true,
+            Type::Named(other) if visited.insert(other.clone()) => {
+                let fields: Vec<&Type> = match (self.structs.get(other), self.enums.get(other)) {
+                    (Some(struct_ty), _) => struct_ty.fields.iter().map(|(_, ty)| ty).collect(),
+                    (_, Some(enum_ty)) => enum_ty
+                        .variants
+                        .iter()
+                        .flat_map(|variant| variant.fields.iter().map(|(_, ty)| ty))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                fields
+                    .into_iter()
+                    .any(|ty| self.contains_type(ty, name, visited))
+            }
+            Type::Tuple(types) => types.iter().any(|ty| self.contains_type(ty, name, visited)),
+            Type::Array(ty, _) => self.contains_type(ty, name, visited),
+            // References, slices and pointers only point to their values
+            _ => false,
+        }
+    }
+
+    /// Checks that the structs and enums a type annotation refers to are declared
+    fn check_type(&self, ty: &Type, span: &Span) -> Result<(), TypeCheckError> {
+        match ty {
+            Type::Named(name)
+                if !self.structs.contains_key(name) && !self.enums.contains_key(name) =>
+            {
+                Err(TypeCheckError::UnknownType(name.clone(), span.clone()))
+            }
+            Type::Tuple(types) => types.iter().try_for_each(|ty| self.check_type(ty, span)),
+            Type::Array(ty, _)
+            | Type::Slice { ty, .. }
+            | Type::Ref { ty, .. }
+            | Type::Pointer { ty, .. } => self.check_type(ty, span),
+            _ => Ok(()),
+        }
+    }
+
+    /// Gets the index and the declaration of the variant that a path refers to
+    fn find_variant(&self, path: &VariantPath) -> Result<(usize, &VariantType), TypeCheckError> {
+        let Some(enum_ty) = self.enums.get(&path.enum_name.value) else {
+            return Err(TypeCheckError::UnknownType(
+                path.enum_name.value.clone(),
+                path.enum_name.span.clone(),
+            ));
+        };
+        enum_ty
+            .variants
+            .iter()
+            .enumerate()
+            .find(|(_, variant)| variant.name == path.variant.value)
+            .ok_or_else(|| TypeCheckError::UnknownVariant {
+                enum_name: path.enum_name.value.clone(),
+                variant: path.variant.value.clone(),
+                span: path.variant.span.clone(),
+            })
+    }
+
+    /// Checks that a variant is written with the same kind of fields it is declared with, and that
+    /// a tuple variant has the right number of fields
+    fn check_variant_kind(
+        path: &VariantPath,
+        variant: &VariantType,
+        kind: VariantKind,
+        fields: usize,
+        span: &Span,
+    ) -> Result<(), TypeCheckError> {
+        if kind != variant.kind {
+            return Err(TypeCheckError::VariantKindMismatch {
+                name: path.to_string(),
+                kind: variant.kind,
+                span: span.clone(),
+            });
+        }
+        if kind == VariantKind::Tuple && fields != variant.fields.len() {
+            return Err(TypeCheckError::FieldCount {
+                name: path.to_string(),
+                expected: variant.fields.len(),
+                found: fields,
+                span: span.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Evaluates the fields of a struct literal or an enum variant, and checks that every declared
+    /// field is initialized once. `ty` is the type that is being constructed, and `name` is the
+    /// struct or variant the fields belong to.
+    fn check_field_inits(
+        &mut self,
+        name: &Spanned<String>,
+        ty: &Type,
+        declared: &[(String, Type)],
+        fields: &mut [FieldInit],
+        ctx: &mut FunctionContext,
+        block_ctx: &mut BlockContext,
+    ) -> Result<bool, TypeCheckError> {
+        let mut diverges = false;
+        let mut initialized: HashMap<String, Span> = HashMap::new();
+        for field in fields {
+            let Some((_, field_ty)) = declared
+                .iter()
+                .find(|(declared, _)| *declared == field.name)
+            else {
+                return Err(TypeCheckError::UnknownField {
+                    ty: ty.clone(),
+                    field: field.name.clone(),
+                    span: field.span.clone(),
+                });
+            };
+            if let Some(first) = initialized.insert(field.name.clone(), field.span.clone()) {
+                return Err(TypeCheckError::DuplicateField {
+                    name: field.name.clone(),
+                    span: field.span.clone(),
+                    first,
+                });
+            }
+            diverges |= self.evaluate_expr(&mut field.value, ctx, block_ctx)?;
+            ctx.unify(
+                field_ty,
+                field.value.ty.as_ref().unwrap(),
+                &field.value.span,
+            )?;
+        }
+        let missing: Vec<_> = declared
+            .iter()
+            .filter(|(field, _)| !initialized.contains_key(field))
+            .map(|(field, _)| field.clone())
+            .collect();
+        if !missing.is_empty() {
+            return Err(TypeCheckError::MissingFields {
+                name: name.value.clone(),
+                fields: missing,
+                span: name.span.clone(),
+            });
+        }
+        Ok(diverges)
+    }
+
+    /// Adds the variables that a pattern binds to the block, when it is matched against a value of
+    /// type `ty`. `declared` is the statement that declares them, and `bound` are the variables
+    /// that were already bound by the pattern
+    fn bind_pattern(
+        &self,
+        pattern: &mut Pattern,
+        ty: &Type,
+        ctx: &mut FunctionContext,
+        block_ctx: &mut BlockContext,
+        declared: &Span,
+        bound: &mut HashMap<String, Span>,
+    ) -> Result<(), TypeCheckError> {
+        let ty = ctx.resolve(ty);
+        match &mut pattern.kind {
+            PatternKind::Binding { name, mutable } => {
+                if let Some(first) = bound.insert(name.clone(), pattern.span.clone()) {
+                    return Err(TypeCheckError::DuplicateBinding {
+                        name: name.clone(),
+                        span: pattern.span.clone(),
+                        first,
+                    });
+                }
+                let variable = Variable {
+                    ty: ty.clone(),
+                    mutable: *mutable,
+                    argument: false,
+                    span: declared.clone(),
+                };
+                block_ctx.variables.insert(name.clone(), variable);
+            }
+            PatternKind::Wildcard => (),
+            PatternKind::Tuple(elements) => {
+                let types = match &ty {
+                    Type::Tuple(types) if types.len() == elements.len() => types.clone(),
+                    // The value is never produced, so the elements can have any type
+                    Type::Never => vec![Type::Never; elements.len()],
+                    _ => {
+                        let plural = if elements.len() == 1 { "" } else { "s" };
+                        return Err(TypeCheckError::MismatchedPattern {
+                            expected: ty,
+                            found: format!("a tuple with {} element{plural}", elements.len()),
+                            span: pattern.span.clone(),
+                        });
+                    }
+                };
+                for (element, ty) in elements.iter_mut().zip(&types) {
+                    self.bind_pattern(element, ty, ctx, block_ctx, declared, bound)?;
+                }
+            }
+            PatternKind::Literal { value, .. } => {
+                let literal_ty = match value {
+                    Literal::Number(NumberLiteral {
+                        value: NumberValue::Float(_),
+                        ..
+                    }) => {
+                        return Err(TypeCheckError::Unsupported(
+                            "float literals in patterns".to_string(),
+                            pattern.span.clone(),
+                        ))
+                    }
+                    Literal::Number(NumberLiteral { suffix: None, .. }) => {
+                        ctx.new_var(InferKind::Int)
+                    }
+                    value => value.ty(),
+                };
+                ctx.unify(&ty, &literal_ty, &pattern.span)?;
+            }
+            PatternKind::Variant { path, kind, fields } => {
+                let enum_ty = Type::Named(path.enum_name.value.clone());
+                if ty != enum_ty && ty != Type::Never {
+                    return Err(TypeCheckError::MismatchedPattern {
+                        expected: ty,
+                        found: format!("`{}`", path.enum_name.value),
+                        span: pattern.span.clone(),
+                    });
+                }
+                let (_, variant) = self.find_variant(path)?;
+                Self::check_variant_kind(path, variant, *kind, fields.len(), &pattern.span)?;
+                let mut matched: HashMap<String, Span> = HashMap::new();
+                for field in fields {
+                    let Some((_, field_ty)) = variant
+                        .fields
+                        .iter()
+                        .find(|(declared, _)| *declared == field.name)
+                    else {
+                        return Err(TypeCheckError::UnknownField {
+                            ty: enum_ty,
+                            field: field.name.clone(),
+                            span: field.span.clone(),
+                        });
+                    };
+                    if let Some(first) = matched.insert(field.name.clone(), field.span.clone()) {
+                        return Err(TypeCheckError::DuplicateField {
+                            name: field.name.clone(),
+                            span: field.span.clone(),
+                            first,
+                        });
+                    }
+                    self.bind_pattern(
+                        &mut field.pattern,
+                        field_ty,
+                        ctx,
+                        block_ctx,
+                        declared,
+                        bound,
+                    )?;
+                }
+                let missing: Vec<_> = variant
+                    .fields
+                    .iter()
+                    .filter(|(field, _)| !matched.contains_key(field))
+                    .map(|(field, _)| field.clone())
+                    .collect();
+                if !missing.is_empty() {
+                    return Err(TypeCheckError::MissingFields {
+                        name: path.to_string(),
+                        fields: missing,
+                        span: pattern.span.clone(),
+                    });
+                }
             }
         }
+        pattern.ty = Some(ty);
+        Ok(())
+    }
 
+    /// Replaces the inference variables in the types of a pattern with the types they were
+    /// resolved to, and checks that literals fit in their type
+    fn resolve_pattern(pattern: &mut Pattern, ctx: &FunctionContext) -> Result<(), TypeCheckError> {
+        pattern.ty = pattern.ty.as_ref().map(|ty| ctx.resolve_default(ty));
+        match &mut pattern.kind {
+            PatternKind::Tuple(elements) => {
+                for element in elements {
+                    Self::resolve_pattern(element, ctx)?;
+                }
+            }
+            PatternKind::Variant { fields, .. } => {
+                for field in fields {
+                    Self::resolve_pattern(&mut field.pattern, ctx)?;
+                }
+            }
+            PatternKind::Literal {
+                value: Literal::Number(number),
+                negated,
+            } => {
+                let Some(Type::Number(number_ty)) = &pattern.ty else {
+                    return Ok(());
+                };
+                if *negated && number_ty.kind == NumberKind::Unsigned {
+                    return Err(TypeCheckError::InvalidOperand {
+                        op: UnaryOp::Neg.to_string(),
+                        ty: Type::Number(number_ty.clone()),
+                        span: pattern.span.clone(),
+                    });
+                }
+                if !number.fits(number_ty, *negated) {
+                    return Err(TypeCheckError::LiteralOutOfRange {
+                        ty: Type::Number(number_ty.clone()),
+                        span: pattern.span.clone(),
+                    });
+                }
+            }
+            _ => (),
+        }
         Ok(())
     }
 
-    #[instrument(skip(self, block, ctx))]
+    /// Checks that an evaluated expression refers to memory that can be written to, which is
+    /// needed to assign to it or to borrow it mutably. Returns the variable that the memory belongs
+    /// to, or `None` if it is behind a mutable reference or pointer
+    fn writable_place<'a>(
+        expr: &'a Expr,
+        ctx: &FunctionContext,
+    ) -> Result<Option<&'a String>, TypeCheckError> {
+        match &expr.kind {
+            ExprKind::Variable(name) => Ok(Some(name)),
+            ExprKind::Field { expr, .. } => Self::writable_place(expr, ctx),
+            ExprKind::Index { expr: base, .. } | ExprKind::Deref(base) => {
+                match ctx.resolve(base.ty.as_ref().unwrap()) {
+                    Type::Slice { mutable: true, .. }
+                    | Type::Ref { mutable: true, .. }
+                    | Type::Pointer { mutable: true, .. } => Ok(None),
+                    ty @ (Type::Slice { .. } | Type::Ref { .. } | Type::Pointer { .. }) => {
+                        Err(TypeCheckError::WriteThroughImmutable {
+                            ty,
+                            span: expr.span.clone(),
+                        })
+                    }
+                    // The elements of an array are part of it
+                    _ => Self::writable_place(base, ctx),
+                }
+            }
+            _ => Err(TypeCheckError::InvalidAssignmentTarget(expr.span.clone())),
+        }
+    }
+
+    /// Evaluates the statements of a block, and returns the type of its value, or `None` if the
+    /// block always returns before reaching its end
+    #[instrument(skip(self, block, ctx, parent))]
     fn evaluate_block(
         &mut self,
         block: &mut Block,
         ctx: &mut FunctionContext,
-    ) -> Result<bool, TypeCheckError> {
+        parent: &BlockContext,
+    ) -> Result<Option<Type>, TypeCheckError> {
         tracing::trace!("Evaluating block");
-        let mut block_ctx = BlockContext::new();
-        for arg in &ctx.arguments {
-            block_ctx.variables.insert(arg.0.clone(), arg.1.clone());
-        }
+        let mut block_ctx = parent.clone();
+        let mut diverges = false;
         for (_idx, statement) in &mut block.statements.iter_mut().enumerate() {
             tracing::trace!("Evaluating statement {_idx}");
-            if self.evaluate_expr(statement, ctx, &mut block_ctx)? {
-                return Ok(true);
+            diverges |= self.evaluate_expr(statement, ctx, &mut block_ctx)?;
+        }
+        let ty = match &mut block.tail {
+            Some(tail) => {
+                diverges |= self.evaluate_expr(tail, ctx, &mut block_ctx)?;
+                tail.ty.clone().unwrap()
+            }
+            None => Type::empty(),
+        };
+        Ok((!diverges).then_some(ty))
+    }
+
+    /// Evaluates the body of a loop, which can't have a value
+    fn evaluate_loop_body(
+        &mut self,
+        body: &mut Block,
+        ctx: &mut FunctionContext,
+        block_ctx: &BlockContext,
+        loop_ctx: LoopContext,
+    ) -> Result<LoopContext, TypeCheckError> {
+        ctx.loops.push(loop_ctx);
+        let ty = self.evaluate_block(body, ctx, block_ctx);
+        let loop_ctx = ctx.loops.pop().unwrap();
+        match ty? {
+            Some(ty) if !ty.is_empty() => Err(TypeCheckError::IncompatibleTypes {
+                expected: Type::empty(),
+                found: ty,
+                span: Self::value_span(body),
+                declared: None,
+            }),
+            _ => Ok(loop_ctx),
+        }
+    }
+
+    /// Finds the loop that a `break` or `continue` refers to
+    fn find_loop<'a>(
+        ctx: &'a mut FunctionContext,
+        label: &Option<String>,
+        keyword: &str,
+        span: &Span,
+    ) -> Result<&'a mut LoopContext, TypeCheckError> {
+        match label {
+            Some(label) => ctx
+                .loops
+                .iter_mut()
+                .rev()
+                .find(|loop_ctx| loop_ctx.label.as_ref() == Some(label))
+                .ok_or_else(|| TypeCheckError::UnknownLabel(label.clone(), span.clone())),
+            None => ctx
+                .loops
+                .last_mut()
+                .ok_or_else(|| TypeCheckError::OutsideOfLoop {
+                    keyword: keyword.to_string(),
+                    span: span.clone(),
+                }),
+        }
+    }
+
+    /// Checks that the condition of an `if` or `while` is a `bool`
+    fn check_condition(ctx: &mut FunctionContext, condition: &Expr) -> Result<(), TypeCheckError> {
+        ctx.unify(&Type::Bool, condition.ty.as_ref().unwrap(), &condition.span)
+    }
+
+    /// Replaces the inference variables in the types of an expression and its children with the
+    /// types they were resolved to, and checks what depends on the final types: literals need to
+    /// fit in their type, and only signed numbers can be negated. `negated` is whether the
+    /// expression is the operand of a negation, which allows literals like `-128i8`.
+    fn resolve_expr(
+        expr: &mut Expr,
+        ctx: &FunctionContext,
+        negated: bool,
+    ) -> Result<(), TypeCheckError> {
+        let ty = expr.ty.as_ref().map(|ty| ctx.resolve_default(ty));
+        match (&mut expr.kind, &ty) {
+            (ExprKind::Literal(Literal::Number(number)), Some(Type::Number(number_ty))) => {
+                if !number.fits(number_ty, negated) {
+                    return Err(TypeCheckError::LiteralOutOfRange {
+                        ty: Type::Number(number_ty.clone()),
+                        span: expr.span.clone(),
+                    });
+                }
+            }
+            (
+                ExprKind::Unary {
+                    op: UnaryOp::Neg,
+                    expr: operand,
+                },
+                Some(ty),
+            ) => {
+                if let Type::Number(number) = ty {
+                    if number.kind == NumberKind::Unsigned {
+                        return Err(TypeCheckError::InvalidOperand {
+                            op: UnaryOp::Neg.to_string(),
+                            ty: ty.clone(),
+                            span: expr.span.clone(),
+                        });
+                    }
+                }
+                let literal = matches!(operand.kind, ExprKind::Literal(_));
+                Self::resolve_expr(operand, ctx, literal)?;
+            }
+            (ExprKind::Let(binding), _) => {
+                Self::resolve_pattern(&mut binding.pattern, ctx)?;
+                Self::resolve_expr(&mut binding.value, ctx, false)?;
+            }
+            (ExprKind::Match { scrutinee, arms }, _) => {
+                Self::resolve_expr(scrutinee, ctx, false)?;
+                for arm in arms {
+                    Self::resolve_pattern(&mut arm.pattern, ctx)?;
+                    Self::resolve_expr(&mut arm.body, ctx, false)?;
+                }
+            }
+            (kind, _) => {
+                for child in kind.children_mut() {
+                    Self::resolve_expr(child, ctx, false)?;
+                }
+            }
+        }
+        expr.ty = ty;
+        Ok(())
+    }
+
+    /// Gets the span of the value of a block, which is its tail expression if it has one
+    fn value_span(block: &Block) -> Span {
+        block
+            .tail
+            .as_ref()
+            .map_or(block.span.clone(), |tail| tail.span.clone())
+    }
+
+    /// Gets the type of a binary operation, assuming both operands have already been evaluated
+    fn binary_type(
+        ctx: &mut FunctionContext,
+        op: BinaryOp,
+        lhs: &Expr,
+        rhs: &Expr,
+        span: &Span,
+    ) -> Result<Type, TypeCheckError> {
+        let lhs_ty = ctx.resolve(lhs.ty.as_ref().unwrap());
+        let valid = match &lhs_ty {
+            // Logical operators only work on booleans, and bitwise operators don't make sense
+            // for floats
+            Type::Number(number) => {
+                !op.is_logical() && (number.kind != NumberKind::Float || !op.is_bitwise())
             }
+            Type::Infer(var) => {
+                !op.is_logical() && (var.kind != InferKind::Float || !op.is_bitwise())
+            }
+            // Booleans have no ordering, and can't be shifted
+            Type::Bool => {
+                op.is_logical()
+                    || matches!(
+                        op,
+                        BinaryOp::BitAnd
+                            | BinaryOp::BitOr
+                            | BinaryOp::BitXor
+                            | BinaryOp::Eq
+                            | BinaryOp::Ne
+                    )
+            }
+            _ => false,
+        };
+        if !valid {
+            return Err(TypeCheckError::InvalidOperand {
+                op: op.to_string(),
+                ty: lhs_ty,
+                span: span.clone(),
+            });
+        }
+        // There are no implicit conversions, even the amount to shift by needs to have the same type
+        ctx.unify(&lhs_ty, rhs.ty.as_ref().unwrap(), &rhs.span)?;
+
+        if op.is_comparison() || op.is_logical() {
+            Ok(Type::Bool)
+        } else {
+            Ok(lhs_ty)
         }
-        Ok(false)
     }
 
     /// Returns a type, and whether of not it was not a return statement
@@ -111,31 +1757,1605 @@ impl TypeChecker {
         tracing::trace!("Evaluating expression");
         // TODO: Control Flow evaluation
         match &mut statement.kind {
-            ExprKind::Return(expr) => match expr {
-                Some(ref mut expr) => {
-                    // We need it to evluate the type first
-                    self.evaluate_expr(expr, ctx, block_ctx)?;
-                    statement.ty.replace(Type::empty());
-                    Ok(true)
+            ExprKind::Return(expr) => {
+                match expr {
+                    Some(ref mut expr) => {
+                        // We need it to evluate the type first
+                        self.evaluate_expr(expr, ctx, block_ctx)?;
+                        ctx.check_return_value(expr)?;
+                    }
+                    // A bare `return` returns `()`
+                    None => ctx.check_return(&Type::empty(), &statement.span)?,
+                }
+                statement.ty.replace(Type::Never);
+                Ok(true)
+            }
+            ExprKind::Literal(lit) => {
+                // The type of a literal without a suffix depends on where it is used
+                let ty = match lit {
+                    Literal::Number(NumberLiteral {
+                        value,
+                        suffix: None,
+                    }) => ctx.new_var(match value {
+                        NumberValue::Int(_) => InferKind::Int,
+                        NumberValue::Float(_) => InferKind::Float,
+                    }),
+                    lit => lit.ty(),
+                };
+                statement.ty.replace(ty);
+                Ok(false)
+            }
+            ExprKind::Variable(name) => {
+                if let Some(variable) = block_ctx.variables.get(name) {
+                    statement.ty.replace(variable.ty.clone());
+                    Ok(false)
+                } else {
+                    Err(TypeCheckError::UnknownVariable(
+                        name.clone(),
+                        statement.span.clone(),
+                    ))
+                }
+            }
+            ExprKind::Let(binding) => {
+                let diverges = self.evaluate_expr(&mut binding.value, ctx, block_ctx)?;
+                let value_ty = binding.value.ty.clone().unwrap();
+                let ty = match &binding.ty {
+                    Some(annotation) => {
+                        self.check_type(&annotation.value, &annotation.span)?;
+                        ctx.unify_value(&annotation.value, &binding.value)?;
+                        annotation.value.clone()
+                    }
+                    None => value_ty,
+                };
+                // The bindings are only added after the value is evaluated, so `let x = x;`
+                // refers to the previous `x`
+                self.bind_pattern(
+                    &mut binding.pattern,
+                    &ty,
+                    ctx,
+                    block_ctx,
+                    &statement.span,
+                    &mut HashMap::new(),
+                )?;
+                self.check_irrefutable(&binding.pattern, &ctx.resolve(&ty))?;
+                statement.ty.replace(Type::empty());
+                Ok(diverges)
+            }
+            ExprKind::Assign { op, target, value } => {
+                self.evaluate_expr(target, ctx, block_ctx)?;
+                // Fields are assigned to through the variable that holds the struct, unless they
+                // are behind a reference
+                if let Some(name) = Self::writable_place(target, ctx)? {
+                    let variable = &block_ctx.variables[name];
+                    if !variable.mutable {
+                        return Err(TypeCheckError::AssignToImmutable {
+                            name: name.clone(),
+                            span: statement.span.clone(),
+                            declared: variable.span.clone(),
+                            argument: variable.argument,
+                        });
+                    }
+                }
+
+                self.evaluate_expr(value, ctx, block_ctx)?;
+                let target_ty = target.ty.clone().unwrap();
+                if let Some(op) = op {
+                    // Compound assignments follow the same rules as the binary operator
+                    Self::binary_type(ctx, *op, target, value, &statement.span).map_err(|err| {
+                        match err {
+                            TypeCheckError::InvalidOperand { op, ty, span } => {
+                                TypeCheckError::InvalidOperand {
+                                    op: format!("{op}="),
+                                    ty,
+                                    span,
+                                }
+                            }
+                            err => err,
+                        }
+                    })?;
+                }
+                ctx.unify_value(&target_ty, value)?;
+                statement.ty.replace(Type::empty());
+                Ok(false)
+            }
+            ExprKind::Binary { op, lhs, rhs } => {
+                self.evaluate_expr(lhs, ctx, block_ctx)?;
+                self.evaluate_expr(rhs, ctx, block_ctx)?;
+                let ty = Self::binary_type(ctx, *op, lhs, rhs, &statement.span)?;
+                statement.ty.replace(ty);
+                Ok(false)
+            }
+            ExprKind::Unary { op, expr } => {
+                self.evaluate_expr(expr, ctx, block_ctx)?;
+                let ty = ctx.resolve(expr.ty.as_ref().unwrap());
+                let valid = match (*op, &ty) {
+                    // Unsigned integers can't be negated, an integer that isn't known yet is
+                    // checked again once it is resolved
+                    (UnaryOp::Neg, Type::Number(number)) => number.kind != NumberKind::Unsigned,
+                    (UnaryOp::Neg, Type::Infer(_)) => true,
+                    (UnaryOp::Not, Type::Number(number)) => number.kind != NumberKind::Float,
+                    (UnaryOp::Not, Type::Infer(var)) => var.kind == InferKind::Int,
+                    (UnaryOp::Not, Type::Bool) => true,
+                    _ => false,
+                };
+                if !valid {
+                    return Err(TypeCheckError::InvalidOperand {
+                        op: op.to_string(),
+                        ty,
+                        span: statement.span.clone(),
+                    });
+                }
+                statement.ty.replace(ty);
+                Ok(false)
+            }
+            ExprKind::Cast { expr, ty } => {
+                self.check_type(&ty.value, &ty.span)?;
+                let diverges = self.evaluate_expr(expr, ctx, block_ctx)?;
+                let from = ctx.resolve(expr.ty.as_ref().unwrap());
+                let valid = match (&from, &ty.value) {
+                    // A literal that isn't constrained yet gets the target type, so `300 as u8` is
+                    // out of range instead of being truncated
+                    (Type::Infer(var), Type::Number(number)) if var.kind.accepts(number) => {
+                        ctx.unify(&ty.value, &from, &expr.span)?;
+                        true
+                    }
+                    (Type::Infer(_), Type::Number(_)) => true,
+                    (Type::Number(_), Type::Number(_)) => true,
+                    (Type::Bool, Type::Number(number)) => number.kind != NumberKind::Float,
+                    // A reference can be turned into a raw pointer, and a slice into a pointer to
+                    // its first element. Only mutable references can become `*mut` pointers
+                    (
+                        Type::Ref {
+                            ty: from_ty,
+                            mutable,
+                        }
+                        | Type::Slice {
+                            ty: from_ty,
+                            mutable,
+                        },
+                        Type::Pointer {
+                            ty: to_ty,
+                            mutable: to_mutable,
+                        },
+                    ) => (*mutable || !to_mutable) && ctx.unify(to_ty, from_ty, &expr.span).is_ok(),
+                    // A string becomes a pointer to its first byte, which is read-only memory
+                    (Type::Str, Type::Pointer { ty, mutable }) => {
+                        !mutable
+                            && ctx
+                                .unify(ty, &Type::Number(NumberType::U8), &expr.span)
+                                .is_ok()
+                    }
+                    // Raw pointers can be cast to pointers of any other type
+                    (Type::Pointer { .. }, Type::Pointer { .. }) => true,
+                    (Type::Never, _) => true,
+                    (from, to) => from == to,
+                };
+                if !valid {
+                    return Err(TypeCheckError::InvalidCast {
+                        from,
+                        to: ty.value.clone(),
+                        span: statement.span.clone(),
+                    });
+                }
+                statement.ty.replace(ty.value.clone());
+                Ok(diverges)
+            }
+            ExprKind::StructLiteral { name, fields } => {
+                let Some(struct_ty) = self.structs.get(&name.value).cloned() else {
+                    return Err(TypeCheckError::UnknownType(
+                        name.value.clone(),
+                        name.span.clone(),
+                    ));
+                };
+                let ty = Type::Named(name.value.clone());
+                let diverges =
+                    self.check_field_inits(name, &ty, &struct_ty.fields, fields, ctx, block_ctx)?;
+                statement.ty.replace(ty);
+                Ok(diverges)
+            }
+            ExprKind::Variant { path, kind, fields } => {
+                let (_, variant) = self.find_variant(path)?;
+                let variant = variant.clone();
+                Self::check_variant_kind(path, &variant, *kind, fields.len(), &statement.span)?;
+                let ty = Type::Named(path.enum_name.value.clone());
+                let name = Spanned {
+                    value: path.to_string(),
+                    span: path.variant.span.clone(),
+                };
+                let diverges =
+                    self.check_field_inits(&name, &ty, &variant.fields, fields, ctx, block_ctx)?;
+                statement.ty.replace(ty);
+                Ok(diverges)
+            }
+            ExprKind::Field { expr, field } => {
+                let diverges = self.evaluate_expr(expr, ctx, block_ctx)?;
+                let ty = ctx.resolve(expr.ty.as_ref().unwrap());
+                let field_ty = match &ty {
+                    Type::Named(name) => self
+                        .structs
+                        .get(name)
+                        .and_then(|struct_ty| struct_ty.field(&field.value))
+                        .cloned(),
+                    Type::Tuple(types) => field
+                        .value
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|index| types.get(index).cloned()),
+                    _ => None,
+                };
+                let Some(field_ty) = field_ty else {
+                    return Err(TypeCheckError::UnknownField {
+                        ty,
+                        field: field.value.clone(),
+                        span: field.span.clone(),
+                    });
+                };
+                statement.ty.replace(field_ty);
+                Ok(diverges)
+            }
+            ExprKind::Tuple(elements) => {
+                let mut diverges = false;
+                for element in &mut *elements {
+                    diverges |= self.evaluate_expr(element, ctx, block_ctx)?;
+                }
+                let types = elements
+                    .iter()
+                    .map(|element| element.ty.clone().unwrap())
+                    .collect();
+                statement.ty.replace(Type::Tuple(types));
+                Ok(diverges)
+            }
+            ExprKind::Array(elements) => {
+                // The elements of an empty array can be used as any type
+                let mut element_ty = Type::Never;
+                let mut diverges = false;
+                for element in &mut *elements {
+                    diverges |= self.evaluate_expr(element, ctx, block_ctx)?;
+                    let ty = element.ty.clone().unwrap();
+                    ctx.unify(&element_ty, &ty, &element.span)?;
+                    if ctx.resolve(&element_ty) == Type::Never {
+                        element_ty = ty;
+                    }
+                }
+                let len = elements.len() as u64;
+                statement.ty.replace(Type::Array(Box::new(element_ty), len));
+                Ok(diverges)
+            }
+            ExprKind::ArrayRepeat { value, count } => {
+                let diverges = self.evaluate_expr(value, ctx, block_ctx)?;
+                let ty = value.ty.clone().unwrap();
+                statement.ty.replace(Type::Array(Box::new(ty), *count));
+                Ok(diverges)
+            }
+            ExprKind::Index { expr, index } => {
+                let mut diverges = self.evaluate_expr(expr, ctx, block_ctx)?;
+                diverges |= self.evaluate_expr(index, ctx, block_ctx)?;
+                let ty = ctx.resolve(expr.ty.as_ref().unwrap());
+                let element_ty = match ty {
+                    Type::Array(ty, _) | Type::Slice { ty, .. } => *ty,
+                    Type::Never => Type::Never,
+                    ty => {
+                        return Err(TypeCheckError::NotIndexable {
+                            ty,
+                            span: expr.span.clone(),
+                        })
+                    }
+                };
+                ctx.unify(
+                    &Type::Number(NumberType::USIZE),
+                    index.ty.as_ref().unwrap(),
+                    &index.span,
+                )?;
+                statement.ty.replace(element_ty);
+                Ok(diverges)
+            }
+            ExprKind::MethodCall {
+                receiver,
+                method,
+                arguments,
+            } => {
+                let diverges = self.evaluate_expr(receiver, ctx, block_ctx)?;
+                let ty = ctx.resolve(receiver.ty.as_ref().unwrap());
+                // The only methods are built in: `len` on arrays, slices and strings, and
+                // `as_bytes` on strings
+                let method_ty = match (method.value.as_str(), &ty) {
+                    ("len", Type::Array(..) | Type::Slice { .. } | Type::Str) => {
+                        Type::Number(NumberType::USIZE)
+                    }
+                    ("as_bytes", Type::Str) => Type::Slice {
+                        ty: Box::new(Type::Number(NumberType::U8)),
+                        mutable: false,
+                    },
+                    _ => {
+                        return Err(TypeCheckError::UnknownMethod {
+                            ty,
+                            method: method.value.clone(),
+                            span: method.span.clone(),
+                        })
+                    }
+                };
+                if !arguments.is_empty() {
+                    return Err(TypeCheckError::ArgumentCount {
+                        expected: 0,
+                        found: arguments.len(),
+                        span: statement.span.clone(),
+                        declared: None,
+                    });
+                }
+                statement.ty.replace(method_ty);
+                Ok(diverges)
+            }
+            ExprKind::Ref { mutable, expr } => {
+                let diverges = self.evaluate_expr(expr, ctx, block_ctx)?;
+                if *mutable {
+                    // Temporaries can always be borrowed mutably, since nothing else refers to them
+                    let place = match Self::writable_place(expr, ctx) {
+                        Err(TypeCheckError::InvalidAssignmentTarget(_)) => None,
+                        place => place?,
+                    };
+                    if let Some(name) = place {
+                        let variable = &block_ctx.variables[name];
+                        if !variable.mutable {
+                            return Err(TypeCheckError::BorrowImmutable {
+                                name: name.clone(),
+                                span: statement.span.clone(),
+                                declared: variable.span.clone(),
+                            });
+                        }
+                    }
+                }
+                let ty = match ctx.resolve(expr.ty.as_ref().unwrap()) {
+                    // Borrowing an array gives a slice of all of its elements
+                    Type::Array(ty, _) => Type::Slice {
+                        ty,
+                        mutable: *mutable,
+                    },
+                    ty => Type::Ref {
+                        ty: Box::new(ty),
+                        mutable: *mutable,
+                    },
+                };
+                statement.ty.replace(ty);
+                Ok(diverges)
+            }
+            ExprKind::Deref(expr) => {
+                let diverges = self.evaluate_expr(expr, ctx, block_ctx)?;
+                let ty = match ctx.resolve(expr.ty.as_ref().unwrap()) {
+                    Type::Ref { ty, .. } => *ty,
+                    // Raw pointers might not point to a valid value
+                    Type::Pointer { ty, .. } if ctx.in_unsafe => *ty,
+                    Type::Pointer { .. } => {
+                        return Err(TypeCheckError::UnsafeDeref(statement.span.clone()))
+                    }
+                    Type::Never => Type::Never,
+                    ty => {
+                        return Err(TypeCheckError::InvalidOperand {
+                            op: "*".to_string(),
+                            ty,
+                            span: statement.span.clone(),
+                        })
+                    }
+                };
+                statement.ty.replace(ty);
+                Ok(diverges)
+            }
+            ExprKind::Unsafe(block) => {
+                let outer = std::mem::replace(&mut ctx.in_unsafe, true);
+                let ty = self.evaluate_block(block, ctx, block_ctx);
+                ctx.in_unsafe = outer;
+                match ty? {
+                    Some(ty) => {
+                        statement.ty.replace(ty);
+                        Ok(false)
+                    }
+                    None => {
+                        statement.ty.replace(Type::empty());
+                        Ok(true)
+                    }
+                }
+            }
+            ExprKind::Block(block) => match self.evaluate_block(block, ctx, block_ctx)? {
+                Some(ty) => {
+                    statement.ty.replace(ty);
+                    Ok(false)
                 }
                 None => {
                     statement.ty.replace(Type::empty());
                     Ok(true)
                 }
             },
-            ExprKind::Literal(lit) => {
-                statement.ty.replace(lit.ty());
+            ExprKind::If {
+                condition,
+                then_block,
+                else_block,
+            } => {
+                self.evaluate_expr(condition, ctx, block_ctx)?;
+                Self::check_condition(ctx, condition)?;
+                let then_ty = self.evaluate_block(then_block, ctx, block_ctx)?;
+                let else_ty = match else_block {
+                    Some(block) => self.evaluate_block(block, ctx, block_ctx)?,
+                    // Without an else branch, the value is `()` when the condition is false
+                    None => Some(Type::empty()),
+                };
+                let ty = match (then_ty, else_ty) {
+                    (Some(then_ty), Some(else_ty)) => {
+                        match else_block {
+                            Some(block) => ctx.unify(&then_ty, &else_ty, &Self::value_span(block)),
+                            None => ctx.unify(&else_ty, &then_ty, &Self::value_span(then_block)),
+                        }?;
+                        then_ty
+                    }
+                    (Some(ty), None) | (None, Some(ty)) => ty,
+                    // Both branches return, so the `if` itself never produces a value
+                    (None, None) => {
+                        statement.ty.replace(Type::empty());
+                        return Ok(true);
+                    }
+                };
+                statement.ty.replace(ty);
                 Ok(false)
             }
-            ExprKind::Variable(name) => {
-                if let Some(ty) = block_ctx.variables.get(name) {
-                    statement.ty.replace(ty.clone());
-                    Ok(false)
-                } else {
-                    Err(TypeCheckError::UnknownVariable(name.clone()))
+            ExprKind::Match { scrutinee, arms } => {
+                let diverges = self.evaluate_expr(scrutinee, ctx, block_ctx)?;
+                let scrutinee_ty = scrutinee.ty.clone().unwrap();
+                let mut ty: Option<Type> = None;
+                for arm in arms.iter_mut() {
+                    // The bindings of an arm are only visible in its body
+                    let mut arm_ctx = block_ctx.clone();
+                    let declared = arm.pattern.span.clone();
+                    self.bind_pattern(
+                        &mut arm.pattern,
+                        &scrutinee_ty,
+                        ctx,
+                        &mut arm_ctx,
+                        &declared,
+                        &mut HashMap::new(),
+                    )?;
+                    if self.evaluate_expr(&mut arm.body, ctx, &mut arm_ctx)? {
+                        continue;
+                    }
+                    let arm_ty = arm.body.ty.clone().unwrap();
+                    match &ty {
+                        Some(ty) => ctx.unify_value(ty, &arm.body)?,
+                        None => ty = Some(arm_ty),
+                    }
+                }
+                self.check_match(&ctx.resolve(&scrutinee_ty), arms, &scrutinee.span)?;
+                match ty {
+                    Some(ty) => {
+                        statement.ty.replace(ty);
+                        Ok(diverges)
+                    }
+                    // Every arm returns, or there are no arms because the scrutinee has no values,
+                    // so the `match` itself never produces a value
+                    None => {
+                        statement.ty.replace(Type::empty());
+                        Ok(true)
+                    }
+                }
+            }
+            ExprKind::While {
+                label,
+                condition,
+                body,
+            } => {
+                self.evaluate_expr(condition, ctx, block_ctx)?;
+                Self::check_condition(ctx, condition)?;
+                let loop_ctx = LoopContext {
+                    label: label.clone(),
+                    has_value: false,
+                    break_ty: None,
+                };
+                self.evaluate_loop_body(body, ctx, block_ctx, loop_ctx)?;
+                statement.ty.replace(Type::empty());
+                Ok(false)
+            }
+            ExprKind::Loop { label, body } => {
+                let loop_ctx = LoopContext {
+                    label: label.clone(),
+                    has_value: true,
+                    break_ty: None,
+                };
+                match self
+                    .evaluate_loop_body(body, ctx, block_ctx, loop_ctx)?
+                    .break_ty
+                {
+                    Some(ty) => {
+                        statement.ty.replace(ty);
+                        Ok(false)
+                    }
+                    // A loop without a `break` can only be exited by returning
+                    None => {
+                        statement.ty.replace(Type::Never);
+                        Ok(true)
+                    }
+                }
+            }
+            ExprKind::Break { label, value } => {
+                let ty = match value {
+                    Some(value) => {
+                        self.evaluate_expr(value, ctx, block_ctx)?;
+                        value.ty.clone().unwrap()
+                    }
+                    None => Type::empty(),
+                };
+                let loop_ctx = Self::find_loop(ctx, label, "break", &statement.span)?;
+                if value.is_some() && !loop_ctx.has_value {
+                    return Err(TypeCheckError::BreakWithValue(statement.span.clone()));
                 }
+                match loop_ctx.break_ty.clone() {
+                    Some(expected) => {
+                        let span = value
+                            .as_ref()
+                            .map_or(statement.span.clone(), |value| value.span.clone());
+                        ctx.unify(&expected, &ty, &span)?;
+                    }
+                    None => loop_ctx.break_ty = Some(ty),
+                }
+                statement.ty.replace(Type::Never);
+                Ok(true)
             }
-            ExprKind::FunctionDef(..) => unimplemented!(),
+            ExprKind::Continue { label } => {
+                Self::find_loop(ctx, label, "continue", &statement.span)?;
+                statement.ty.replace(Type::Never);
+                Ok(true)
+            }
+            ExprKind::Call { callee, arguments } => {
+                let ExprKind::Variable(name) = &callee.kind else {
+                    return Err(TypeCheckError::Unsupported(
+                        "calls to anything but a function name".to_string(),
+                        callee.span.clone(),
+                    ));
+                };
+                let Some(signature) = self.functions.get(name).cloned() else {
+                    return Err(TypeCheckError::UnknownFunction(
+                        name.clone(),
+                        callee.span.clone(),
+                    ));
+                };
+                if arguments.len() != signature.arguments.len() {
+                    return Err(TypeCheckError::ArgumentCount {
+                        expected: signature.arguments.len(),
+                        found: arguments.len(),
+                        span: statement.span.clone(),
+                        declared: Some(signature.span),
+                    });
+                }
+                let mut diverges = false;
+                for (argument, expected) in arguments.iter_mut().zip(signature.arguments) {
+                    diverges |= self.evaluate_expr(argument, ctx, block_ctx)?;
+                    ctx.unify_value(&expected, argument)?;
+                }
+                statement.ty.replace(signature.return_ty);
+                Ok(diverges)
+            }
+            ExprKind::FunctionDef(..) => Err(TypeCheckError::Unsupported(
+                "nested functions".to_string(),
+                statement.span.clone(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use redox_ast::NumberType;
+    use redox_parser::Parser;
+
+    fn type_check(source: &str) -> Result<Vec<TopLevel>, TypeCheckError> {
+        let mut ast = Parser::with_source(source).parse().into_result().unwrap();
+        TypeChecker::new().type_check(&mut ast)?;
+        Ok(ast)
+    }
+
+    #[test]
+    fn test_assign_mutable() {
+        type_check("fn foo() -> i32 { let mut x = 1; x = 2; x += x; return x; }").unwrap();
+    }
+
+    #[test]
+    fn test_operators() {
+        type_check("fn foo(a: i32, b: i32) -> i32 { return -a * (b + 1) % 3 << 2 ^ !b; }").unwrap();
+        let source = "fn foo(a: u32) -> u32 { return -a; }";
+        let Err(TypeCheckError::InvalidOperand { op, span, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(op, "-");
+        assert_eq!(&source[span], "-a");
+        let source = "fn foo(a: u32, b: i32) -> u32 { return a + b; }";
+        let Err(TypeCheckError::IncompatibleTypes { span, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[span], "b");
+
+        type_check("fn foo(a: u8, b: u8) -> bool { a / b >> b < a % b }").unwrap();
+        type_check("fn foo(a: f64, b: f64) -> f64 { -a * b % a }").unwrap();
+        let source = "fn foo(a: f32) -> f32 { a << a }";
+        let Err(TypeCheckError::InvalidOperand { ty, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(ty, Type::Number(NumberType::F32));
+        // Pointer sized integers are different types, even if they have the same size
+        let source = "fn foo(a: isize, b: i64) -> isize { a + b }";
+        let Err(TypeCheckError::IncompatibleTypes {
+            expected, found, ..
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(expected.to_string(), "isize");
+        assert_eq!(found.to_string(), "i64");
+    }
+
+    #[test]
+    fn test_literal_range() {
+        type_check("fn foo() -> i8 { -128i8 }").unwrap();
+        type_check("fn foo() -> u128 { 340282366920938463463374607431768211455u128 }").unwrap();
+        type_check("fn foo() -> f32 { 3.4e38f32 }").unwrap();
+        type_check("fn foo() -> i32 { -2147483648 }").unwrap();
+
+        for (source, ty) in [
+            ("fn foo() -> i8 { 128i8 }", "i8"),
+            ("fn foo() -> i8 { -129i8 }", "i8"),
+            ("fn foo() -> u8 { 0x100u8 }", "u8"),
+            ("fn foo() -> i32 { 2147483648 }", "i32"),
+            ("fn foo() -> f32 { 1e39f32 }", "f32"),
+        ] {
+            let Err(TypeCheckError::LiteralOutOfRange { ty: found, span }) = type_check(source)
+            else {
+                panic!("Expected an error for {source}");
+            };
+            assert_eq!(found.to_string(), ty);
+            // The span is the literal, without the negation
+            assert!(source.ends_with(&format!("{} }}", &source[span])));
         }
     }
+
+    #[test]
+    fn test_literal_inference() {
+        let ast = type_check(
+            "fn foo(a: u64) -> i64 { let x = 1; let y: u64 = x + a; let z = 2.5; \
+             let w = 3; bar(4, 1.5); return 5; } fn bar(a: u8, b: f32) {}",
+        )
+        .unwrap();
+        let TopLevelKind::Expr(expr) = &ast[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            unreachable!()
+        };
+        let types = function
+            .body
+            .statements
+            .iter()
+            .map(|statement| match &statement.kind {
+                ExprKind::Let(binding) => binding.value.ty.as_ref().unwrap().to_string(),
+                ExprKind::Call { arguments, .. } => arguments
+                    .iter()
+                    .map(|argument| argument.ty.as_ref().unwrap().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                ExprKind::Return(Some(value)) => value.ty.as_ref().unwrap().to_string(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        // Literals that aren't constrained are `i32` or `f64`
+        assert_eq!(types, ["u64", "u64", "f64", "i32", "u8, f32", "i64"]);
+        type_check("fn foo() -> i64 { let x = 3_000_000_000; x }").unwrap();
+
+        let source = "fn foo() -> u8 { let x = 1; if true { x } else { 256 } }";
+        let Err(TypeCheckError::LiteralOutOfRange { ty, span }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(ty.to_string(), "u8");
+        assert_eq!(&source[span], "256");
+        // The literal is only known to be unsigned after it is negated
+        let source = "fn foo(a: u32) -> u32 { let x = -1; a + x }";
+        let Err(TypeCheckError::InvalidOperand { op, span, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(op, "-");
+        assert_eq!(&source[span], "-1");
+        let source = "fn foo(a: f32) -> f32 { a * 2 }";
+        let Err(TypeCheckError::IncompatibleTypes {
+            expected, found, ..
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(
+            (expected.to_string(), found.to_string()),
+            ("f32".into(), "{integer}".into())
+        );
+        let source = "fn foo() { let x = 1.5; x << 1; }";
+        let Err(TypeCheckError::InvalidOperand { ty, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(ty.to_string(), "{float}");
+    }
+
+    #[test]
+    fn test_casts() {
+        type_check(
+            "fn foo(a: u8, b: f32, c: bool) -> i64 { (a as i64 + b as i64) * c as i64 - -(a as i64) }",
+        )
+        .unwrap();
+        type_check("fn foo(a: i64) -> f32 { (a as f64 * 1.5) as f32 + 1 as f32 }").unwrap();
+        // Unconstrained literals get the type they are cast to
+        let source = "fn foo() -> i32 { 300 as u8 as i32 }";
+        let Err(TypeCheckError::LiteralOutOfRange { ty, span }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(ty.to_string(), "u8");
+        assert_eq!(&source[span], "300");
+        let source = "fn foo(a: bool) -> f64 { a as f64 }";
+        let Err(TypeCheckError::InvalidCast { from, to, span }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(from, Type::Bool);
+        assert_eq!(to, Type::Number(NumberType::F64));
+        assert_eq!(&source[span], "a as f64");
+        let source = "fn foo(a: i32) -> bool { a as bool }";
+        let Err(TypeCheckError::InvalidCast { .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+    }
+
+    #[test]
+    fn test_structs() {
+        type_check(
+            "fn foo(a: Point) -> Line { let mut b = Line { end: a, start: a }; b.end.x += 1; b } \
+             struct Line { start: Point, end: Point } \
+             struct Point { x: i32, y: f32 }",
+        )
+        .unwrap();
+        // The fields of the literal are inferred from the declaration
+        type_check("struct A { a: u8 } fn foo() -> u8 { let a = A { a: 255 }; a.a }").unwrap();
+
+        let source = "struct A { a: B }";
+        let Err(TypeCheckError::UnknownType(name, span)) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(name, "B");
+        assert_eq!(&source[span], "B");
+        let source = "struct A { a: i32 } fn foo(a: A) -> i32 { a.b }";
+        let Err(TypeCheckError::UnknownField { ty, field, span }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(ty.to_string(), "A");
+        assert_eq!(field, "b");
+        assert_eq!(&source[span], "b");
+        let source = "struct A { a: i32, b: i32, c: i32 } fn foo() -> A { A { b: 1 } }";
+        let Err(TypeCheckError::MissingFields { fields, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(fields, ["a", "c"]);
+        let source = "struct A { a: i32 } fn foo() -> A { A { a: 1, a: 2 } }";
+        let Err(TypeCheckError::DuplicateField { span, first, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[first], "a: 1");
+        assert_eq!(&source[span], "a: 2");
+        let source = "struct A { a: i32 } fn foo(a: A) { a.a = 1; }";
+        let Err(TypeCheckError::AssignToImmutable { name, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(name, "a");
+    }
+
+    #[test]
+    fn test_tuples() {
+        type_check(
+            "fn foo(a: (i32, bool)) -> (bool, (i32, f64)) { let mut t = (a.1, (a.0, 1.5)); \
+             t.1.0 += 1; let (b, (c, _)) = t; (b, (c, 2.0)) }",
+        )
+        .unwrap();
+        type_check("fn foo() { let () = (); let (a,) = (1,); let b: i8 = a; }").unwrap();
+
+        let source = "fn foo(a: (i32, i32)) { let (b, c, d) = a; }";
+        let Err(TypeCheckError::MismatchedPattern {
+            expected,
+            found,
+            span,
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(expected.to_string(), "(i32, i32)");
+        assert_eq!(found, "a tuple with 3 elements");
+        assert_eq!(&source[span], "(b, c, d)");
+        let source = "fn foo(a: (i32, i32)) { let (b, b) = a; }";
+        let Err(TypeCheckError::DuplicateBinding { name, span, first }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(name, "b");
+        assert_eq!(first.start, 29);
+        assert_eq!(span.start, 32);
+        let source = "fn foo(a: (i32, i32)) -> i32 { a.2 }";
+        let Err(TypeCheckError::UnknownField { field, span, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(field, "2");
+        assert_eq!(&source[span], "2");
+        let source = "fn foo(a: (i32, i32)) { a.0 = 1; }";
+        let Err(TypeCheckError::AssignToImmutable { .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+
+        // A mismatch in a tuple literal points at the element
+        let source = "fn foo() -> (i32, (bool, i8)) { (1, (true, 2u8)) }";
+        let Err(TypeCheckError::IncompatibleTypes {
+            expected,
+            found,
+            span,
+            declared,
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(expected.to_string(), "i8");
+        assert_eq!(found.to_string(), "u8");
+        assert_eq!(&source[span], "2u8");
+        assert_eq!(declared, None);
+        // The elements that matched are resolved
+        let source = "fn foo() { let a = (1, 2); let b: (i32, bool) = a; }";
+        let Err(TypeCheckError::IncompatibleTypes { found, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(found.to_string(), "(i32, {integer})");
+    }
+
+    #[test]
+    fn test_recursive_struct() {
+        let source = "struct A { b: B } struct B { a: A }";
+        let Err(TypeCheckError::RecursiveType { name, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(name, "A");
+        let Err(TypeCheckError::DuplicateType { .. }) = type_check("struct A {} struct A {}")
+        else {
+            panic!("Expected an error");
+        };
+    }
+
+    #[test]
+    fn test_arrays() {
+        type_check(
+            "fn foo(a: [i32; 3], i: usize) -> usize { let mut b = [a, [0; 3]]; b[1][i] += a[0]; \
+             let s: &[[i32; 3]] = &b; let e: [bool; 0] = []; s[0][2]; s.len() + b[0].len() }",
+        )
+        .unwrap();
+        // The elements are inferred like the elements of a tuple
+        let mut ast = type_check("fn foo() -> u8 { let a = [1, 2]; a[0] }").unwrap();
+        let TopLevelKind::Expr(expr) = &mut ast[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            panic!("Expected a function definition");
+        };
+        let ExprKind::Let(binding) = &function.body.statements[0].kind else {
+            panic!("Expected a let statement");
+        };
+        assert_eq!(binding.value.ty.as_ref().unwrap().to_string(), "[u8; 2]");
+
+        let source = "fn foo() { [1, true]; }";
+        let Err(TypeCheckError::IncompatibleTypes { span, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[span], "true");
+        let source = "fn foo(a: [i32; 2]) -> [i32; 3] { a }";
+        let Err(TypeCheckError::IncompatibleTypes { .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        let source = "fn foo(a: [i32; 2], i: u32) -> i32 { a[i] }";
+        let Err(TypeCheckError::IncompatibleTypes { expected, span, .. }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(expected, Type::Number(NumberType::USIZE));
+        assert_eq!(&source[span], "i");
+        let source = "fn foo(a: (i32, i32)) -> i32 { a[0] }";
+        let Err(TypeCheckError::NotIndexable { ty, span }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(ty.to_string(), "(i32, i32)");
+        assert_eq!(&source[span], "a");
+        let source = "fn foo(a: [i32; 2]) -> usize { a.size() }";
+        let Err(TypeCheckError::UnknownMethod { method, span, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(method, "size");
+        assert_eq!(&source[span], "size");
+        let source = "fn foo(a: [i32; 2]) -> usize { a.len(1) }";
+        let Err(TypeCheckError::ArgumentCount { declared: None, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        let source = "fn foo(a: [i32; 2]) { a[0] = 1; }";
+        let Err(TypeCheckError::AssignToImmutable { .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        let source = "fn foo(a: [i32; 2]) { let mut s = &a; s[0] = 1; }";
+        let Err(TypeCheckError::WriteThroughImmutable { ty, span }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(ty.to_string(), "&[i32]");
+        assert_eq!(&source[span], "s[0]");
+        type_check("fn foo() { let mut a = [1, 2]; let s = &mut a; s[0] = 1; }").unwrap();
+        let source = "struct A { a: [A; 1] }";
+        let Err(TypeCheckError::RecursiveType { .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        // A slice only points to its elements, so it can contain the struct it's in
+        type_check("struct A { a: &[A] }").unwrap();
+    }
+
+    #[test]
+    fn test_references() {
+        type_check(
+            "struct P { x: i32 } fn foo(p: P, q: &mut P) -> i32 { let mut p = p; let r = &mut p; \
+             (*r).x += 1; (*q).x = *&2; let s: &P = q; let c = &mut 3; *c = 4; (*s).x + (*r).x }",
+        )
+        .unwrap();
+        type_check(
+            "fn foo() -> i32 { let mut a = 0; let p = &mut a as *mut i32; \
+             let q = p as *const u8 as *const i32; unsafe { *p = 1; *q } }",
+        )
+        .unwrap();
+
+        let source = "fn foo(a: i32) { &mut a; }";
+        let Err(TypeCheckError::BorrowImmutable { name, span, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(name, "a");
+        assert_eq!(&source[span], "&mut a");
+        let source = "fn foo(a: &i32) { *a = 1; }";
+        let Err(TypeCheckError::WriteThroughImmutable { ty, span }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(ty.to_string(), "&i32");
+        assert_eq!(&source[span], "*a");
+        let source = "fn foo(a: &i32) -> &mut i32 { a }";
+        let Err(TypeCheckError::IncompatibleTypes { .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        let source = "fn foo(a: &i32) -> *mut i32 { a as *mut i32 }";
+        let Err(TypeCheckError::InvalidCast { .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        let source = "fn foo(a: i32) -> i32 { *a }";
+        let Err(TypeCheckError::InvalidOperand { op, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(op, "*");
+        let source = "fn foo(p: *const i32) -> i32 { unsafe { 1 }; *p }";
+        let Err(TypeCheckError::UnsafeDeref(span)) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[span], "*p");
+        // References only point to their values, so they can be part of a recursive struct
+        type_check("struct A { a: &A, b: *mut A }").unwrap();
+    }
+
+    #[test]
+    fn test_borrows() {
+        // A borrow ends at the last use of the reference
+        type_check(
+            "fn foo() -> i32 { let mut x = 1; let r = &mut x; *r += 1; let s = &x; \
+             let y = *s; x = 2; x + y }",
+        )
+        .unwrap();
+        type_check(
+            "fn first(s: &[i32]) -> &i32 { &s[0] } fn foo(a: &mut i32) -> &mut i32 { a } \
+             fn bar() { let mut x = 0; let mut r = &0; while x < 3 { r = &x; x += 1; } }",
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_write_while_borrowed() {
+        let source = "fn foo() -> i32 { let mut x = 1; let r = &x; x = 2; *r }";
+        let Err(TypeCheckError::BorrowConflict {
+            access,
+            span,
+            borrow,
+            later,
+            ..
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(access, Access::Write);
+        assert_eq!(&source[span.start..], "x = 2; *r }");
+        assert_eq!(&source[span], "x");
+        assert_eq!(&source[borrow], "&x");
+        assert_eq!(&source[later], "r");
+        // The borrow is still held by the reference in the next iteration of the loop
+        let source = "fn foo() { let mut x = 0; let mut r = &0; loop { x += 1; *r; r = &x; } }";
+        let Err(TypeCheckError::BorrowConflict {
+            access: Access::Write,
+            span,
+            ..
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[span.start..], "x += 1; *r; r = &x; } }");
+        let source = "struct H { r: &i32 } fn foo() -> i32 { let mut x = 1; let h = H { r: &x }; \
+                      x = 2; *h.r }";
+        let Err(TypeCheckError::BorrowConflict { span, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[span.start..], "x = 2; *h.r }");
+    }
+
+    #[test]
+    fn test_read_while_borrowed() {
+        let source = "fn foo() -> i32 { let mut x = 1; let a = &mut x; x + *a }";
+        let Err(TypeCheckError::BorrowConflict {
+            access: Access::Read,
+            span,
+            borrow,
+            ..
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[span.start..], "x + *a }");
+        assert_eq!(&source[span], "x");
+        assert_eq!(&source[borrow], "&mut x");
+        // The arguments of a call are used by the call
+        let source = "fn f(a: &mut i32, b: i32) {} fn foo() { let mut x = 1; f(&mut x, x); }";
+        let Err(TypeCheckError::BorrowConflict {
+            access: Access::Read,
+            span,
+            later,
+            ..
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[span.start..], "x); }");
+        assert_eq!(&source[later], "f(&mut x, x)");
+    }
+
+    #[test]
+    fn test_borrow_while_borrowed() {
+        // A conflicting borrow points at the whole `&` expression
+        let source = "fn foo() -> i32 { let mut x = 1; let a = &mut x; let b = &x; *a + *b }";
+        let Err(TypeCheckError::BorrowConflict {
+            access,
+            borrow_mutable: true,
+            span,
+            borrow,
+            ..
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(access, Access::Borrow { mutable: false });
+        assert_eq!(&source[span], "&x");
+        assert_eq!(&source[borrow], "&mut x");
+        let source = "fn foo() -> i32 { let mut x = 1; let a = &x; let b = &mut x; *a + *b }";
+        let Err(TypeCheckError::BorrowConflict {
+            access,
+            borrow_mutable: false,
+            span,
+            borrow,
+            ..
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(access, Access::Borrow { mutable: true });
+        assert_eq!(&source[span], "&mut x");
+        assert_eq!(&source[borrow], "&x");
+    }
+
+    #[test]
+    fn test_borrow_outlives_value() {
+        let source = "fn foo() -> i32 { let mut r = &0; { let b = 1; r = &b; } *r }";
+        let Err(TypeCheckError::BorrowOutlivesValue {
+            name,
+            borrow,
+            dropped,
+            later: Some(later),
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(name, "b");
+        assert_eq!(&source[borrow], "&b");
+        assert_eq!(&source[dropped.start..], "} *r }");
+        assert_eq!(&source[later], "r");
+        let source = "fn foo() { let r = { let b = 1; &b }; }";
+        let Err(TypeCheckError::BorrowOutlivesValue { later: None, .. }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+    }
+
+    #[test]
+    fn test_return_local_reference() {
+        let source = "fn foo(a: i32) -> &i32 { &a }";
+        let Err(TypeCheckError::ReturnLocalReference { name, borrow, span }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(name, "a");
+        assert_eq!(&source[borrow], "&a");
+        assert_eq!(&source[span], "&a");
+        // The reference returned by a call might borrow from its arguments
+        let source = "fn id(a: &i32) -> &i32 { a } fn foo() -> &i32 { let x = 1; return id(&x); }";
+        let Err(TypeCheckError::ReturnLocalReference { span, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[span], "id(&x)");
+    }
+
+    #[test]
+    fn test_strings() {
+        // String literals are constants, so they can be returned from the function that has them
+        type_check(
+            "fn greet() -> &str { \"hi\\n\" } fn foo() -> usize { let s = greet(); \
+             let b: &[u8] = s.as_bytes(); let p = s as *const u8; s.len() + b.len() + b\"ab\".len() }",
+        )
+        .unwrap();
+
+        let source = "fn foo() -> &str { b\"a\" }";
+        let Err(TypeCheckError::IncompatibleTypes { expected, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(expected.to_string(), "&str");
+        let source = "fn foo() -> *mut u8 { \"a\" as *mut u8 }";
+        let Err(TypeCheckError::InvalidCast { .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        let source = "fn foo() -> usize { b\"a\".as_bytes().len() }";
+        let Err(TypeCheckError::UnknownMethod { ty, method, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(ty.to_string(), "&[u8]");
+        assert_eq!(method, "as_bytes");
+    }
+
+    const SHAPE: &str = "enum Shape { Circle(f64), Rect { w: f64, h: f64 }, Empty } ";
+
+    #[test]
+    fn test_enums() {
+        type_check(&format!(
+            "{SHAPE} fn foo(r: f64) -> Shape {{ let s = Shape::Rect {{ h: r, w: 2.0 }}; \
+             Shape::Circle(1.0); Shape::Empty; s }}"
+        ))
+        .unwrap();
+        let Err(TypeCheckError::RecursiveType { name, .. }) =
+            type_check("enum List { Cons(i32, List), Nil }")
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(name, "List");
+        let Err(TypeCheckError::DuplicateType { .. }) = type_check("struct A {} enum A {}") else {
+            panic!("Expected an error");
+        };
+        let Err(TypeCheckError::DuplicateVariant { name, .. }) = type_check("enum A { B, B(i32) }")
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(name, "B");
+
+        let source = format!("{SHAPE} fn foo() {{ Shape::Square(1.0); }}");
+        let Err(TypeCheckError::UnknownVariant { variant, span, .. }) = type_check(&source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(variant, "Square");
+        assert_eq!(&source[span], "Square");
+        let source = format!("{SHAPE} fn foo() {{ Shape::Circle {{ r: 1.0 }}; }}");
+        let Err(TypeCheckError::VariantKindMismatch { name, kind, .. }) = type_check(&source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!((name.as_str(), kind), ("Shape::Circle", VariantKind::Tuple));
+        let source = format!("{SHAPE} fn foo() {{ Shape::Circle(1.0, 2.0); }}");
+        let Err(TypeCheckError::FieldCount {
+            expected, found, ..
+        }) = type_check(&source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!((expected, found), (1, 2));
+        let source = format!("{SHAPE} fn foo() {{ Shape::Rect {{ w: 1.0 }}; }}");
+        let Err(TypeCheckError::MissingFields { name, fields, .. }) = type_check(&source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(name, "Shape::Rect");
+        assert_eq!(fields, ["h"]);
+        let source = format!("{SHAPE} fn foo() {{ Shape::Circle(true); }}");
+        let Err(TypeCheckError::IncompatibleTypes { found, .. }) = type_check(&source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(found, Type::Bool);
+    }
+
+    #[test]
+    fn test_match() {
+        type_check(&format!(
+            "{SHAPE} fn foo(s: Shape) -> f64 {{ match s {{ Shape::Circle(r) => r * r, \
+             Shape::Rect {{ w, h }} => {{ w * h }} Shape::Empty => return 0.0, }} }}"
+        ))
+        .unwrap();
+        type_check(
+            "fn foo(a: (i8, bool)) -> i8 { match a { (-128, _) => 0, (x, true) => x, (_, false) => 1 } }",
+        )
+        .unwrap();
+        // A match where every arm returns doesn't need a value
+        type_check("fn foo(a: bool) -> i32 { match a { true => return 1, false => return 2 } }")
+            .unwrap();
+
+        let source = format!(
+            "{SHAPE} fn foo(s: Shape) {{ match s {{ Shape::Circle(_) => (), Shape::Empty => () }} }}"
+        );
+        let Err(TypeCheckError::NonExhaustive { missing, span }) = type_check(&source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(missing, "Shape::Rect { w: _, h: _ }");
+        assert_eq!(&source[span], "s");
+        let source = "fn foo(a: (bool, bool)) { match a { (true, _) => (), (_, true) => () } }";
+        let Err(TypeCheckError::NonExhaustive { missing, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(missing, "(false, false)");
+        let source = "fn foo(a: i32) { match a { 0 => (), 1 => () } }";
+        let Err(TypeCheckError::NonExhaustive { missing, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(missing, "_");
+
+        let source = "fn foo(a: (bool, i32)) { match a { (_, _) => (), (true, 1) => () } }";
+        let mut ast = Parser::with_source(source).parse().into_result().unwrap();
+        let mut type_checker = TypeChecker::new();
+        type_checker.type_check(&mut ast).unwrap();
+        let [TypeCheckWarning::UnreachablePattern(span)] = type_checker.warnings() else {
+            panic!("Expected a warning");
+        };
+        assert_eq!(&source[span.clone()], "(true, 1)");
+        let source = "fn foo(a: u8) { match a { 256 => (), _ => () } }";
+        let Err(TypeCheckError::LiteralOutOfRange { span, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[span], "256");
+        let source = "fn foo(a: u8) { match a { -1 => (), _ => () } }";
+        let Err(TypeCheckError::InvalidOperand { .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        let source = "fn foo(a: f64) { match a { 1.0 => (), _ => () } }";
+        let Err(TypeCheckError::Unsupported(..)) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        let source = "fn foo(a: bool) -> i32 { match a { true => 1, false => false } }";
+        let Err(TypeCheckError::IncompatibleTypes { found, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(found, Type::Bool);
+
+        let source = format!("{SHAPE} fn foo(s: Shape) {{ let Shape::Circle(r) = s; }}");
+        let Err(TypeCheckError::RefutablePattern { missing, span }) = type_check(&source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(missing, "Shape::Rect { w: _, h: _ }");
+        assert_eq!(&source[span], "Shape::Circle(r)");
+        // A variant is irrefutable if it is the only one
+        type_check("enum A { B(i32) } fn foo(a: A) -> i32 { let A::B(b) = a; b }").unwrap();
+    }
+
+    #[test]
+    fn test_bool() {
+        let ast = type_check(
+            "fn foo(a: i32, b: bool) -> i32 { let mut c = a < 1 && !b || true; c &= a != 2; return a; }",
+        )
+        .unwrap();
+        let TopLevelKind::Expr(expr) = &ast[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            unreachable!()
+        };
+        let ExprKind::Let(binding) = &function.body.statements[0].kind else {
+            unreachable!()
+        };
+        assert_eq!(binding.value.ty, Some(Type::Bool));
+
+        let source = "fn foo(a: i32) -> i32 { return a && true; }";
+        let Err(TypeCheckError::InvalidOperand { op, ty, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!((op.as_str(), ty), ("&&", Type::Number(NumberType::I32)));
+        let source = "fn foo(a: bool) -> bool { return a < false; }";
+        let Err(TypeCheckError::InvalidOperand { op, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(op, "<");
+    }
+
+    #[test]
+    fn test_if() {
+        type_check(
+            "fn foo(a: i32) -> i32 { let x = if a > 0 { 1 } else if a < 0 { return 2; } else { a }; \
+             if x == 1 { return x; } else { return 0; } }",
+        )
+        .unwrap();
+
+        let source = "fn foo(a: i32) -> i32 { if a { return 1; } return 0; }";
+        let Err(TypeCheckError::IncompatibleTypes {
+            expected, found, ..
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(
+            (expected, found),
+            (Type::Bool, Type::Number(NumberType::I32))
+        );
+        let source = "fn foo(a: bool) -> i32 { let x = if a { 1 } else { false }; return x; }";
+        let Err(TypeCheckError::IncompatibleTypes { span, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[span], "false");
+        // Without an else branch, the value of the `if` is `()`
+        let source = "fn foo(a: bool) -> i32 { let x = if a { 1 }; return x; }";
+        let Err(TypeCheckError::IncompatibleTypes { expected, span, .. }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(expected, Type::empty());
+        assert_eq!(&source[span], "1");
+    }
+
+    #[test]
+    fn test_tail_return() {
+        let ast = type_check(
+            "fn foo(a: i32) -> i32 { let x = { let a = a > 0; a }; if x { { a } } else { 0 } }",
+        )
+        .unwrap();
+        let TopLevelKind::Expr(expr) = &ast[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            unreachable!()
+        };
+        // The shadowing binding is only in scope inside of the block
+        assert_eq!(function.body.statements[0].ty, Some(Type::empty()));
+        let ExprKind::Let(binding) = &function.body.statements[0].kind else {
+            unreachable!()
+        };
+        assert_eq!(binding.value.ty, Some(Type::Bool));
+        type_check("fn foo(a: i32) -> i32 { { return a; } }").unwrap();
+
+        let source = "fn foo(a: i32) -> i32 { a == 1 }";
+        let Err(TypeCheckError::IncompatibleTypes {
+            expected,
+            found,
+            span,
+            declared: Some(declared),
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(
+            (expected, found),
+            (Type::Number(NumberType::I32), Type::Bool)
+        );
+        assert_eq!(&source[span], "a == 1");
+        assert_eq!(&source[declared], "i32");
+    }
+
+    #[test]
+    fn test_return() {
+        type_check("fn foo(a: bool) -> i32 { if a { return 1; } let x: i32 = return 2; }").unwrap();
+        type_check("fn foo(a: bool) { if a { return; } }").unwrap();
+
+        for (source, expected, found, span, declared) in [
+            ("fn foo() -> i32 { return; }", "i32", "()", "return", "i32"),
+            (
+                "fn foo() -> i32 { return true; }",
+                "i32",
+                "bool",
+                "true",
+                "i32",
+            ),
+            ("fn foo() { return 1u8; }", "()", "u8", "1u8", "fn foo()"),
+            (
+                "fn foo() -> u8 { loop { return 1.5; } }",
+                "u8",
+                "{float}",
+                "1.5",
+                "u8",
+            ),
+        ] {
+            let Err(TypeCheckError::IncompatibleTypes {
+                expected: expected_ty,
+                found: found_ty,
+                span: found_span,
+                declared: Some(declared_span),
+            }) = type_check(source)
+            else {
+                panic!("Expected an error for {source}");
+            };
+            assert_eq!(expected_ty.to_string(), expected);
+            assert_eq!(found_ty.to_string(), found);
+            assert_eq!(&source[found_span], span);
+            assert_eq!(&source[declared_span], declared);
+        }
+    }
+
+    #[test]
+    fn test_unit_return() {
+        type_check("fn foo(a: i32) { if a > 0 { return; } bar(a); } fn bar(a: i32) { foo(a - 1) }")
+            .unwrap();
+        type_check("fn foo() -> () {}").unwrap();
+
+        let source = "fn foo(a: bool) -> i32 {\n    if a {\n        return 1;\n    }\n}";
+        let Err(TypeCheckError::MissingReturn {
+            expected,
+            span,
+            declared,
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(expected, Type::Number(NumberType::I32));
+        assert_eq!(span, source.len() - 1..source.len());
+        assert_eq!(&source[declared], "i32");
+        let source = "fn foo() { 1 }";
+        let Err(TypeCheckError::IncompatibleTypes { expected, span, .. }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(expected, Type::empty());
+        assert_eq!(&source[span], "1");
+    }
+
+    #[test]
+    fn test_loops() {
+        let ast = type_check(
+            "fn foo(a: i32) -> i32 { let x = 'outer: loop { while true { if a > 0 { break 'outer a; } \
+             continue 'outer; } break 1; }; loop { return x; } }",
+        )
+        .unwrap();
+        let TopLevelKind::Expr(expr) = &ast[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            unreachable!()
+        };
+        let ExprKind::Let(binding) = &function.body.statements[0].kind else {
+            unreachable!()
+        };
+        assert_eq!(binding.value.ty, Some(Type::Number(NumberType::I32)));
+        // A loop without a break never finishes
+        assert_eq!(function.body.tail.as_ref().unwrap().ty, Some(Type::Never));
+
+        let source = "fn foo() -> i32 { if true { break; } return 1; }";
+        let Err(TypeCheckError::OutsideOfLoop { keyword, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(keyword, "break");
+        let source = "fn foo() -> i32 { 'a: loop { loop { continue 'b; } } }";
+        let Err(TypeCheckError::UnknownLabel(label, _)) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(label, "b");
+        let source = "fn foo() -> i32 { while true { break 1; } return 1; }";
+        let Err(TypeCheckError::BreakWithValue(span)) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[span], "break 1");
+        let source = "fn foo() -> i32 { let x = loop { break 1; break true; }; return x; }";
+        let Err(TypeCheckError::IncompatibleTypes { span, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[span], "true");
+    }
+
+    #[test]
+    fn test_calls() {
+        // Functions can be called before they are defined, and recursively
+        let ast = type_check(
+            "fn foo(a: i32) -> i32 { baz(false, a); return foo(a - 1) + baz(true, a); } \
+             fn baz(b: bool, a: i32) -> i32 { if b { return a; } return 0; }",
+        )
+        .unwrap();
+        let TopLevelKind::Expr(expr) = &ast[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &expr.kind else {
+            unreachable!()
+        };
+        assert_eq!(
+            function.body.statements[0].ty,
+            Some(Type::Number(NumberType::I32))
+        );
+
+        let source = "fn foo() -> i32 { return bar(); }";
+        let Err(TypeCheckError::UnknownFunction(name, span)) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(name, "bar");
+        assert_eq!(&source[span], "bar");
+        let source = "fn foo(a: i32) -> i32 { return foo(1, 2); }";
+        let Err(TypeCheckError::ArgumentCount {
+            expected,
+            found,
+            span,
+            declared,
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!((expected, found), (1, 2));
+        assert_eq!(&source[span], "foo(1, 2)");
+        assert_eq!(&source[declared.unwrap()], "fn foo(a: i32) -> i32");
+        let source = "fn foo(a: i32) -> i32 { return foo(a == 1); }";
+        let Err(TypeCheckError::IncompatibleTypes { span, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[span], "a == 1");
+        let source = "fn foo() -> i32 { return 1; } fn foo() -> i32 { return 2; }";
+        let Err(TypeCheckError::DuplicateFunction { span, first, .. }) = type_check(source) else {
+            panic!("Expected an error");
+        };
+        assert_eq!((span.start, first.start), (30, 0));
+    }
+
+    #[test]
+    fn test_assign_immutable() {
+        let source = "fn foo(a: i32) -> i32 { let x = 1; x = 2; return x; }";
+        let Err(TypeCheckError::AssignToImmutable {
+            name,
+            span,
+            declared,
+            argument,
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(name, "x");
+        assert_eq!(&source[span], "x = 2");
+        assert_eq!(&source[declared], "let x = 1");
+        assert!(!argument);
+
+        let source = "fn foo(a: i32) -> i32 { a -= 1; return a; }";
+        let Err(TypeCheckError::AssignToImmutable {
+            declared, argument, ..
+        }) = type_check(source)
+        else {
+            panic!("Expected an error");
+        };
+        assert_eq!(&source[declared], "a: i32");
+        assert!(argument);
+    }
+
+    #[test]
+    fn test_unsupported() {
+        // The parser only accepts functions at the top level, so the nested function is moved
+        // into the body of the other one
+        let source = "fn foo() -> i32 { return 1; } fn bar() {}";
+        let mut ast = Parser::with_source(source).parse().into_result().unwrap();
+        let TopLevelKind::Expr(nested) = ast.pop().unwrap().kind else {
+            panic!("Expected an expression");
+        };
+        let TopLevelKind::Expr(function) = &mut ast[0].kind else {
+            panic!("Expected an expression");
+        };
+        let ExprKind::FunctionDef(function) = &mut function.kind else {
+            panic!("Expected a function");
+        };
+        function.body.statements.insert(0, *nested);
+        let Err(TypeCheckError::Unsupported(construct, span)) =
+            TypeChecker::new().type_check(&mut ast)
+        else {
+            panic!("Expected an unsupported error");
+        };
+        assert_eq!(construct, "nested functions");
+        assert_eq!(&source[span], "fn bar() {}");
+    }
 }
@@ -1,4 +1,6 @@
-use crate::{Block, BlockId, Function, Instruction, Module, TempVar, TempVarId, Type};
+use crate::{
+    Block, BlockId, Function, Global, GlobalId, Instruction, Module, TempVar, TempVarId, Type,
+};
 use ascii::AsciiString;
 use std::collections::HashMap;
 
@@ -8,6 +10,7 @@ pub struct ModuleBuilder {
     variables: HashMap<BlockId, HashMap<TempVarId, TempVar>>,
     blocks: HashMap<BlockId, Block>,
     functions: Vec<Function>,
+    globals: Vec<Global>,
 }
 
 impl ModuleBuilder {
@@ -17,6 +20,7 @@ impl ModuleBuilder {
             variables: HashMap::new(),
             blocks: HashMap::new(),
             functions: Vec::new(),
+            globals: Vec::new(),
         }
     }
 
@@ -86,6 +90,14 @@ impl ModuleBuilder {
         self.blocks.get_mut(block).unwrap()
     }
 
+    /// Whether the block already ends with a terminator, so no more instructions can be added
+    pub fn is_terminated(&self, block: &BlockId) -> bool {
+        self.get_block(block)
+            .instructions
+            .last()
+            .is_some_and(|instruction| instruction.is_terminator())
+    }
+
     pub fn get_var_type(&self, block: &BlockId, id: &TempVarId) -> Type {
         self.variables.get(block).unwrap()[id].ty.clone()
     }
@@ -106,6 +118,19 @@ impl ModuleBuilder {
         self.functions.push(function);
     }
 
+    /// Adds a global constant with the given bytes, constants with the same bytes share a global
+    pub fn build_global(&mut self, data: Vec<u8>) -> GlobalId {
+        if let Some(global) = self.globals.iter().find(|global| global.data == data) {
+            return global.id.clone();
+        }
+        let id = GlobalId(self.globals.len());
+        self.globals.push(Global {
+            id: id.clone(),
+            data,
+        });
+        id
+    }
+
     pub fn build_instruction(&mut self, block: &BlockId, instruction: Instruction) {
         self.get_block_mut(block).instructions.push(instruction);
     }
@@ -115,6 +140,7 @@ impl ModuleBuilder {
             name,
             blocks: self.blocks.into_iter().map(|(i, b)| (i, b)).collect(),
             functions: self.functions,
+            globals: self.globals,
         }
     }
 }
@@ -3,6 +3,7 @@ use std::{collections::HashMap, fmt::Write};
 /// RXIR Representation:
 /// - variables prefixed with '%' are temporary variables
 /// - variables prefixed with '@' are block labels
+/// - variables prefixed with '$' are global constants
 mod builder;
 mod operand;
 mod pass;
@@ -39,6 +40,16 @@ impl ToString for BlockId {
     }
 }
 
+/// The id of a global constant in a module
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GlobalId(pub usize);
+
+impl ToString for GlobalId {
+    fn to_string(&self) -> String {
+        format!("${}", self.0)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct TempVar {
     ty: Type,
@@ -49,11 +60,17 @@ pub struct Module {
     pub name: AsciiString,
     pub blocks: HashMap<BlockId, Block>,
     pub functions: Vec<Function>,
+    /// The constants that are stored in read-only memory, indexed by their id
+    pub globals: Vec<Global>,
 }
 
 impl ToString for Module {
     fn to_string(&self) -> String {
         let mut result = format!("module {}\n", self.name);
+        for global in &self.globals {
+            result.push_str(&global.to_string());
+            result.push('\n');
+        }
         for function in &self.functions {
             result.push_str(&function.to_string(self));
         }
@@ -63,6 +80,40 @@ impl ToString for Module {
 
 impl Module {}
 
+/// Constant bytes in read-only memory, like the contents of a string literal. Operands refer to
+/// the global by a pointer to its first byte
+#[derive(Debug, Clone)]
+pub struct Global {
+    pub id: GlobalId,
+    pub data: Vec<u8>,
+}
+
+impl Global {
+    /// The type of the memory that holds the global
+    pub fn ty(&self) -> Type {
+        Type::Array(Box::new(Type::Unsigned(8)), self.data.len() as u64)
+    }
+}
+
+impl ToString for Global {
+    fn to_string(&self) -> String {
+        // Bytes that aren't printable are written as two hex digits after a backslash
+        let data: String = self
+            .data
+            .iter()
+            .map(|&byte| match byte {
+                b' '..=b'~' if byte != b'"' && byte != b'\\' => (byte as char).to_string(),
+                _ => format!("\\{byte:02X}"),
+            })
+            .collect();
+        format!(
+            "{} = constant {} c\"{data}\"",
+            self.id.to_string(),
+            self.ty()
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Function {
     pub signature: AsciiString,
@@ -116,8 +167,22 @@ impl ToString for Block {
 #[derive(Debug, Clone)]
 pub enum Type {
     Void,
-    Signed32,
+    /// A single bit, which is either 0 or 1
+    Bool,
+    /// A signed integer with the given number of bits
+    Signed(u32),
+    /// An unsigned integer with the given number of bits
+    Unsigned(u32),
+    /// A floating point number with the given number of bits, either 32 or 64
+    Float(u32),
     Pointer(Box<Type>),
+    /// A struct with the fields in the given order, laid out like a C struct by the backend
+    Struct(Vec<Type>),
+    /// Memory that holds one of the members at a time, every member starts at the beginning and
+    /// the union is as large as the largest member
+    Union(Vec<Type>),
+    /// A fixed number of elements of the same type, laid out one after another
+    Array(Box<Type>, u64),
 }
 
 impl Type {
@@ -130,8 +195,20 @@ impl std::fmt::Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Type::Void => f.write_str("void"),
-            Type::Signed32 => f.write_str("i32"),
+            Type::Bool => f.write_str("bool"),
+            Type::Signed(bits) => write!(f, "i{bits}"),
+            Type::Unsigned(bits) => write!(f, "u{bits}"),
+            Type::Float(bits) => write!(f, "f{bits}"),
             Type::Pointer(ty) => f.write_fmt(format_args!("{}*", *ty)),
+            Type::Struct(fields) => {
+                let fields: Vec<_> = fields.iter().map(ToString::to_string).collect();
+                write!(f, "{{{}}}", fields.join(", "))
+            }
+            Type::Union(members) => {
+                let members: Vec<_> = members.iter().map(ToString::to_string).collect();
+                write!(f, "union {{{}}}", members.join(", "))
+            }
+            Type::Array(ty, len) => write!(f, "[{len} x {ty}]"),
         }
     }
 }
@@ -147,14 +224,243 @@ pub enum Instruction {
     Return {
         value: Option<Operand>,
     },
+    /// Loads a value of the given type from the pointer in `src`
     Load {
         dest: TempVarId,
         src: TempVarId,
+        ty: Type,
     },
+    /// Stores a value into the pointer in `dest`
     Store {
         dest: TempVarId,
         src: Operand,
     },
+    /// An arithmetic operation, both operands must have the same type, which is also the type of
+    /// the result
+    Binary {
+        dest: TempVarId,
+        op: BinaryOp,
+        lhs: Operand,
+        rhs: Operand,
+    },
+    /// Compares two operands of the same type, the result is 1 if the comparison holds and 0
+    /// otherwise
+    Compare {
+        dest: TempVarId,
+        op: CompareOp,
+        lhs: Operand,
+        rhs: Operand,
+    },
+    Unary {
+        dest: TempVarId,
+        op: UnaryOp,
+        operand: Operand,
+    },
+    /// Converts a value to the type `ty`, `op` decides how the value is converted
+    Cast {
+        dest: TempVarId,
+        op: CastOp,
+        value: Operand,
+        ty: Type,
+    },
+    /// Gets a pointer to the field at `index` of the struct of type `ty` that `src` points to. If
+    /// `ty` is a union, this is a pointer to the member at `index`, which has the same address
+    FieldAddress {
+        dest: TempVarId,
+        src: TempVarId,
+        ty: Type,
+        index: u32,
+    },
+    /// Gets a pointer to the element at `index` of the elements of type `ty` that start at `src`.
+    /// The index isn't checked, it has to be in bounds of the elements
+    ElementAddress {
+        dest: TempVarId,
+        src: TempVarId,
+        ty: Type,
+        index: Operand,
+    },
+    /// Gets the value of the field at `index` of a struct value
+    ExtractField {
+        dest: TempVarId,
+        value: Operand,
+        index: u32,
+    },
+    /// Jumps to `then_block` if the boolean condition is true, and to `else_block` otherwise.
+    /// This has to be the last instruction of a block
+    Branch {
+        condition: Operand,
+        then_block: BlockId,
+        else_block: BlockId,
+    },
+    /// Jumps to the given block unconditionally, this has to be the last instruction of a block
+    Jump {
+        target: BlockId,
+    },
+    /// Jumps to the block of the case that equals the integer `value`, or to `default` if no case
+    /// does. The cases are the bits of constants with the type of `value`, this has to be the last
+    /// instruction of a block
+    Switch {
+        value: Operand,
+        default: BlockId,
+        cases: Vec<(u128, BlockId)>,
+    },
+    /// Marks the end of a block that can't be reached, like the default of a switch that covers
+    /// every value. This has to be the last instruction of a block
+    Unreachable,
+    /// Aborts the program, like when an index is out of bounds. This has to be the last
+    /// instruction of a block
+    Trap,
+    /// Selects the value from the block that was executed before the current one, every
+    /// predecessor of the block needs an incoming value. Phis have to be at the start of a block
+    Phi {
+        dest: TempVarId,
+        ty: Type,
+        incoming: Vec<(Operand, BlockId)>,
+    },
+    /// Calls a function in the module by its signature, functions returning void have no
+    /// destination
+    Call {
+        dest: Option<TempVarId>,
+        function: AsciiString,
+        arguments: Vec<Operand>,
+        return_ty: Type,
+    },
+}
+
+impl Instruction {
+    /// Whether the instruction ends a block
+    pub fn is_terminator(&self) -> bool {
+        matches!(
+            self,
+            Self::Return { .. }
+                | Self::Branch { .. }
+                | Self::Jump { .. }
+                | Self::Switch { .. }
+                | Self::Unreachable
+                | Self::Trap
+        )
+    }
+
+    /// Gets the blocks that the instruction can jump to
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Self::Branch {
+                then_block,
+                else_block,
+                ..
+            } => vec![then_block.clone(), else_block.clone()],
+            Self::Jump { target } => vec![target.clone()],
+            Self::Switch { default, cases, .. } => cases
+                .iter()
+                .map(|(_, block)| block.clone())
+                .chain(std::iter::once(default.clone()))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+}
+
+impl std::fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Add => f.write_str("add"),
+            Self::Sub => f.write_str("sub"),
+            Self::Mul => f.write_str("mul"),
+            Self::Div => f.write_str("div"),
+            Self::Rem => f.write_str("rem"),
+            Self::And => f.write_str("and"),
+            Self::Or => f.write_str("or"),
+            Self::Xor => f.write_str("xor"),
+            Self::Shl => f.write_str("shl"),
+            Self::Shr => f.write_str("shr"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl std::fmt::Display for CompareOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Eq => f.write_str("eq"),
+            Self::Ne => f.write_str("ne"),
+            Self::Lt => f.write_str("lt"),
+            Self::Le => f.write_str("le"),
+            Self::Gt => f.write_str("gt"),
+            Self::Ge => f.write_str("ge"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+impl std::fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Neg => f.write_str("neg"),
+            Self::Not => f.write_str("not"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CastOp {
+    /// Drops the high bits of an integer
+    Trunc,
+    /// Widens an integer by filling the new bits with zeros
+    ZExt,
+    /// Widens an integer by copying the sign bit into the new bits
+    SExt,
+    /// Converts a float to a signed integer, rounding towards zero
+    FpToSi,
+    /// Converts a float to an unsigned integer, rounding towards zero
+    FpToUi,
+    SiToFp,
+    UiToFp,
+    FpTrunc,
+    FpExt,
+}
+
+impl std::fmt::Display for CastOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Trunc => f.write_str("trunc"),
+            Self::ZExt => f.write_str("zext"),
+            Self::SExt => f.write_str("sext"),
+            Self::FpToSi => f.write_str("fptosi"),
+            Self::FpToUi => f.write_str("fptoui"),
+            Self::SiToFp => f.write_str("sitofp"),
+            Self::UiToFp => f.write_str("uitofp"),
+            Self::FpTrunc => f.write_str("fptrunc"),
+            Self::FpExt => f.write_str("fpext"),
+        }
+    }
 }
 
 impl ToString for Instruction {
@@ -165,7 +471,130 @@ impl ToString for Instruction {
                 None => "return void".to_string(),
                 Some(value) => format!("return {} {}", value.ty(), value.to_string()),
             },
-            Self::Load { .. } | Self::Store { .. } => unimplemented!(),
+            Self::Load { dest, src, ty } => {
+                format!("{} = load {}, {}", dest.to_string(), ty, src.to_string())
+            }
+            Self::Store { dest, src } => format!(
+                "store {} {}, {}",
+                src.ty(),
+                src.to_string(),
+                dest.to_string()
+            ),
+            Self::Binary { dest, op, lhs, rhs } => format!(
+                "{} = {op} {} {}, {}",
+                dest.to_string(),
+                lhs.ty(),
+                lhs.to_string(),
+                rhs.to_string()
+            ),
+            Self::Compare { dest, op, lhs, rhs } => format!(
+                "{} = cmp {op} {} {}, {}",
+                dest.to_string(),
+                lhs.ty(),
+                lhs.to_string(),
+                rhs.to_string()
+            ),
+            Self::Unary { dest, op, operand } => format!(
+                "{} = {op} {} {}",
+                dest.to_string(),
+                operand.ty(),
+                operand.to_string()
+            ),
+            Self::Cast {
+                dest,
+                op,
+                value,
+                ty,
+            } => format!(
+                "{} = {op} {} {} to {ty}",
+                dest.to_string(),
+                value.ty(),
+                value.to_string()
+            ),
+            Self::FieldAddress {
+                dest,
+                src,
+                ty,
+                index,
+            } => format!(
+                "{} = fieldaddr {ty} {}, {index}",
+                dest.to_string(),
+                src.to_string()
+            ),
+            Self::ElementAddress {
+                dest,
+                src,
+                ty,
+                index,
+            } => format!(
+                "{} = elementaddr {ty} {}, {} {}",
+                dest.to_string(),
+                src.to_string(),
+                index.ty(),
+                index.to_string()
+            ),
+            Self::ExtractField { dest, value, index } => format!(
+                "{} = extract {} {}, {index}",
+                dest.to_string(),
+                value.ty(),
+                value.to_string()
+            ),
+            Self::Branch {
+                condition,
+                then_block,
+                else_block,
+            } => format!(
+                "branch {} {}, {}, {}",
+                condition.ty(),
+                condition.to_string(),
+                then_block.to_string(),
+                else_block.to_string()
+            ),
+            Self::Jump { target } => format!("jump {}", target.to_string()),
+            Self::Switch {
+                value,
+                default,
+                cases,
+            } => {
+                let cases = cases
+                    .iter()
+                    .map(|(case, block)| format!("{case}: {}", block.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!(
+                    "switch {} {}, {} [{cases}]",
+                    value.ty(),
+                    value.to_string(),
+                    default.to_string()
+                )
+            }
+            Self::Unreachable => "unreachable".to_string(),
+            Self::Trap => "trap".to_string(),
+            Self::Phi { dest, ty, incoming } => {
+                let incoming = incoming
+                    .iter()
+                    .map(|(value, block)| format!("[{}, {}]", value.to_string(), block.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                format!("{} = phi {ty} {incoming}", dest.to_string())
+            }
+            Self::Call {
+                dest,
+                function,
+                arguments,
+                return_ty,
+            } => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| format!("{} {}", argument.ty(), argument.to_string()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let call = format!("call {return_ty} {function}({arguments})");
+                match dest {
+                    Some(dest) => format!("{} = {call}", dest.to_string()),
+                    None => call,
+                }
+            }
         }
     }
 }
@@ -183,44 +612,49 @@ pub mod utils {
     use crate::{BlockId, Function, Module};
     use std::collections::HashSet;
 
-    /// Gets all the blocks that are related to the given function, ordered in the order of execution
+    /// Gets all the blocks that are reachable from the entry of the given function, in reverse
+    /// postorder. This means that a block always comes after the blocks that jump to it, unless
+    /// the jump is a back edge of a loop, so values are defined before they are used.
     pub fn get_related_blocks(module: &Module, function: &Function) -> Vec<BlockId> {
-        let mut blocks = Vec::new();
-        blocks.push(function.entry.clone());
+        let mut postorder = Vec::new();
         let mut visited: HashSet<BlockId> = HashSet::new();
-        let mut temp = vec![function.entry.clone()];
-        // Now we iterate through the blocks, always starting with the first block, and prepending
-        // to the blocks that have already visited, so the blocks are ordered in the correct order
-        // when executing
-
-        while let Some(block) = temp.pop() {
-            let connected_blocks = get_connected_blocks(module, &block);
-            let connected_blocks = connected_blocks
-                .iter()
-                .filter(|blk| {
-                    let blk = (*blk).clone();
-                    if visited.contains(&blk) {
-                        return false;
+        visited.insert(function.entry.clone());
+        // Each entry is a block, and the successors that still need to be visited
+        let mut stack = vec![(
+            function.entry.clone(),
+            get_connected_blocks(module, &function.entry),
+        )];
+
+        while let Some((block, successors)) = stack.last_mut() {
+            // The last successor is visited first, which puts the first successor first in the
+            // result, so the `then` block of a branch comes before the `else` block
+            match successors.pop() {
+                Some(next) => {
+                    if visited.insert(next.clone()) {
+                        let connected = get_connected_blocks(module, &next);
+                        stack.push((next, connected));
                     }
-                    visited.insert(blk);
-                    true
-                })
-                .cloned();
-            // We reverse the order so that the blocks are ordered in the correct order
-            temp.extend(connected_blocks.rev());
-            // TODO: We need to add it to the blocks, but we need to make sure that we add it in
-            // the correct order, this is difficult because we need to put it in the blocks in
-            // normal order, but reversed order for the stack (because the stack is top to bottom)
+                }
+                None => {
+                    postorder.push(block.clone());
+                    stack.pop();
+                }
+            }
         }
 
-        blocks
+        postorder.reverse();
+        postorder
     }
 
     /// Gets all the blocks that are directly connected to the given block, so for a jump instruction
     /// this will return the block that the jump will jump to, for a branch instruction this will
     /// return the two blocks that the branch will jump to.
     pub fn get_connected_blocks(module: &Module, block: &BlockId) -> Vec<BlockId> {
-        // TODO: Implement this, but we currently dont have control flow
-        Vec::new()
+        module
+            .blocks
+            .get(block)
+            .and_then(|block| block.instructions.last())
+            .map(|instruction| instruction.successors())
+            .unwrap_or_default()
     }
 }
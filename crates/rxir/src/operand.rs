@@ -1,9 +1,21 @@
-use crate::{TempVarId, Type};
+use crate::{GlobalId, TempVarId, Type};
 
 #[derive(Debug, Clone)]
 pub enum Operand {
-    Immediate { ty: Type, value: u64 },
-    TempVar { ty: Type, id: TempVarId },
+    /// A constant, wide enough for 128-bit integers. For floats `value` holds the bits of an `f64`
+    Immediate {
+        ty: Type,
+        value: u128,
+    },
+    TempVar {
+        ty: Type,
+        id: TempVarId,
+    },
+    /// The address of a global constant, `ty` is the pointer type it is used as
+    Global {
+        ty: Type,
+        id: GlobalId,
+    },
 }
 
 impl Operand {
@@ -11,6 +23,7 @@ impl Operand {
         match self {
             Self::Immediate { ty, .. } => ty.clone(),
             Self::TempVar { ty, .. } => ty.clone(),
+            Self::Global { ty, .. } => ty.clone(),
         }
     }
 }
@@ -18,8 +31,17 @@ impl Operand {
 impl ToString for Operand {
     fn to_string(&self) -> String {
         match self {
+            Operand::Immediate {
+                ty: Type::Bool,
+                value,
+            } => (*value != 0).to_string(),
+            Operand::Immediate {
+                ty: ty @ Type::Float(_),
+                value,
+            } => format!("{:?}{ty}", f64::from_bits(*value as u64)),
             Operand::Immediate { ty, value } => format!("{value}{ty}"),
             Operand::TempVar { ty: _, id } => id.to_string(),
+            Operand::Global { ty: _, id } => id.to_string(),
         }
     }
 }